@@ -313,8 +313,8 @@ config.ruleTypes.forEach(ruleTypeDef => {
 	let def = ruleTypeDef.default;
 	switch (typeof def) {
 		case 'undefined': /*ok*/ break;
-		case 'number': ruleTypeDef.default = () => def; break;
-		case 'function': /*ok*/ break;
+		case 'number': ruleTypeDef.default = withExpression(() => def, def); break;
+		case 'function': withExpression(def); break;
 		default: throw Error();
 	}
 })
@@ -329,8 +329,8 @@ config.rules.forEach(rule => {
 		let def = rule[key];
 		switch (typeof def) {
 			case 'undefined': /*ok*/ break;
-			case 'number': rule[key] = () => def; break;
-			case 'function': /*ok*/ break;
+			case 'number': rule[key] = withExpression(() => def, def); break;
+			case 'function': withExpression(def); break;
 			default: throw Error();
 		}
 	})
@@ -345,7 +345,10 @@ config.rules.forEach(rule => {
 				rule[key] = def0;
 			} else {
 				// if both are defined, take the maximum result
-				rule[key] = (h, r) => Math.max(def0(h, r), def1(h, r));
+				rule[key] = withExpression(
+					(h, r) => Math.max(def0(h, r), def1(h, r)),
+					`max(${def0.expression}, ${def1.expression})`
+				);
 			}
 		}
 
@@ -358,6 +361,15 @@ config.rules.forEach(rule => {
 })
 config.rules = ruleLookup;
 
+// adds the rule as expression string (e.g. "10 * (h + r)"), that can be evaluated by the rust renderer
+function withExpression(func, expression) {
+	if (expression === undefined) {
+		expression = func.toString().replace(/^\(h, r\)\s*=>/, '').trim();
+	}
+	func.expression = String(expression);
+	return func;
+}
+
 
 
 module.exports = config;
@@ -0,0 +1,443 @@
+/*
+	File format of the bin tiles (folder_bin), that merge reads to build the next zoom level.
+	All numbers are little endian:
+
+	magic          8 bytes   "WRADTILE"
+	version        u16       currently 3, tiles without curves are written as version 2
+	zoom, x, y     3 × u32   position of the tile
+	size           u32       width and height in pixels, at most MAX_SIZE
+	channel count  u16
+	per channel:
+	   name        u16 length, followed by UTF-8
+	   value_range 2 × f32   the values, that the pixel values 0.0 and 1.0 stand for
+	   fill_value  f32       used for missing tiles when merging
+	   encoding    u8        0: f32
+	   curve       u8        only version 3, see ValueCurve: 0: linear, 1: log followed by the scale as f32,
+	                         2: piecewise followed by the count as u16 and the breakpoints as f32
+	compression    u8        0: none, 1: lz4, 2: zstd
+	data length    u64       bytes of the pixel data, as stored
+	pixel data:
+	   per channel size × size values in the encoding of the channel, row by row.
+	   if compressed, the bytes are grouped by their position in the value first
+	   (byte 0 of all values, then byte 1, …), which compresses smooth fields much better.
+
+	Version 2 is the same without curves, version 1 also without compression and data length.
+	Files without magic are version 0: bincode dumps of GeoImage, as written before.
+	They are migrated when loading. Files with a newer version are refused.
+*/
+
+use crate::encoding::ValueCurve;
+use crate::error::{Error, Result};
+use crate::geoimage::{ChannelInfo, GeoImage};
+use bincode::Options;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"WRADTILE";
+pub const VERSION: u16 = 3;
+// larger tiles are refused, so that a broken header can not allocate gigabytes
+pub const MAX_SIZE: u32 = 8192;
+// a lz4 block can not expand its data more than about 255 times
+const LZ4_MAX_RATIO: usize = 255;
+
+// compression of the pixel data, chosen with "bin_compression"
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+	None,
+	// fast, about half the size
+	Lz4,
+	// slower, smallest files
+	Zstd,
+}
+
+impl Compression {
+	pub fn from_name(name: &str) -> Option<Compression> {
+		return match name {
+			"none" => Some(Compression::None),
+			"lz4" => Some(Compression::Lz4),
+			"zstd" => Some(Compression::Zstd),
+			_ => None,
+		};
+	}
+	fn code(&self) -> u8 {
+		return match self {
+			Compression::None => 0,
+			Compression::Lz4 => 1,
+			Compression::Zstd => 2,
+		};
+	}
+	fn from_code(code: u8) -> std::result::Result<Compression, String> {
+		return match code {
+			0 => Ok(Compression::None),
+			1 => Ok(Compression::Lz4),
+			2 => Ok(Compression::Zstd),
+			code => Err(format!("unknown compression {}", code)),
+		};
+	}
+	fn compress(&self, data: Vec<u8>) -> Vec<u8> {
+		return match self {
+			Compression::None => data,
+			Compression::Lz4 => lz4_flex::compress(&shuffle(&data)),
+			Compression::Zstd => zstd::bulk::compress(&shuffle(&data), 3).expect("zstd compression of a buffer can not fail"),
+		};
+	}
+	// before decompressing, so that the size of the pixel data in the header is not trusted blindly
+	fn check_length(&self, data: &[u8], length: usize) -> std::result::Result<(), String> {
+		let fits = match self {
+			Compression::None => data.len() == length,
+			Compression::Lz4 => length <= (data.len() + 1) * LZ4_MAX_RATIO,
+			Compression::Zstd => matches!(zstd::zstd_safe::get_frame_content_size(data), Ok(Some(size)) if size == length as u64),
+		};
+		if !fits {
+			return Err(format!("pixel data of {} bytes can not hold the {} bytes of the tile", data.len(), length));
+		}
+		return Ok(());
+	}
+	fn decompress(&self, data: &[u8], length: usize) -> std::result::Result<Vec<u8>, String> {
+		let shuffled = match self {
+			Compression::None => return Ok(data.to_vec()),
+			Compression::Lz4 => lz4_flex::decompress(data, length).map_err(|e| format!("lz4: {}", e))?,
+			Compression::Zstd => zstd::bulk::decompress(data, length).map_err(|e| format!("zstd: {}", e))?,
+		};
+		if shuffled.len() != length {
+			return Err(format!("pixel data has {} bytes, expected {}", shuffled.len(), length));
+		}
+		return Ok(unshuffle(&shuffled));
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+	F32,
+}
+
+impl Encoding {
+	fn code(&self) -> u8 {
+		return match self {
+			Encoding::F32 => 0,
+		};
+	}
+	fn from_code(code: u8) -> std::result::Result<Encoding, String> {
+		return match code {
+			0 => Ok(Encoding::F32),
+			code => Err(format!("unknown encoding {}", code)),
+		};
+	}
+	// bytes per pixel
+	fn value_size(&self) -> usize {
+		return match self {
+			Encoding::F32 => 4,
+		};
+	}
+}
+
+// how a loaded tile was stored
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FileInfo {
+	pub version: u16,
+	pub compression: Compression,
+}
+
+struct Header {
+	version: u16,
+	zoom: u32,
+	x: u32,
+	y: u32,
+	size: u32,
+	infos: Vec<ChannelInfo>,
+	encodings: Vec<Encoding>,
+}
+
+pub fn load(filename: &Path) -> Result<(GeoImage, FileInfo)> {
+	let buffer = fs::read(filename).map_err(|e| Error::io(filename, e))?;
+	return decode(&buffer).map_err(|e| Error::format(filename, e));
+}
+
+pub fn encode(image: &GeoImage, compression: Compression) -> Vec<u8> {
+	let mut buffer: Vec<u8> = Vec::new();
+	// older versions of the program can read tiles without curves
+	let with_curves = image.channels.iter().any(|channel| channel.info.curve != ValueCurve::Linear);
+	let version: u16 = if with_curves { VERSION } else { 2 };
+	buffer.extend_from_slice(MAGIC);
+	buffer.extend_from_slice(&version.to_le_bytes());
+	for value in [image.zoom, image.x_offset, image.y_offset, image.size] {
+		buffer.extend_from_slice(&value.to_le_bytes());
+	}
+
+	buffer.extend_from_slice(&(image.channels.len() as u16).to_le_bytes());
+	for channel in &image.channels {
+		let info = &channel.info;
+		let name = info.name.as_bytes();
+		buffer.extend_from_slice(&(name.len() as u16).to_le_bytes());
+		buffer.extend_from_slice(name);
+		for value in [info.value_range[0], info.value_range[1], info.fill_value] {
+			buffer.extend_from_slice(&value.to_le_bytes());
+		}
+		buffer.push(Encoding::F32.code());
+		if with_curves {
+			write_curve(&mut buffer, &info.curve);
+		}
+	}
+
+	let mut data: Vec<u8> = Vec::new();
+	for channel in &image.channels {
+		for value in &channel.data {
+			data.extend_from_slice(&value.to_le_bytes());
+		}
+	}
+	let data = compression.compress(data);
+	buffer.push(compression.code());
+	buffer.extend_from_slice(&(data.len() as u64).to_le_bytes());
+	buffer.extend_from_slice(&data);
+	return buffer;
+}
+
+pub fn decode(buffer: &[u8]) -> std::result::Result<(GeoImage, FileInfo), String> {
+	if !buffer.starts_with(MAGIC) {
+		return Ok((decode_version_0(buffer)?, FileInfo { version: 0, compression: Compression::None }));
+	}
+
+	let mut reader = TileReader { data: buffer, pos: 0 };
+	let Header { version, zoom, x, y, size, infos, encodings } = read_header(&mut reader)?;
+
+	// the image is only allocated, when the pixel data in the buffer can fill it
+	let pixels = (size as usize) * (size as usize);
+	let length = encodings.iter().map(|encoding| pixels * encoding.value_size()).sum();
+	let mut compression = Compression::None;
+	let stored = if version < 2 {
+		reader.read_slice(buffer.len() - reader.pos)?
+	} else {
+		compression = Compression::from_code(reader.read_u8()?)?;
+		let stored_length = reader.read_u64()?;
+		if stored_length != (buffer.len() - reader.pos) as u64 {
+			return Err(format!("pixel data of {} bytes, but {} bytes left in the bin tile", stored_length, buffer.len() - reader.pos));
+		}
+		reader.read_slice(stored_length as usize)?
+	};
+	compression.check_length(stored, length)?;
+	let data = compression.decompress(stored, length)?;
+
+	let mut image = GeoImage::with_channels(size, zoom, x, y, &infos);
+
+	let mut bytes = data.as_slice();
+	for (channel, encoding) in image.channels.iter_mut().zip(encodings) {
+		match encoding {
+			Encoding::F32 => {
+				let (channel_bytes, rest) = bytes.split_at(channel.data.len() * 4);
+				for (value, bytes) in channel.data.iter_mut().zip(channel_bytes.chunks_exact(4)) {
+					*value = f32::from_le_bytes(bytes.try_into().unwrap());
+				}
+				bytes = rest;
+			},
+		}
+	}
+	return Ok((image, FileInfo { version, compression }));
+}
+
+// reads everything up to the pixel data
+fn read_header(reader: &mut TileReader) -> std::result::Result<Header, String> {
+	if reader.read_slice(MAGIC.len())? != MAGIC {
+		return Err("not a bin tile".to_string());
+	}
+	let version = reader.read_u16()?;
+	if version > VERSION {
+		return Err(format!("unsupported bin tile version {}, expected at most {}", version, VERSION));
+	}
+	if version == 0 {
+		return Err("invalid bin tile version 0".to_string());
+	}
+
+	let zoom = reader.read_u32()?;
+	let x = reader.read_u32()?;
+	let y = reader.read_u32()?;
+	let size = reader.read_u32()?;
+	check_position(zoom, x, y, size)?;
+
+	let channel_count = reader.read_u16()?;
+	let mut infos: Vec<ChannelInfo> = Vec::new();
+	let mut encodings: Vec<Encoding> = Vec::new();
+	for _ in 0..channel_count {
+		let length = reader.read_u16()? as usize;
+		let name = String::from_utf8(reader.read_slice(length)?.to_vec()).map_err(|_| "channel name is not UTF-8".to_string())?;
+		let value_range = [reader.read_f32()?, reader.read_f32()?];
+		let fill_value = reader.read_f32()?;
+		encodings.push(Encoding::from_code(reader.read_u8()?)?);
+		let curve = if version >= 3 { read_curve(reader)? } else { ValueCurve::Linear };
+		infos.push(ChannelInfo { name, value_range, fill_value, curve });
+	}
+
+	return Ok(Header { version, zoom, x, y, size, infos, encodings });
+}
+
+fn write_curve(buffer: &mut Vec<u8>, curve: &ValueCurve) {
+	match curve {
+		ValueCurve::Linear => buffer.push(0),
+		ValueCurve::Log { scale } => {
+			buffer.push(1);
+			buffer.extend_from_slice(&scale.to_le_bytes());
+		},
+		ValueCurve::Piecewise { breakpoints } => {
+			buffer.push(2);
+			buffer.extend_from_slice(&(breakpoints.len() as u16).to_le_bytes());
+			for value in breakpoints {
+				buffer.extend_from_slice(&value.to_le_bytes());
+			}
+		},
+	}
+}
+
+fn read_curve(reader: &mut TileReader) -> std::result::Result<ValueCurve, String> {
+	return match reader.read_u8()? {
+		0 => Ok(ValueCurve::Linear),
+		1 => Ok(ValueCurve::Log { scale: reader.read_f32()? }),
+		2 => {
+			let count = reader.read_u16()?;
+			let breakpoints = (0..count).map(|_| reader.read_f32()).collect::<std::result::Result<Vec<f32>, String>>()?;
+			Ok(ValueCurve::Piecewise { breakpoints })
+		},
+		code => Err(format!("unknown curve {}", code)),
+	};
+}
+
+// groups the bytes of the f32 values by their position: all first bytes, all second bytes, …
+fn shuffle(data: &[u8]) -> Vec<u8> {
+	let count = data.len() / 4;
+	let mut result = vec![0u8; data.len()];
+	for (index, value) in data.chunks_exact(4).enumerate() {
+		for (position, byte) in value.iter().enumerate() {
+			result[position * count + index] = *byte;
+		}
+	}
+	return result;
+}
+
+fn unshuffle(data: &[u8]) -> Vec<u8> {
+	let count = data.len() / 4;
+	let mut result = vec![0u8; data.len()];
+	for (index, value) in result.chunks_exact_mut(4).enumerate() {
+		for (position, byte) in value.iter_mut().enumerate() {
+			*byte = data[position * count + index];
+		}
+	}
+	return result;
+}
+
+fn check_position(zoom: u32, x: u32, y: u32, size: u32) -> std::result::Result<(), String> {
+	if (zoom > 31) || (x as u64 >= 1u64 << zoom) || (y as u64 >= 1u64 << zoom) {
+		return Err(format!("invalid tile position {}/{}/{}", zoom, y, x));
+	}
+	if size == 0 || size > MAX_SIZE {
+		return Err(format!("invalid tile size {}", size));
+	}
+	return Ok(());
+}
+
+// the layouts of GeoImage, that were written by bincode without a header:
+// first with 2 fixed channels "dyn" and "fix", then with fill values per channel, then with f64 coordinates
+#[derive(Deserialize)]
+struct ImageVersion0<F, C> {
+	size: u32,
+	zoom: u32,
+	x_offset: u32,
+	y_offset: u32,
+	_x0: F,
+	_y0: F,
+	_pixel_scale: F,
+	channels: Vec<C>,
+}
+
+#[derive(Deserialize)]
+struct ChannelVersion0 {
+	width: u32,
+	height: u32,
+	data: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct FillChannelVersion0 {
+	width: u32,
+	height: u32,
+	fill_value: f32,
+	data: Vec<f32>,
+}
+
+fn decode_version_0(buffer: &[u8]) -> std::result::Result<GeoImage, String> {
+	// like bincode::deserialize, but the whole buffer has to be used, so that a wrong layout fails,
+	// and lengths, that are larger than the buffer, are refused before allocating
+	let options = bincode::DefaultOptions::new().with_fixint_encoding().reject_trailing_bytes().with_limit(buffer.len() as u64);
+
+	let image: ImageVersion0<f64, FillChannelVersion0> = match options.deserialize(buffer) {
+		Ok(image) => image,
+		Err(_) => match options.deserialize::<ImageVersion0<f32, FillChannelVersion0>>(buffer) {
+			Ok(image) => ImageVersion0 {
+				size: image.size, zoom: image.zoom, x_offset: image.x_offset, y_offset: image.y_offset,
+				_x0: 0.0, _y0: 0.0, _pixel_scale: 0.0,
+				channels: image.channels,
+			},
+			Err(_) => {
+				let image: ImageVersion0<f32, ChannelVersion0> = options.deserialize(buffer)
+					.map_err(|_| "neither a bin tile nor a bin tile of version 0".to_string())?;
+				let fill_values = [1.0e6, 0.0];
+				ImageVersion0 {
+					size: image.size, zoom: image.zoom, x_offset: image.x_offset, y_offset: image.y_offset,
+					_x0: 0.0, _y0: 0.0, _pixel_scale: 0.0,
+					channels: image.channels.into_iter().enumerate().map(|(index, channel)| FillChannelVersion0 {
+						width: channel.width,
+						height: channel.height,
+						fill_value: fill_values.get(index).copied().unwrap_or(0.0),
+						data: channel.data,
+					}).collect(),
+				}
+			},
+		},
+	};
+	check_position(image.zoom, image.x_offset, image.y_offset, image.size)?;
+
+	// the value ranges are unknown, so the pixel values stay as they are
+	let names = ["dyn", "fix"];
+	let infos: Vec<ChannelInfo> = image.channels.iter().enumerate().map(|(index, channel)| {
+		let name = if image.channels.len() == 2 { names[index] } else { "" };
+		return ChannelInfo::new(name, [0.0, 1.0], channel.fill_value);
+	}).collect();
+
+	let mut result = GeoImage::with_channels(image.size, image.zoom, image.x_offset, image.y_offset, &infos);
+	for (channel, channel0) in result.channels.iter_mut().zip(image.channels) {
+		if (channel0.width != image.size) || (channel0.height != image.size) || (channel0.data.len() != channel.data.len()) {
+			return Err(format!("channel of {}×{} pixels in a tile of size {}", channel0.width, channel0.height, image.size));
+		}
+		channel.data = channel0.data;
+	}
+	return Ok(result);
+}
+
+struct TileReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl TileReader<'_> {
+	fn read_slice(&mut self, length: usize) -> std::result::Result<&[u8], String> {
+		let slice = match self.data.get(self.pos..self.pos + length) {
+			Some(slice) => slice,
+			None => return Err("truncated bin tile".to_string()),
+		};
+		self.pos += length;
+		return Ok(slice);
+	}
+	fn read_u8(&mut self) -> std::result::Result<u8, String> {
+		return Ok(self.read_slice(1)?[0]);
+	}
+	fn read_u16(&mut self) -> std::result::Result<u16, String> {
+		return Ok(u16::from_le_bytes(self.read_slice(2)?.try_into().unwrap()));
+	}
+	fn read_u32(&mut self) -> std::result::Result<u32, String> {
+		return Ok(u32::from_le_bytes(self.read_slice(4)?.try_into().unwrap()));
+	}
+	fn read_u64(&mut self) -> std::result::Result<u64, String> {
+		return Ok(u64::from_le_bytes(self.read_slice(8)?.try_into().unwrap()));
+	}
+	fn read_f32(&mut self) -> std::result::Result<f32, String> {
+		return Ok(f32::from_le_bytes(self.read_slice(4)?.try_into().unwrap()));
+	}
+}
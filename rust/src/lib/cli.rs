@@ -0,0 +1,232 @@
+/*
+	Command line arguments:
+	windradabstand <command> [arguments]
+	The arguments of a command are one JSON object, that can be given as:
+	- a JSON string, like the Node scripts do: windradabstand render '{"zoom":9,"x0":275,…}'
+	- a config file: windradabstand render --config render.json
+	- flags: windradabstand render --zoom 9 --x0 275 --folder-png tiles/png …
+	  "-" in flag names is read as "_". Values are parsed as JSON if possible, so --bbox [5.8,47.2,15.1,55.1] works.
+	  Flags override the values of the config file.
+	Every command checks its keys, so typos are reported instead of being ignored.
+*/
+
+use crate::error::{Error, Result};
+use json::JsonValue;
+use std::fs;
+use std::path::Path;
+
+pub const EXIT_OK: i32 = 0;
+// error while processing, e.g. an unreadable geometry file
+pub const EXIT_FAILURE: i32 = 1;
+// unknown command or flag, unreadable config
+pub const EXIT_USAGE: i32 = 2;
+// missing or invalid argument values
+pub const EXIT_INVALID: i32 = 3;
+
+// collects the arguments after the command into one JSON object
+pub fn parse_command_line(args: &[String]) -> Result<JsonValue> {
+	if (args.len() == 1) && args[0].trim_start().starts_with('{') {
+		return parse_json_object(&args[0]).map_err(|e| e.context("JSON argument"));
+	}
+
+	let mut config = JsonValue::new_object();
+	let mut flags = JsonValue::new_object();
+	let mut i = 0;
+	while i < args.len() {
+		let arg = &args[i];
+		let name = match arg.strip_prefix("--") {
+			Some(name) if !name.is_empty() => name,
+			_ => return Err(Error::Usage(format!("unexpected argument \"{}\"", arg))),
+		};
+
+		let (name, value) = match name.split_once('=') {
+			Some((name, value)) => (name, value.to_string()),
+			None => {
+				i += 1;
+				match args.get(i) {
+					Some(value) => (name, value.clone()),
+					None => return Err(Error::Usage(format!("missing value for --{}", name))),
+				}
+			},
+		};
+		i += 1;
+
+		if name == "config" {
+			let content = fs::read_to_string(&value)
+				.map_err(|e| Error::Usage(format!("can not read config file \"{}\": {}", value, e)))?;
+			config = parse_json_object(&content).map_err(|e| e.context(&value))?;
+		} else {
+			flags[name.replace('-', "_")] = parse_flag_value(&value);
+		}
+	}
+
+	for (name, value) in flags.entries() {
+		config[name] = value.clone();
+	}
+	return Ok(config);
+}
+
+fn parse_json_object(text: &str) -> Result<JsonValue> {
+	let value = json::parse(text).map_err(|e| Error::Usage(format!("invalid JSON: {}", e)))?;
+	if !value.is_object() {
+		return Err(Error::Usage("expected a JSON object".to_string()));
+	}
+	return Ok(value);
+}
+
+// numbers, arrays and objects are read as JSON, everything else is a string
+fn parse_flag_value(value: &str) -> JsonValue {
+	return match json::parse(value) {
+		Ok(parsed) if parsed.is_number() || parsed.is_array() || parsed.is_object() || parsed.is_boolean() => parsed,
+		_ => JsonValue::from(value),
+	};
+}
+
+// reports keys, that are not used by the command
+pub fn check_keys(obj: &JsonValue, known: &[&str]) -> Result<()> {
+	for (name, _) in obj.entries() {
+		if !known.contains(&name) {
+			return Err(Error::Usage(format!("unknown argument \"{}\", expected one of: {}", name, known.join(", "))));
+		}
+	}
+	return Ok(());
+}
+
+pub fn get_str(obj: &JsonValue, name: &str) -> Result<String> {
+	return match &obj[name] {
+		JsonValue::Null => Err(missing(name)),
+		value => match value.as_str() {
+			Some(value) => Ok(value.to_string()),
+			None => Err(Error::Invalid(format!("\"{}\" must be a string, found {}", name, value))),
+		},
+	};
+}
+
+pub fn get_u32(obj: &JsonValue, name: &str) -> Result<u32> {
+	return match &obj[name] {
+		JsonValue::Null => Err(missing(name)),
+		value => match value.as_u32() {
+			Some(value) => Ok(value),
+			None => Err(Error::Invalid(format!("\"{}\" must be a positive integer, found {}", name, value))),
+		},
+	};
+}
+
+pub fn get_usize_or(obj: &JsonValue, name: &str, default: usize) -> Result<usize> {
+	return match &obj[name] {
+		JsonValue::Null => Ok(default),
+		value => match value.as_usize() {
+			Some(value) => Ok(value),
+			None => Err(Error::Invalid(format!("\"{}\" must be a positive integer, found {}", name, value))),
+		},
+	};
+}
+
+pub fn get_bool_or(obj: &JsonValue, name: &str, default: bool) -> Result<bool> {
+	return match &obj[name] {
+		JsonValue::Null => Ok(default),
+		value => match value.as_bool() {
+			Some(value) => Ok(value),
+			None => Err(Error::Invalid(format!("\"{}\" must be true or false, found {}", name, value))),
+		},
+	};
+}
+
+pub fn get_u8_or(obj: &JsonValue, name: &str, default: u8) -> Result<u8> {
+	return match &obj[name] {
+		JsonValue::Null => Ok(default),
+		value => match value.as_u8() {
+			Some(value) => Ok(value),
+			None => Err(Error::Invalid(format!("\"{}\" must be an integer between 0 and 255, found {}", name, value))),
+		},
+	};
+}
+
+pub fn get_f32(obj: &JsonValue, name: &str) -> Result<f32> {
+	return match &obj[name] {
+		JsonValue::Null => Err(missing(name)),
+		value => match value.as_f32() {
+			Some(value) if value.is_finite() => Ok(value),
+			_ => Err(Error::Invalid(format!("\"{}\" must be a number, found {}", name, value))),
+		},
+	};
+}
+
+pub fn get_f32_or(obj: &JsonValue, name: &str, default: f32) -> Result<f32> {
+	if obj[name].is_null() {
+		return Ok(default);
+	}
+	return get_f32(obj, name);
+}
+
+pub fn get_f32_list_or(obj: &JsonValue, name: &str, default: &[f32]) -> Result<Vec<f32>> {
+	let value = &obj[name];
+	if value.is_null() {
+		return Ok(default.to_vec());
+	}
+	let invalid = || Error::Invalid(format!("\"{}\" must be a list of numbers, found {}", name, value));
+	if !value.is_array() || value.is_empty() {
+		return Err(invalid());
+	}
+	return value.members().map(|member| member.as_f32().filter(|v| v.is_finite()).ok_or_else(invalid)).collect();
+}
+
+pub fn get_usize_list_or(obj: &JsonValue, name: &str, default: &[usize]) -> Result<Vec<usize>> {
+	let value = &obj[name];
+	if value.is_null() {
+		return Ok(default.to_vec());
+	}
+	let invalid = || Error::Invalid(format!("\"{}\" must be a list of positive integers, found {}", name, value));
+	if !value.is_array() {
+		return Err(invalid());
+	}
+	return value.members().map(|member| member.as_usize().ok_or_else(invalid)).collect();
+}
+
+// [x_min, y_min, x_max, y_max] in degrees
+pub fn get_bbox(obj: &JsonValue, name: &str) -> Result<[f64; 4]> {
+	let value = &obj[name];
+	if value.is_null() {
+		return Err(missing(name));
+	}
+	let invalid = || Error::Invalid(format!("\"{}\" must be [x_min, y_min, x_max, y_max] in degrees, found {}", name, value));
+
+	if !value.is_array() || (value.len() != 4) {
+		return Err(invalid());
+	}
+	let mut bbox = [0.0; 4];
+	for (i, coordinate) in bbox.iter_mut().enumerate() {
+		*coordinate = value[i].as_f64().ok_or_else(invalid)?;
+	}
+
+	let [x_min, y_min, x_max, y_max] = bbox;
+	if (x_min >= x_max) || (y_min >= y_max) || (x_min < -180.0) || (x_max > 180.0) || (y_min < -85.0) || (y_max > 85.0) {
+		return Err(invalid());
+	}
+	return Ok(bbox);
+}
+
+pub fn check_power_of_two(name: &str, value: u32) -> Result<()> {
+	if !value.is_power_of_two() {
+		return Err(Error::Invalid(format!("\"{}\" must be a power of two, found {}", name, value)));
+	}
+	return Ok(());
+}
+
+pub fn check_min_max(name_min: &str, min: f32, name_max: &str, max: f32) -> Result<()> {
+	if min >= max {
+		return Err(Error::Invalid(format!("\"{}\" ({}) must be smaller than \"{}\" ({})", name_min, min, name_max, max)));
+	}
+	return Ok(());
+}
+
+pub fn check_file(name: &str, filename: &Path) -> Result<()> {
+	if !filename.is_file() {
+		return Err(Error::Invalid(format!("\"{}\": file not found: {}", name, filename.display())));
+	}
+	return Ok(());
+}
+
+fn missing(name: &str) -> Error {
+	return Error::Invalid(format!("missing argument \"{}\"", name));
+}
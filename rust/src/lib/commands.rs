@@ -0,0 +1,593 @@
+/*
+	The commands of the windradabstand binary:
+	- render: GeoJSON/GeoPackage/FlatGeobuf to Signed Distance Field (Image) as PNG Tiles:
+		1. load geometries of every layer inside the bbox
+		2. add segments to R-Tree
+		3. for every pixel and every distance layer:
+			3.1. calc distance to nearest segment in meters
+			3.2. limit distance to the max_distance of the layer
+			3.3. if inside polygon: negative distance
+		4. save as png tiles, one channel per layer
+	- merge: merges the 4 bin thumbnails of the next zoom level into one tile
+	  (or renders it again with "downsampling": "recompute")
+	- pyramid: renders the complete tile pyramid of a bbox:
+		1. load GeoJSON/GeoPackage/FlatGeobuf of every layer once
+		2. render all tiles of zoom level "max_zoom - combined_render_levels",
+		   each one as a block of 2^combined_render_levels × 2^combined_render_levels tiles
+		3. merge the tiles level by level down to zoom level 0
+		Tiles that already exist in the output are skipped, so an interrupted run can be resumed (not with PMTiles).
+	- inspect: prints position and channel statistics of a bin tile
+	- contours: polygons of a bin tile closer than some distances as GeoJSON or vector tile, see contours.rs
+	- statistics: buildable area per Bundesland or municipality for a sweep of distances, see statistics.rs
+*/
+
+use crate::bintile::{self, Compression};
+use crate::cli::*;
+use crate::contours::*;
+use crate::encoding::*;
+use crate::error::{Error, Result};
+use crate::geoimage::*;
+use crate::geometry::Bbox;
+use crate::layers::*;
+use crate::output::*;
+use crate::statistics::*;
+use crate::tiles::*;
+use json::JsonValue;
+use std::fs;
+use std::io::Write;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const USAGE: &str = "\
+usage: windradabstand <command> '{json}'
+       windradabstand <command> [--config file.json] [--name value …]
+
+commands:
+   render    renders a block of n×n tiles
+             layers (or filename_geo_dyn, filename_geo_fix, min_distance, max_distance),
+             distance_model, renderer, coverage_distance, curve, output, folder_bin, zoom, x0, y0, n, size, threads
+   merge     merges the 4 tiles of the next zoom level into one tile
+             output, folder_bin, zoom, x0, y0, size, threads,
+             layers (or …), distance_model, renderer, coverage_distance and curve only with downsampling recompute
+   pyramid   renders the tile pyramid of a bbox
+             layers (or …), distance_model, renderer, coverage_distance, curve, output, folder_bin, bbox, max_zoom,
+             combined_render_levels, size, threads
+   inspect   prints position and channel statistics of a bin tile
+             filename
+   contours  polygons of a bin tile closer than some distances, as .geojson or vector tile (.mvt or .pbf)
+             filename, output, channel (default 0), distances (default [400, 600, 800, 1000, 1500])
+   statistics  buildable km² per region of a VG250 GeoJSON (e.g. bundeslaender.geojson) for every distance,
+             using the bin tiles of a zoom level
+             folder_bin, zoom, regions, channel (default 0), distances (default like contours),
+             exclude_channels (coverage, also with coverage_distance, default []), output (.json or .tsv, default: print), threads
+
+options of render, merge and pyramid:
+   output              folder, .mbtiles or .pmtiles file (only pyramid), \"folder_png\" is the old name
+   image_format        of the tiles in output: png (default) or webp
+   webp_near_lossless  100 (default) is lossless, lower values give smaller files
+   pixel_encoding      rgb8 (default, 3 channels per image), rg16, gray16 (only png), terrarium or mapbox,
+                       render and pyramid save how to decode it as metadata.json
+   bin_compression     of the intermediate bin tiles: none (default), lz4 or zstd
+   skip_uniform_tiles  true: tiles with only fill values (e.g. far away from all geometries)
+                       are not saved, a missing tile means uniform default (default: false)
+   downsampling        how lower zoom levels are calculated: box (default, mean), min (conservative:
+                       the nearest distance and the largest coverage of the block),
+                       center (value at the pixel center) or recompute (rendered again from the layers)
+   renderer            of distances: tree (default, nearest segment per pixel) or edt (distance transform
+                       of the rasterised geometries, faster for dense buildings, at most 1/4 pixel off), also per layer
+   coverage_distance   [min, max] in meters: coverage layers (e.g. filename_geo_fix) are signed distances
+                       (negative inside) instead of the covered share of the pixel, a layer can set its own
+                       min_distance and max_distance
+   curve               how distances are spread over the pixel values of rgb8, rg16 and gray16 images:
+                       linear (default), {\"type\": \"log\", \"scale\": 250} (fine near 0, coarse far away)
+                       or {\"type\": \"piecewise\", \"breakpoints\": [0, 1000, 2500]} (equal shares), also per layer,
+                       written to metadata.json and the bin tiles, so it can be inverted
+
+exit codes: 0 ok, 1 error while processing, 2 invalid command line, 3 invalid arguments";
+
+// the layer arguments of render and pyramid, see parse_layers
+const LAYER_KEYS: [&str; 9] = ["layers", "distance_model", "renderer", "coverage_distance", "curve", "filename_geo_dyn", "filename_geo_fix", "min_distance", "max_distance"];
+
+// tiles have at most 2^MAX_ZOOM × 2^MAX_ZOOM pixels per axis
+const MAX_ZOOM: u32 = 30;
+
+enum Command {
+	Render(RenderArguments),
+	Merge(MergeArguments),
+	Pyramid(PyramidArguments),
+	Inspect(InspectArguments),
+	Contours(ContoursArguments),
+	Statistics(StatisticsArguments),
+}
+
+struct RenderArguments {
+	layers: Vec<Layer>,
+	options: TileOptions,
+	tile: TileCoord,
+	n: u32,
+}
+
+struct MergeArguments {
+	// only with Downsampling::Recompute
+	layers: Vec<Layer>,
+	options: TileOptions,
+	tile: TileCoord,
+}
+
+struct PyramidArguments {
+	layers: Vec<Layer>,
+	options: TileOptions,
+	bbox: [f64; 4],
+	max_zoom: u32,
+	combined_render_levels: u32,
+	threads: usize,
+}
+
+struct InspectArguments {
+	filename: PathBuf,
+}
+
+struct StatisticsArguments {
+	folder_bin: PathBuf,
+	zoom: u32,
+	regions: PathBuf,
+	sweep: Sweep,
+	output: Option<PathBuf>,
+	threads: usize,
+}
+
+struct ContoursArguments {
+	filename: PathBuf,
+	output: PathBuf,
+	channel: usize,
+	distances: Vec<f32>,
+}
+
+// runs a command line (without the program name) and returns the exit code
+pub fn run(args: &[String]) -> i32 {
+	let command = match args.first() {
+		Some(command) => command.as_str(),
+		None => {
+			eprintln!("{}", USAGE);
+			return EXIT_USAGE;
+		},
+	};
+	if ["help", "-h", "--help"].contains(&command) || args.iter().any(|arg| arg == "--help") {
+		println!("{}", USAGE);
+		return EXIT_OK;
+	}
+
+	let command = match parse_command(command, &args[1..]) {
+		Ok(command) => command,
+		Err(error) => {
+			eprintln!("error: {}", error);
+			if let Error::Usage(_) = error {
+				eprintln!("see: windradabstand --help");
+			}
+			return error.exit_code();
+		},
+	};
+
+	// panics are bugs, their message is printed by the panic handler
+	return match panic::catch_unwind(panic::AssertUnwindSafe(|| execute(command))) {
+		Ok(Ok(())) => EXIT_OK,
+		Ok(Err(error)) => {
+			eprintln!("error: {}", error);
+			error.exit_code()
+		},
+		Err(_) => EXIT_FAILURE,
+	};
+}
+
+fn parse_command(command: &str, args: &[String]) -> Result<Command> {
+	let obj = parse_command_line(args)?;
+	return match command {
+		"render" => Ok(Command::Render(parse_render(&obj)?)),
+		"merge" => Ok(Command::Merge(parse_merge(&obj)?)),
+		"pyramid" => Ok(Command::Pyramid(parse_pyramid(&obj)?)),
+		"inspect" => Ok(Command::Inspect(parse_inspect(&obj)?)),
+		"contours" => Ok(Command::Contours(parse_contours(&obj)?)),
+		"statistics" => Ok(Command::Statistics(parse_statistics(&obj)?)),
+		command => Err(Error::Usage(format!("unknown command \"{}\"", command))),
+	};
+}
+
+fn execute(command: Command) -> Result<()> {
+	return match command {
+		Command::Render(arguments) => render(arguments),
+		Command::Merge(arguments) => merge(arguments),
+		Command::Pyramid(arguments) => pyramid(arguments),
+		Command::Inspect(arguments) => inspect(arguments),
+		Command::Contours(arguments) => contours(arguments),
+		Command::Statistics(arguments) => statistics(arguments),
+	};
+}
+
+fn render(mut arguments: RenderArguments) -> Result<()> {
+	let bbox = tile_bbox(arguments.tile);
+	for layer in arguments.layers.iter_mut() {
+		layer.load(&bbox)?;
+	}
+	let output = open_output(&arguments.options.output, arguments.options.image_format, arguments.options.pixel_encoding)?;
+	output.write_metadata(&layer_metadata(&arguments.layers, &arguments.options))?;
+	render_tile(&arguments.layers, &arguments.options, output.as_ref(), arguments.tile, arguments.n)?;
+	return output.finish();
+}
+
+fn merge(mut arguments: MergeArguments) -> Result<()> {
+	let bbox = tile_bbox(arguments.tile);
+	for layer in arguments.layers.iter_mut() {
+		layer.load(&bbox)?;
+	}
+	let output = open_output(&arguments.options.output, arguments.options.image_format, arguments.options.pixel_encoding)?;
+	merge_tile(&arguments.layers, &arguments.options, output.as_ref(), arguments.tile)?;
+	return output.finish();
+}
+
+fn pyramid(mut arguments: PyramidArguments) -> Result<()> {
+	let [x_min, y_min, x_max, y_max] = arguments.bbox;
+	let bbox = Bbox::from_coordinates(x_min, y_min, x_max, y_max);
+
+	for layer in arguments.layers.iter_mut() {
+		layer.load(&bbox)?;
+	}
+
+	let options = &arguments.options;
+	let output = open_output(&options.output, options.image_format, options.pixel_encoding)?;
+	let output = output.as_ref();
+	output.write_metadata(&layer_metadata(&arguments.layers, options))?;
+	let render_zoom = arguments.max_zoom - arguments.combined_render_levels;
+	let n = 2u32.pow(arguments.combined_render_levels);
+
+	let todos = get_todos(&arguments, output, render_zoom)?;
+	println!("process level {} using: render", render_zoom);
+	process_level(&todos, arguments.threads, |tile| {
+		return render_tile(&arguments.layers, options, output, *tile, n);
+	})?;
+
+	for zoom in (0..render_zoom).rev() {
+		let todos = get_todos(&arguments, output, zoom)?;
+		println!("process level {} using: merge", zoom);
+		process_level(&todos, arguments.threads, |tile| {
+			return merge_tile(&arguments.layers, options, output, *tile);
+		})?;
+	}
+	output.finish()?;
+
+	println!("Finished");
+	return Ok(());
+}
+
+fn inspect(arguments: InspectArguments) -> Result<()> {
+	let (image, file_info) = bintile::load(&arguments.filename)?;
+	let migrated = if file_info.version < bintile::VERSION { " (migrated)" } else { "" };
+	println!("format:   bin tile version {}{}, compression {:?}", file_info.version, migrated, file_info.compression);
+	print!("{}", image);
+	return Ok(());
+}
+
+fn contours(arguments: ContoursArguments) -> Result<()> {
+	let image = GeoImage::load(&arguments.filename)?;
+	let contours = image.contours(arguments.channel, &arguments.distances)?;
+	let data = if is_geojson(&arguments.output) {
+		contours_to_geojson(&image, &contours).dump().into_bytes()
+	} else {
+		contours_to_mvt(&image, &contours)
+	};
+	return fs::write(&arguments.output, data).map_err(|e| Error::io(&arguments.output, e));
+}
+
+fn statistics(arguments: StatisticsArguments) -> Result<()> {
+	let regions = load_regions(&arguments.regions)?;
+	let statistics = area_statistics(&arguments.folder_bin, arguments.zoom, &regions, &arguments.sweep, arguments.threads)?;
+	if statistics.missing_tiles > 0 {
+		eprintln!("{} of {} tiles are missing, they are counted as uniform default", statistics.missing_tiles, statistics.tiles);
+	}
+
+	let distances = &arguments.sweep.distances;
+	let data = match &arguments.output {
+		Some(output) if output.extension().is_some_and(|extension| extension == "json") => {
+			let regions:Vec<JsonValue> = statistics.regions.iter().map(|region| json::object! {
+				ags: region.ags.clone(),
+				name: region.name.clone(),
+				area: region.area,
+				buildable: distances.iter().zip(&region.buildable).map(|(distance, area)| json::object! { distance: *distance, area: *area }).collect::<Vec<_>>(),
+			}).collect();
+			json::object! { unit: "km²", regions: regions }.pretty(1)
+		},
+		_ => {
+			// one line per region, areas in km²
+			let mut table = format!("ags\tname\tarea{}\n", distances.iter().map(|distance| format!("\t{}", distance)).collect::<String>());
+			for region in &statistics.regions {
+				let buildable:String = region.buildable.iter().map(|area| format!("\t{:.3}", area)).collect();
+				table += &format!("{}\t{}\t{:.3}{}\n", region.ags, region.name, region.area, buildable);
+			}
+			table
+		},
+	};
+	return match &arguments.output {
+		Some(output) => fs::write(output, data).map_err(|e| Error::io(output, e)),
+		None => {
+			print!("{}", data);
+			Ok(())
+		},
+	};
+}
+
+// how to decode the channels of all layers, merge uses the one written by render
+fn layer_metadata(layers: &[Layer], options: &TileOptions) -> JsonValue {
+	let infos:Vec<ChannelInfo> = layers.iter().flat_map(|layer| layer.channel_infos()).collect();
+	return tile_metadata(options.image_format, options.pixel_encoding, &infos);
+}
+
+// all tiles of a zoom level, that are not rendered yet
+fn get_todos(arguments: &PyramidArguments, output: &dyn TileOutput, zoom: u32) -> Result<Vec<TileCoord>> {
+	let mut todos:Vec<TileCoord> = Vec::new();
+	for tile in list_tiles(&arguments.bbox, zoom) {
+		if !output.contains(tile)? {
+			todos.push(tile);
+		}
+	}
+	return Ok(todos);
+}
+
+// stops at the first error, tiles that are already running are finished
+fn process_level<F>(todos: &[TileCoord], threads: usize, callback: F) -> Result<()> where F: Fn(&TileCoord) -> Result<()> + Sync {
+	let finished = AtomicUsize::new(0);
+	let first_error: Mutex<Option<Error>> = Mutex::new(None);
+
+	run_queue(todos, threads, |_index, tile| {
+		if first_error.lock().unwrap().is_some() {
+			return;
+		}
+		if let Err(error) = callback(tile) {
+			let _error = first_error.lock().unwrap().get_or_insert(error);
+			return;
+		}
+		let count = finished.fetch_add(1, Ordering::Relaxed) + 1;
+		print!("\r   {}/{}", count, todos.len());
+		let _result = std::io::stdout().flush();
+	});
+
+	println!();
+	return match first_error.into_inner().unwrap() {
+		Some(error) => Err(error),
+		None => Ok(()),
+	};
+}
+
+fn parse_render(obj: &JsonValue) -> Result<RenderArguments> {
+	let mut known = vec!["output", "folder_png", "folder_bin", "image_format", "webp_near_lossless", "pixel_encoding", "bin_compression", "skip_uniform_tiles", "downsampling", "zoom", "x0", "y0", "n", "size", "threads"];
+	known.extend(LAYER_KEYS);
+	check_keys(obj, &known)?;
+
+	let tile = parse_tile(obj)?;
+	let n = get_u32(obj, "n")?;
+	check_power_of_two("n", n)?;
+	if tile.zoom + n.trailing_zeros() > MAX_ZOOM {
+		return Err(Error::Invalid(format!("\"zoom\" + log2(\"n\") must be at most {}", MAX_ZOOM)));
+	}
+
+	let options = parse_tile_options(obj, get_usize_or(obj, "threads", 0)?)?;
+	check_single_tile_output(&options)?;
+
+	return Ok(RenderArguments {
+		layers: parse_layers(obj)?,
+		options,
+		tile,
+		n,
+	});
+}
+
+fn parse_merge(obj: &JsonValue) -> Result<MergeArguments> {
+	let mut known = vec!["output", "folder_png", "folder_bin", "image_format", "webp_near_lossless", "pixel_encoding", "bin_compression", "skip_uniform_tiles", "downsampling", "zoom", "x0", "y0", "size", "threads"];
+	known.extend(LAYER_KEYS);
+	check_keys(obj, &known)?;
+
+	let options = parse_tile_options(obj, get_usize_or(obj, "threads", 0)?)?;
+	check_single_tile_output(&options)?;
+
+	// the bin thumbnails are enough for all other downsamplings
+	let layers = if options.downsampling == Downsampling::Recompute {
+		parse_layers(obj)?
+	} else {
+		if let Some(key) = LAYER_KEYS.iter().find(|key| !obj[**key].is_null()) {
+			return Err(Error::Invalid(format!("\"{}\" is only used with \"downsampling\": \"recompute\"", key)));
+		}
+		Vec::new()
+	};
+
+	return Ok(MergeArguments {
+		layers,
+		options,
+		tile: parse_tile(obj)?,
+	});
+}
+
+// render and merge add tiles to an existing output, PMTiles are written at once
+fn check_single_tile_output(options: &TileOptions) -> Result<()> {
+	if OutputKind::from_path(&options.output) == OutputKind::Pmtiles {
+		return Err(Error::Invalid("\"output\": PMTiles can only be written by pyramid, use a folder or .mbtiles".to_string()));
+	}
+	return Ok(());
+}
+
+fn parse_pyramid(obj: &JsonValue) -> Result<PyramidArguments> {
+	let mut known = vec!["output", "folder_png", "folder_bin", "image_format", "webp_near_lossless", "pixel_encoding", "bin_compression", "skip_uniform_tiles", "downsampling", "bbox", "max_zoom", "combined_render_levels", "size", "threads"];
+	known.extend(LAYER_KEYS);
+	check_keys(obj, &known)?;
+
+	let max_zoom = get_u32(obj, "max_zoom")?;
+	let combined_render_levels = get_u32(obj, "combined_render_levels")?;
+	if max_zoom > MAX_ZOOM {
+		return Err(Error::Invalid(format!("\"max_zoom\" must be at most {}, found {}", MAX_ZOOM, max_zoom)));
+	}
+	if combined_render_levels > max_zoom {
+		return Err(Error::Invalid(format!("\"combined_render_levels\" ({}) must not be larger than \"max_zoom\" ({})", combined_render_levels, max_zoom)));
+	}
+
+	return Ok(PyramidArguments {
+		layers: parse_layers(obj)?,
+		// the work queue is already using all threads
+		options: parse_tile_options(obj, 1)?,
+		bbox: get_bbox(obj, "bbox")?,
+		max_zoom,
+		combined_render_levels,
+		threads: get_usize_or(obj, "threads", 0)?,
+	});
+}
+
+fn parse_inspect(obj: &JsonValue) -> Result<InspectArguments> {
+	check_keys(obj, &["filename"])?;
+	return Ok(InspectArguments { filename: get_bin_filename(obj)? });
+}
+
+fn get_bin_filename(obj: &JsonValue) -> Result<PathBuf> {
+	let filename = PathBuf::from(get_str(obj, "filename")?);
+	check_file("filename", &filename)?;
+	if filename.extension().is_none_or(|extension| extension != "bin") {
+		return Err(Error::Invalid(format!("\"filename\" must be a bin tile, found {}", filename.display())));
+	}
+	return Ok(filename);
+}
+
+fn parse_statistics(obj: &JsonValue) -> Result<StatisticsArguments> {
+	check_keys(obj, &["folder_bin", "zoom", "regions", "channel", "distances", "exclude_channels", "output", "threads"])?;
+
+	let zoom = get_u32(obj, "zoom")?;
+	if zoom > MAX_ZOOM {
+		return Err(Error::Invalid(format!("\"zoom\" must be at most {}, found {}", MAX_ZOOM, zoom)));
+	}
+	let regions = PathBuf::from(get_str(obj, "regions")?);
+	check_file("regions", &regions)?;
+	let output = match obj["output"].is_null() {
+		true => None,
+		false => Some(PathBuf::from(get_str(obj, "output")?)),
+	};
+	return Ok(StatisticsArguments {
+		folder_bin: PathBuf::from(get_str(obj, "folder_bin")?),
+		zoom,
+		regions,
+		sweep: Sweep {
+			channel: get_usize_or(obj, "channel", 0)?,
+			distances: get_f32_list_or(obj, "distances", &DEFAULT_DISTANCES)?,
+			exclude_channels: get_usize_list_or(obj, "exclude_channels", &[])?,
+		},
+		output,
+		threads: get_usize_or(obj, "threads", 0)?,
+	});
+}
+
+fn parse_contours(obj: &JsonValue) -> Result<ContoursArguments> {
+	check_keys(obj, &["filename", "output", "channel", "distances"])?;
+
+	let filename = get_bin_filename(obj)?;
+	let output = PathBuf::from(get_str(obj, "output")?);
+	if !is_geojson(&output) && output.extension().is_none_or(|extension| (extension != "mvt") && (extension != "pbf")) {
+		return Err(Error::Invalid(format!("\"output\" must be a .geojson, .mvt or .pbf file, found {}", output.display())));
+	}
+	return Ok(ContoursArguments {
+		filename,
+		output,
+		channel: get_usize_or(obj, "channel", 0)?,
+		distances: get_f32_list_or(obj, "distances", &DEFAULT_DISTANCES)?,
+	});
+}
+
+fn is_geojson(path: &Path) -> bool {
+	return path.extension().is_some_and(|extension| extension == "geojson");
+}
+
+fn parse_tile(obj: &JsonValue) -> Result<TileCoord> {
+	let zoom = get_u32(obj, "zoom")?;
+	if zoom > MAX_ZOOM {
+		return Err(Error::Invalid(format!("\"zoom\" must be at most {}, found {}", MAX_ZOOM, zoom)));
+	}
+	let x = get_u32(obj, "x0")?;
+	let y = get_u32(obj, "y0")?;
+	let count = 1u64 << zoom;
+	if (x as u64 >= count) || (y as u64 >= count) {
+		return Err(Error::Invalid(format!("\"x0\" and \"y0\" must be smaller than 2^zoom ({}), found {}, {}", count, x, y)));
+	}
+	return Ok(TileCoord { zoom, x, y });
+}
+
+fn parse_tile_options(obj: &JsonValue, threads: usize) -> Result<TileOptions> {
+	let size = get_u32(obj, "size")?;
+	check_power_of_two("size", size)?;
+	// the bin thumbnails have half the size
+	if size < 2 {
+		return Err(Error::Invalid(format!("\"size\" must be at least 2, found {}", size)));
+	}
+
+	let bin_compression = match &obj["bin_compression"] {
+		JsonValue::Null => Compression::None,
+		value => match value.as_str().and_then(Compression::from_name) {
+			Some(compression) => compression,
+			None => return Err(Error::Invalid(format!("unknown bin_compression {}, expected none, lz4 or zstd", value))),
+		},
+	};
+
+	let image_format = match &obj["image_format"] {
+		JsonValue::Null => TileFormat::Png,
+		value => match value.as_str() {
+			Some("png") => TileFormat::Png,
+			Some("webp") => {
+				let near_lossless = get_u8_or(obj, "webp_near_lossless", 100)?;
+				if near_lossless > 100 {
+					return Err(Error::Invalid(format!("\"webp_near_lossless\" must be between 0 and 100, found {}", near_lossless)));
+				}
+				TileFormat::Webp { near_lossless }
+			},
+			_ => return Err(Error::Invalid(format!("unknown image_format {}, expected png or webp", value))),
+		},
+	};
+	if !obj["webp_near_lossless"].is_null() && !matches!(image_format, TileFormat::Webp { .. }) {
+		return Err(Error::Invalid("\"webp_near_lossless\" needs \"image_format\": \"webp\"".to_string()));
+	}
+
+	let pixel_encoding = match &obj["pixel_encoding"] {
+		JsonValue::Null => PixelEncoding::Rgb8,
+		value => match value.as_str().and_then(PixelEncoding::from_name) {
+			Some(encoding) => encoding,
+			None => return Err(Error::Invalid(format!("unknown pixel_encoding {}, expected rgb8, rg16, gray16, terrarium or mapbox", value))),
+		},
+	};
+	match (image_format, pixel_encoding) {
+		(TileFormat::Webp { .. }, PixelEncoding::Gray16) => {
+			return Err(Error::Invalid("\"pixel_encoding\": gray16 needs \"image_format\": \"png\"".to_string()));
+		},
+		(TileFormat::Webp { near_lossless }, encoding) if near_lossless < 100 && !encoding.is_8_bit() => {
+			return Err(Error::Invalid(format!("\"webp_near_lossless\" below 100 would destroy the low bytes of \"pixel_encoding\": {}", encoding.name())));
+		},
+		_ => {},
+	}
+
+	let downsampling = match &obj["downsampling"] {
+		JsonValue::Null => Downsampling::Box,
+		value => match value.as_str().and_then(Downsampling::from_name) {
+			Some(downsampling) => downsampling,
+			None => return Err(Error::Invalid(format!("unknown downsampling {}, expected box, min, center or recompute", value))),
+		},
+	};
+
+	let output = match (obj["output"].is_null(), obj["folder_png"].is_null()) {
+		(false, false) => return Err(Error::Invalid("use either \"output\" or \"folder_png\"".to_string())),
+		(true, false) => get_str(obj, "folder_png")?,
+		_ => get_str(obj, "output")?,
+	};
+
+	return Ok(TileOptions {
+		output: PathBuf::from(output),
+		folder_bin: PathBuf::from(get_str(obj, "folder_bin")?),
+		size,
+		threads,
+		image_format,
+		pixel_encoding,
+		bin_compression,
+		skip_uniform_tiles: get_bool_or(obj, "skip_uniform_tiles", false)?,
+		downsampling,
+	});
+}
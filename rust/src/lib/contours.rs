@@ -0,0 +1,308 @@
+/*
+	Contour polygons of a distance channel, e.g. everything closer than 400, 600, 800, 1000 and 1500 m:
+	- marching squares on the pixel centers, the crossings are interpolated linearly
+	- the image is surrounded by pixels "far away", so that the rings are closed along the border of the image
+	- saddle cells are decided by the mean of the 4 corners
+	Rings are in pixel coordinates ((0, 0) is the top left corner, (size, size) the bottom right one),
+	exterior rings are clockwise on screen, holes counterclockwise.
+	They are written as GeoJSON or as Mapbox Vector Tile (protobuf, layer "contours", extent 4096).
+*/
+
+use crate::error::{Error, Result};
+use crate::geoimage::GeoImage;
+use crate::output::write_varint;
+use json::JsonValue;
+use std::collections::HashMap;
+
+// the distances of the vector layer in meters
+pub const DEFAULT_DISTANCES: [f32; 5] = [400.0, 600.0, 800.0, 1000.0, 1500.0];
+
+const MVT_EXTENT: u32 = 4096;
+
+pub type Ring = Vec<[f64; 2]>;
+
+pub struct Contour {
+	pub distance: f32,
+	// every polygon is the exterior ring followed by its holes
+	pub polygons: Vec<Vec<Ring>>,
+}
+
+impl Contour {
+	// in square pixels, without the holes
+	pub fn area(&self) -> f64 {
+		return self.polygons.iter().flatten().map(ring_area).sum();
+	}
+}
+
+impl GeoImage {
+	// the areas closer than every distance, distances are in the unit of the value range (meters)
+	pub fn contours(&self, channel_index: usize, distances: &[f32]) -> Result<Vec<Contour>> {
+		let channel = self.channels.get(channel_index).ok_or_else(|| {
+			return Error::Invalid(format!("channel {} does not exist, the tile has {} channels", channel_index, self.channels.len()));
+		})?;
+		let [min, max] = channel.info.value_range;
+
+		let mut contours:Vec<Contour> = Vec::with_capacity(distances.len());
+		for distance in distances {
+			if (*distance <= min) || (*distance > max) {
+				return Err(Error::Invalid(format!("distance {} is outside of the value range [{}, {}] of channel \"{}\"", distance, min, max, channel.info.name)));
+			}
+			let threshold = ((distance - min) / (max - min)) as f64;
+			contours.push(Contour { distance: *distance, polygons: trace(&channel.data, self.size as usize, threshold) });
+		}
+		return Ok(contours);
+	}
+}
+
+// the polygons around all pixels with a value below threshold
+fn trace(data: &[f32], size: usize, threshold: f64) -> Vec<Vec<Ring>> {
+	// the grid of pixel centers with a border far away, grid point (gx, gy) is pixel (gx - 1, gy - 1)
+	let value = |(gx, gy): (usize, usize)| -> f64 {
+		if (gx == 0) || (gy == 0) || (gx > size) || (gy > size) {
+			return f64::INFINITY;
+		}
+		return data[(gx - 1) + (gy - 1) * size] as f64;
+	};
+	let crossing = |a: (usize, usize), b: (usize, usize)| -> [f64; 2] {
+		let (value_a, value_b) = (value(a), value(b));
+		// between the border and a pixel center is the edge of the image
+		let t = if value_a.is_infinite() || value_b.is_infinite() { 0.5 } else { (threshold - value_a) / (value_b - value_a) };
+		return [
+			(a.0 as f64) - 0.5 + t * ((b.0 as f64) - (a.0 as f64)),
+			(a.1 as f64) - 0.5 + t * ((b.1 as f64) - (a.1 as f64)),
+		];
+	};
+	let edge_id = |a: (usize, usize), b: (usize, usize)| -> usize {
+		let (gx, gy) = (a.0.min(b.0), a.1.min(b.1));
+		let vertical = if a.0 == b.0 { 1 } else { 0 };
+		return (gx + gy * (size + 2)) * 2 + vertical;
+	};
+
+	// every segment goes from the edge, where the corners change from outside to inside (clockwise),
+	// to the edge, where they change back, so the inside is left of it and the segments of neighbouring cells continue each other
+	let mut segments: HashMap<usize, (usize, Vec<[f64; 2]>)> = HashMap::new();
+	let mut starts: Vec<usize> = Vec::new();
+	for gy in 0..=size {
+		for gx in 0..=size {
+			let corners = [(gx, gy), (gx + 1, gy), (gx + 1, gy + 1), (gx, gy + 1)];
+			let inside = corners.map(|corner| value(corner) < threshold);
+			if inside.iter().all(|v| *v == inside[0]) {
+				continue;
+			}
+
+			// (edge, entering) in clockwise order
+			let mut crossings: Vec<(usize, bool)> = Vec::with_capacity(4);
+			for i in 0..4 {
+				if inside[i] != inside[(i + 1) % 4] {
+					crossings.push((i, inside[(i + 1) % 4]));
+				}
+			}
+			let count = crossings.len();
+			// a saddle connects the inside corners, if the center is inside
+			let center = corners.iter().map(|corner| value(*corner)).sum::<f64>() / 4.0;
+			let step = if (count == 4) && (center < threshold) { count - 1 } else { 1 };
+			for (k, (edge, entering)) in crossings.iter().enumerate() {
+				if !entering {
+					continue;
+				}
+				let exit = crossings[(k + step) % count].0;
+				let start = (corners[*edge], corners[(edge + 1) % 4]);
+				let end = (corners[exit], corners[(exit + 1) % 4]);
+				let id = edge_id(start.0, start.1);
+				let mut points = vec![crossing(start.0, start.1)];
+				// in the corners of the image the segment runs along its border
+				if ((gx == 0) || (gx == size)) && ((gy == 0) || (gy == size)) {
+					points.push([gx as f64, gy as f64]);
+				}
+				segments.insert(id, (edge_id(end.0, end.1), points));
+				starts.push(id);
+			}
+		}
+	}
+
+	let mut exteriors: Vec<Ring> = Vec::new();
+	let mut holes: Vec<Ring> = Vec::new();
+	for start in starts {
+		let Some((mut next, mut ring)) = segments.remove(&start) else {
+			continue;
+		};
+		while next != start {
+			let (following, points) = segments.remove(&next).expect("contour rings are closed");
+			ring.extend(points);
+			next = following;
+		}
+		// the inside was left of the segments: exterior rings are counterclockwise on screen so far
+		ring.reverse();
+		let ring = remove_collinear(ring);
+		if ring_area(&ring) > 0.0 {
+			exteriors.push(ring);
+		} else {
+			holes.push(ring);
+		}
+	}
+
+	// every hole belongs to the smallest exterior ring around it
+	let mut polygons: Vec<Vec<Ring>> = exteriors.into_iter().map(|ring| vec![ring]).collect();
+	for hole in holes {
+		let owner = polygons.iter_mut()
+			.filter(|polygon| ring_contains(&polygon[0], hole[0]))
+			.min_by(|a, b| ring_area(&a[0]).total_cmp(&ring_area(&b[0])));
+		if let Some(polygon) = owner {
+			polygon.push(hole);
+		}
+	}
+	return polygons;
+}
+
+// e.g. along the border of the image
+fn remove_collinear(ring: Ring) -> Ring {
+	let n = ring.len();
+	let result: Ring = (0..n).filter(|i| {
+		let (a, b, c) = (ring[(i + n - 1) % n], ring[*i], ring[(i + 1) % n]);
+		return (b[0] - a[0]) * (c[1] - b[1]) != (b[1] - a[1]) * (c[0] - b[0]);
+	}).map(|i| ring[i]).collect();
+	return if result.len() >= 3 { result } else { ring };
+}
+
+// positive for clockwise rings on screen (y down)
+fn ring_area(ring: &Ring) -> f64 {
+	let n = ring.len();
+	return (0..n).map(|i| {
+		let (a, b) = (ring[i], ring[(i + 1) % n]);
+		return a[0] * b[1] - b[0] * a[1];
+	}).sum::<f64>() / 2.0;
+}
+
+fn ring_contains(ring: &Ring, point: [f64; 2]) -> bool {
+	let mut inside = false;
+	let mut previous = ring[ring.len() - 1];
+	for current in ring {
+		if (current[1] > point[1]) != (previous[1] > point[1]) {
+			let x = previous[0] + (point[1] - previous[1]) / (current[1] - previous[1]) * (current[0] - previous[0]);
+			if point[0] < x {
+				inside = !inside;
+			}
+		}
+		previous = *current;
+	}
+	return inside;
+}
+
+// a FeatureCollection with one MultiPolygon per distance, exterior rings are counterclockwise (RFC 7946)
+pub fn contours_to_geojson(image: &GeoImage, contours: &[Contour]) -> JsonValue {
+	// about 1 cm
+	let round = |value: f64| (value * 1.0e7).round() / 1.0e7;
+	let features: Vec<JsonValue> = contours.iter().map(|contour| {
+		let polygons: Vec<JsonValue> = contour.polygons.iter().map(|polygon| {
+			return JsonValue::Array(polygon.iter().map(|ring| {
+				let mut coordinates: Vec<JsonValue> = ring.iter().rev().map(|position| {
+					let point = image.get_position_as_point(position[0], position[1]);
+					return json::array![round(point.x), round(point.y)];
+				}).collect();
+				coordinates.push(coordinates[0].clone());
+				return JsonValue::Array(coordinates);
+			}).collect());
+		}).collect();
+		return json::object! {
+			"type": "Feature",
+			"properties": { "distance": contour.distance },
+			"geometry": { "type": "MultiPolygon", "coordinates": polygons },
+		};
+	}).collect();
+	return json::object! { "type": "FeatureCollection", "features": features };
+}
+
+// a vector tile of the image tile with the layer "contours", one feature per distance with the property "distance"
+pub fn contours_to_mvt(image: &GeoImage, contours: &[Contour]) -> Vec<u8> {
+	let scale = (MVT_EXTENT as f64) / (image.size as f64);
+	let mut layer: Vec<u8> = Vec::new();
+	write_uint_field(&mut layer, 15, 2);
+	write_bytes_field(&mut layer, 1, b"contours");
+	for (index, contour) in contours.iter().enumerate() {
+		let geometry = mvt_geometry(contour, scale);
+		if geometry.is_empty() {
+			continue;
+		}
+		let mut feature: Vec<u8> = Vec::new();
+		write_uint_field(&mut feature, 1, index as u64 + 1);
+		// key "distance", value of this distance
+		write_packed_field(&mut feature, 2, &[0, index as u32]);
+		// polygon
+		write_uint_field(&mut feature, 3, 3);
+		write_packed_field(&mut feature, 4, &geometry);
+		write_bytes_field(&mut layer, 2, &feature);
+	}
+	write_bytes_field(&mut layer, 3, b"distance");
+	for contour in contours {
+		// double_value
+		let mut value: Vec<u8> = vec![3 << 3 | 1];
+		value.extend_from_slice(&(contour.distance as f64).to_le_bytes());
+		write_bytes_field(&mut layer, 4, &value);
+	}
+	write_uint_field(&mut layer, 5, MVT_EXTENT as u64);
+
+	let mut tile: Vec<u8> = Vec::new();
+	write_bytes_field(&mut tile, 3, &layer);
+	return tile;
+}
+
+// MoveTo, LineTo and ClosePath with zigzag encoded deltas, rings that vanish by rounding are dropped
+fn mvt_geometry(contour: &Contour, scale: f64) -> Vec<u32> {
+	let command = |id: u32, count: usize| id | ((count as u32) << 3);
+	let zigzag = |value: i64| ((value << 1) ^ (value >> 63)) as u32;
+
+	let mut geometry: Vec<u32> = Vec::new();
+	let mut cursor = [0i64; 2];
+	for polygon in &contour.polygons {
+		for (index, ring) in polygon.iter().enumerate() {
+			let mut points: Vec<[i64; 2]> = ring.iter().map(|p| [(p[0] * scale).round() as i64, (p[1] * scale).round() as i64]).collect();
+			points.dedup();
+			while (points.len() > 1) && (points[0] == points[points.len() - 1]) {
+				points.pop();
+			}
+			let area: i64 = (0..points.len()).map(|i| {
+				let (a, b) = (points[i], points[(i + 1) % points.len()]);
+				return a[0] * b[1] - b[0] * a[1];
+			}).sum();
+			let exterior = index == 0;
+			if (points.len() < 3) || (exterior && (area <= 0)) || (!exterior && (area >= 0)) {
+				if exterior {
+					break;
+				}
+				continue;
+			}
+
+			for (i, point) in points.iter().enumerate() {
+				if i == 0 {
+					geometry.push(command(1, 1));
+				} else if i == 1 {
+					geometry.push(command(2, points.len() - 1));
+				}
+				geometry.push(zigzag(point[0] - cursor[0]));
+				geometry.push(zigzag(point[1] - cursor[1]));
+				cursor = *point;
+			}
+			geometry.push(command(7, 1));
+		}
+	}
+	return geometry;
+}
+
+fn write_uint_field(buffer: &mut Vec<u8>, field: u32, value: u64) {
+	write_varint(buffer, (field << 3) as u64);
+	write_varint(buffer, value);
+}
+
+fn write_bytes_field(buffer: &mut Vec<u8>, field: u32, data: &[u8]) {
+	write_varint(buffer, (field << 3 | 2) as u64);
+	write_varint(buffer, data.len() as u64);
+	buffer.extend_from_slice(data);
+}
+
+fn write_packed_field(buffer: &mut Vec<u8>, field: u32, values: &[u32]) {
+	let mut data: Vec<u8> = Vec::new();
+	for value in values {
+		write_varint(&mut data, *value as u64);
+	}
+	write_bytes_field(buffer, field, &data);
+}
@@ -0,0 +1,310 @@
+/*
+	Euclidean distance transform of the rasterised geometries as an alternative to the segment tree (see Renderer).
+	The transform itself is exact, but it measures to the centers of the boundary cells, not to the segments:
+	1. rasterise all segments into a grid of "oversampling" × "oversampling" cells per pixel,
+	   with a margin of max_distance around the image, so geometries outside of the tile are found as well
+	2. per column of the grid: the nearest boundary cell for every row of pixel centers
+	3. per row of pixel centers: the nearest boundary cell, as the lower envelope of parabolas
+	   (Felzenszwalb & Huttenlocher, "Distance Transforms of Sampled Functions"), linear in the width of the grid
+	4. the distance in meters to the nearest cell, measured at the latitude of both points,
+	   so the pixel size, that shrinks with the latitude in web mercator, is taken into account
+	5. inside polygons the distance is negative, like in GeoImage::draw_distances
+	The grid is square in web mercator, so the nearest cell in the grid is also the nearest one in meters,
+	besides the change of scale over max_distance (about 0.05% for 2.5 km in Germany).
+	Measured to the cells, the error compared to the tree is up to half the diagonal of a cell, tests/edt.rs allows 0.5 m more for rounding
+	and checks, that it is not slower than the tree for dense buildings.
+*/
+
+use crate::geoimage::GeoImage;
+use crate::geometry::{Collection, Point};
+use std::f64::consts::PI;
+use std::thread;
+
+// odd, so that every pixel center is the center of a cell
+pub const DEFAULT_OVERSAMPLING: u32 = 3;
+
+const EARTH_RADIUS: f64 = 6378137.0;
+const DEG2METERS: f64 = EARTH_RADIUS * PI / 180.0;
+const NONE: u32 = u32::MAX;
+
+// how distance layers are rendered:
+// - Tree: nearest segment in the segment tree for every pixel, exact (default)
+// - Edt: distance transform of the rasterised geometries, faster for dense geometries, only the approximate distance model
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Renderer {
+	Tree,
+	Edt { oversampling: u32 },
+}
+
+impl Renderer {
+	pub fn from_name(name: &str) -> Option<Renderer> {
+		return match name {
+			"tree" => Some(Renderer::Tree),
+			"edt" => Some(Renderer::Edt { oversampling: DEFAULT_OVERSAMPLING }),
+			_ => None,
+		};
+	}
+}
+
+// the grid of cells in mercator units, including the margin
+struct Grid {
+	x0: f64,
+	y0: f64,
+	cell: f64,
+	width: usize,
+	// cells of the margin, the first pixel center is at margin + oversampling / 2
+	margin: usize,
+	oversampling: usize,
+}
+
+impl Grid {
+	fn position(&self, point: &Point) -> (f64, f64) {
+		return ((mercator_x(point.x) - self.x0) / self.cell, (mercator_y(point.y) - self.y0) / self.cell);
+	}
+	fn center(&self, column: usize, row: usize) -> Point {
+		let x = self.x0 + (column as f64 + 0.5) * self.cell;
+		let y = self.y0 + (row as f64 + 0.5) * self.cell;
+		return Point::new(demercator_x(x), demercator_y(y));
+	}
+	// the grid line of the center of pixel i
+	fn pixel_line(&self, i: usize) -> usize {
+		return self.margin + i * self.oversampling + self.oversampling / 2;
+	}
+	// marks the cells along a segment, in steps of half a cell
+	fn rasterise(&self, p0: &Point, p1: &Point, columns: &mut [Vec<u32>]) {
+		let (x0, y0) = self.position(p0);
+		let (x1, y1) = self.position(p1);
+		let size = self.width as f64;
+		// only the part of the segment inside of the grid (Liang–Barsky)
+		let (mut t0, mut t1) = (0.0f64, 1.0f64);
+		for (p, q) in [(x0 - x1, x0), (x1 - x0, size - x0), (y0 - y1, y0), (y1 - y0, size - y0)] {
+			if p == 0.0 {
+				if q < 0.0 {
+					return;
+				}
+				continue;
+			}
+			let t = q / p;
+			if p < 0.0 {
+				t0 = t0.max(t);
+			} else {
+				t1 = t1.min(t);
+			}
+		}
+		if t0 > t1 {
+			return;
+		}
+
+		let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt() * (t1 - t0);
+		let steps = (length * 2.0).ceil().max(1.0) as usize;
+		for step in 0..=steps {
+			let t = t0 + (t1 - t0) * (step as f64) / (steps as f64);
+			let column = (x0 + t * (x1 - x0)).floor();
+			let row = (y0 + t * (y1 - y0)).floor();
+			if (column >= 0.0) && (row >= 0.0) && (column < size) && (row < size) {
+				columns[column as usize].push(row as u32);
+			}
+		}
+	}
+}
+
+impl GeoImage {
+	// same result as draw_distances, see the top of this file
+	pub fn draw_distances_edt(&mut self, channel_index: usize, collection: &Collection, min_distance: f32, max_distance: f32, oversampling: u32, threads: usize) {
+		let threads = if threads > 0 {
+			threads
+		} else {
+			thread::available_parallelism().map_or(1, |n| n.get())
+		};
+		let size = self.size as usize;
+		let scale = 2.0f64.powi(self.zoom as i32);
+		let pixel_scale = 1.0 / (self.size as f64) / scale;
+		let cell = pixel_scale / (oversampling as f64);
+
+		// the margin has to reach max_distance, where the cells are smallest: at the latitude farthest from the equator
+		// (plus 1% for the change of latitude inside of the margin)
+		let lat_top = demercator_y(self.y_offset as f64 / scale);
+		let lat_bottom = demercator_y((self.y_offset + 1) as f64 / scale);
+		let max_lat = lat_top.abs().max(lat_bottom.abs()).min(89.0);
+		let cell_meters = cell * 2.0 * PI * EARTH_RADIUS * max_lat.to_radians().cos();
+		let margin = (max_distance.max(-min_distance).max(0.0) as f64 * 1.01 / cell_meters).ceil() as usize + 1;
+		let grid = Grid {
+			x0: (self.x_offset as f64) / scale - (margin as f64) * cell,
+			y0: (self.y_offset as f64) / scale - (margin as f64) * cell,
+			cell,
+			width: size * oversampling as usize + 2 * margin,
+			margin,
+			oversampling: oversampling as usize,
+		};
+
+		// 1. the boundary cells, sorted per column
+		let mut columns: Vec<Vec<u32>> = vec![Vec::new(); grid.width];
+		collection.geometry.for_each_segment(|p0, p1| grid.rasterise(p0, p1, &mut columns));
+		for column in columns.iter_mut() {
+			column.sort_unstable();
+			column.dedup();
+		}
+
+		// 2. nearest boundary row per column and row of pixel centers, column by column
+		let mut nearest_rows: Vec<u32> = vec![NONE; grid.width * size];
+		let chunk_columns = grid.width.div_ceil(threads);
+		thread::scope(|scope| {
+			for (chunk_index, chunk) in nearest_rows.chunks_mut(chunk_columns * size).enumerate() {
+				let (grid, columns) = (&grid, &columns);
+				scope.spawn(move || {
+					for (offset, result) in chunk.chunks_mut(size).enumerate() {
+						let rows = &columns[chunk_index * chunk_columns + offset];
+						if rows.is_empty() {
+							continue;
+						}
+						let mut index = 0;
+						for (y, nearest) in result.iter_mut().enumerate() {
+							let row = grid.pixel_line(y) as u32;
+							while (index + 1 < rows.len()) && (rows[index + 1] <= row) {
+								index += 1;
+							}
+							// the last row above and the first one below
+							let mut best = rows[index];
+							if let Some(next) = rows.get(index + 1) {
+								if next.abs_diff(row) < best.abs_diff(row) {
+									best = *next;
+								}
+							}
+							*nearest = best;
+						}
+					}
+				});
+			}
+		});
+
+		// 3. and 4. nearest boundary cell per pixel center, row by row, in meters
+		let mut distances: Vec<f32> = vec![max_distance; size * size];
+		let chunk_rows = size.div_ceil(threads);
+		thread::scope(|scope| {
+			for (chunk_index, chunk) in distances.chunks_mut(chunk_rows * size).enumerate() {
+				let (grid, nearest_rows) = (&grid, &nearest_rows);
+				scope.spawn(move || {
+					let mut envelope = Envelope::new(grid.width);
+					for (offset, result) in chunk.chunks_mut(size).enumerate() {
+						let y = chunk_index * chunk_rows + offset;
+						let row = grid.pixel_line(y);
+						envelope.build(|column| {
+							let nearest = nearest_rows[column * size + y];
+							return if nearest == NONE { None } else { Some((nearest as f64 - row as f64).powi(2)) };
+						});
+						let mut query = envelope.query();
+						for (x, distance) in result.iter_mut().enumerate() {
+							let column = grid.pixel_line(x);
+							if let Some(nearest) = query.nearest(column) {
+								let meters = approximate_distance(&grid.center(column, row), &grid.center(nearest, nearest_rows[nearest * size + y] as usize));
+								*distance = (meters as f32).min(max_distance);
+							}
+						}
+					}
+				});
+			}
+		});
+
+		// 5. the sign, with the same cut geometries as the tree renderer
+		let (x0, y0) = ((self.x_offset as f64) / scale, (self.y_offset as f64) / scale);
+		self.draw_blocks(channel_index, vec![&collection.geometry], threads, |point, geometries| {
+			let x = ((mercator_x(point.x) - x0) / pixel_scale - 0.5).round() as usize;
+			let y = ((mercator_y(point.y) - y0) / pixel_scale - 0.5).round() as usize;
+			let mut distance = distances[y.min(size - 1) * size + x.min(size - 1)];
+			if geometries[0].contains_point(point) {
+				distance = -distance;
+			}
+			return (distance - min_distance) / (max_distance - min_distance);
+		});
+	}
+}
+
+// lower envelope of the parabolas (x - q)² + f(q) of all columns q with a boundary cell
+struct Envelope {
+	width: usize,
+	// columns of the parabolas in the envelope, and where each one starts
+	vertices: Vec<usize>,
+	values: Vec<f64>,
+	starts: Vec<f64>,
+}
+
+impl Envelope {
+	fn new(width: usize) -> Envelope {
+		return Envelope { width, vertices: Vec::with_capacity(width), values: Vec::with_capacity(width), starts: Vec::with_capacity(width) };
+	}
+	fn build<F>(&mut self, f: F) where F: Fn(usize) -> Option<f64> {
+		self.vertices.clear();
+		self.values.clear();
+		self.starts.clear();
+		for column in 0..self.width {
+			let Some(value) = f(column) else {
+				continue;
+			};
+			while let Some(&last) = self.vertices.last() {
+				// intersection with the last parabola of the envelope
+				let last_value = *self.values.last().unwrap();
+				let s = ((value + (column * column) as f64) - (last_value + (last * last) as f64)) / (2.0 * (column - last) as f64);
+				if s <= *self.starts.last().unwrap() {
+					self.vertices.pop();
+					self.values.pop();
+					self.starts.pop();
+					continue;
+				}
+				self.starts.push(s);
+				break;
+			}
+			if self.vertices.is_empty() {
+				self.starts.clear();
+				self.starts.push(f64::NEG_INFINITY);
+			}
+			self.vertices.push(column);
+			self.values.push(value);
+		}
+	}
+	// the queries have to be in increasing order
+	fn query(&self) -> EnvelopeQuery<'_> {
+		return EnvelopeQuery { envelope: self, index: 0 };
+	}
+}
+
+struct EnvelopeQuery<'a> {
+	envelope: &'a Envelope,
+	index: usize,
+}
+
+impl EnvelopeQuery<'_> {
+	// column of the parabola, that is lowest at x
+	fn nearest(&mut self, x: usize) -> Option<usize> {
+		let envelope = self.envelope;
+		if envelope.vertices.is_empty() {
+			return None;
+		}
+		while (self.index + 1 < envelope.vertices.len()) && (envelope.starts[self.index + 1] < x as f64) {
+			self.index += 1;
+		}
+		return Some(envelope.vertices[self.index]);
+	}
+}
+
+// equirectangular at the mean latitude of both points
+fn approximate_distance(p0: &Point, p1: &Point) -> f64 {
+	let dx = (p1.x - p0.x) * ((p0.y + p1.y) / 2.0).to_radians().cos();
+	let dy = p1.y - p0.y;
+	return (dx * dx + dy * dy).sqrt() * DEG2METERS;
+}
+
+fn mercator_x(x: f64) -> f64 {
+	return (x + 180.0) / 360.0;
+}
+
+fn mercator_y(y: f64) -> f64 {
+	return 0.5 * (1.0 - (PI * (1.0 + y / 90.0) / 4.0).tan().ln() / PI);
+}
+
+fn demercator_x(x: f64) -> f64 {
+	return x * 360.0 - 180.0;
+}
+
+fn demercator_y(y: f64) -> f64 {
+	return (((1.0 - y * 2.0) * PI).exp().atan() * 4.0 / PI - 1.0) * 90.0;
+}
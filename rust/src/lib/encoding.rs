@@ -0,0 +1,248 @@
+/*
+	How channel values are stored in the pixels of image tiles:
+	- "rgb8": 3 channels per image as R, G and B, 8 bit each and truncated (like before, the default)
+	- "rg16": 1 channel per image, 16 bit split into R (high byte) and G (low byte)
+	- "gray16": 1 channel per image as 16 bit grayscale PNG
+	- "terrarium": 1 channel per image, the value (e.g. meters) packed like Terrarium elevation tiles:
+	  value = R * 256 + G + B / 256 - 32768
+	- "mapbox": 1 channel per image, packed like Mapbox Terrain-RGB: value = (R * 65536 + G * 256 + B) * 0.1 - 10000
+	All but rgb8 are rounded. tile_metadata describes for every channel how to decode it:
+	value = weights · pixel + offset, with pixel = [R, G, B] as bytes (gray16: the 16 bit value)
+
+	rgb8, rg16 and gray16 can spread the value range with a curve (see ValueCurve), then the weights and offset
+	give the position p (0..1) and the metadata contains the curve to invert it with min, max = value_range:
+	- "log" with scale k: f(v) = sign(v) · ln(1 + |v| / k), s = f(min) + p · (f(max) - f(min)), value = sign(s) · k · (e^|s| - 1)
+	- "piecewise" with breakpoints b: t = [min, b…, max], n = len(t) - 1, i = min(floor(p · n), n - 1),
+	  value = t[i] + (p · n - i) · (t[i + 1] - t[i])
+	Bin tiles always keep the values themselves, the curve is only applied to the images.
+*/
+
+use crate::geoimage::{Channel, ChannelInfo, TileFormat};
+use image::{DynamicImage, ImageBuffer, Luma, Rgb};
+use json::JsonValue;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelEncoding {
+	Rgb8,
+	Rg16,
+	Gray16,
+	Terrarium,
+	Mapbox,
+}
+
+impl PixelEncoding {
+	pub fn from_name(name: &str) -> Option<PixelEncoding> {
+		return match name {
+			"rgb8" => Some(PixelEncoding::Rgb8),
+			"rg16" => Some(PixelEncoding::Rg16),
+			"gray16" => Some(PixelEncoding::Gray16),
+			"terrarium" => Some(PixelEncoding::Terrarium),
+			"mapbox" => Some(PixelEncoding::Mapbox),
+			_ => None,
+		};
+	}
+	pub fn name(&self) -> &'static str {
+		return match self {
+			PixelEncoding::Rgb8 => "rgb8",
+			PixelEncoding::Rg16 => "rg16",
+			PixelEncoding::Gray16 => "gray16",
+			PixelEncoding::Terrarium => "terrarium",
+			PixelEncoding::Mapbox => "mapbox",
+		};
+	}
+	pub fn channels_per_image(&self) -> usize {
+		return match self {
+			PixelEncoding::Rgb8 => 3,
+			_ => 1,
+		};
+	}
+	// lossy compression would destroy the low bytes
+	pub fn is_8_bit(&self) -> bool {
+		return *self == PixelEncoding::Rgb8;
+	}
+	// terrarium and mapbox store the values themselves
+	pub fn uses_curve(&self) -> bool {
+		return matches!(self, PixelEncoding::Rgb8 | PixelEncoding::Rg16 | PixelEncoding::Gray16);
+	}
+	// weights for R, G, B and offset to decode the value of a channel, component is the position in rgb8.
+	// with a curve they decode the position (0..1), see ValueCurve
+	pub fn decoder(&self, info: &ChannelInfo, component: usize) -> ([f64; 3], f64) {
+		let (min, range) = if self.uses_curve() && (info.curve != ValueCurve::Linear) {
+			(0.0, 1.0)
+		} else {
+			(info.value_range[0] as f64, info.value_range[1] as f64 - info.value_range[0] as f64)
+		};
+		return match self {
+			PixelEncoding::Rgb8 => {
+				let mut weights = [0.0; 3];
+				weights[component] = range / 255.0;
+				(weights, min)
+			},
+			PixelEncoding::Rg16 => ([range * 256.0 / 65535.0, range / 65535.0, 0.0], min),
+			PixelEncoding::Gray16 => ([range / 65535.0, 0.0, 0.0], min),
+			PixelEncoding::Terrarium => ([256.0, 1.0, 1.0 / 256.0], -32768.0),
+			PixelEncoding::Mapbox => ([6553.6, 25.6, 0.1], -10000.0),
+		};
+	}
+}
+
+// how the values of a channel are spread over the pixel values of an image:
+// - Linear: evenly over the value range (default)
+// - Log: symmetric logarithm around 0, fine steps close to the geometries, coarse ones far away and deep inside,
+//   so an 8 bit channel can keep inside distances and distances of 10 km
+// - Piecewise: linear between the breakpoints, every part of the value range gets the same share of the pixel values
+#[derive(Clone, PartialEq, Debug)]
+pub enum ValueCurve {
+	Linear,
+	Log { scale: f32 },
+	Piecewise { breakpoints: Vec<f32> },
+}
+
+impl ValueCurve {
+	pub fn name(&self) -> &'static str {
+		return match self {
+			ValueCurve::Linear => "linear",
+			ValueCurve::Log { .. } => "log",
+			ValueCurve::Piecewise { .. } => "piecewise",
+		};
+	}
+	// the error is a message without the name of the argument
+	pub fn check(&self, value_range: [f32; 2]) -> Result<(), String> {
+		return match self {
+			ValueCurve::Linear => Ok(()),
+			ValueCurve::Log { scale } if scale.is_finite() && (*scale > 0.0) => Ok(()),
+			ValueCurve::Log { scale } => Err(format!("the scale of a log curve must be positive, found {}", scale)),
+			ValueCurve::Piecewise { breakpoints } => {
+				let knots = self.knots(value_range);
+				if breakpoints.is_empty() || knots.windows(2).any(|pair| pair[0] >= pair[1]) {
+					return Err(format!("breakpoints must be increasing and inside of [{}, {}], found {:?}", value_range[0], value_range[1], breakpoints));
+				}
+				Ok(())
+			},
+		};
+	}
+	// min, breakpoints, max
+	fn knots(&self, value_range: [f32; 2]) -> Vec<f64> {
+		let mut knots = vec![value_range[0] as f64];
+		if let ValueCurve::Piecewise { breakpoints } = self {
+			knots.extend(breakpoints.iter().map(|value| *value as f64));
+		}
+		knots.push(value_range[1] as f64);
+		return knots;
+	}
+	// position of a value (e.g. meters) between 0 and 1
+	pub fn to_position(&self, value_range: [f32; 2], value: f64) -> f64 {
+		let (min, max) = (value_range[0] as f64, value_range[1] as f64);
+		let position = match self {
+			ValueCurve::Linear => (value - min) / (max - min),
+			ValueCurve::Log { scale } => {
+				let f = |v: f64| v.signum() * (v.abs() / *scale as f64).ln_1p();
+				(f(value) - f(min)) / (f(max) - f(min))
+			},
+			ValueCurve::Piecewise { .. } => {
+				let knots = self.knots(value_range);
+				let n = knots.len() - 1;
+				let i = knots[1..n].iter().take_while(|knot| value >= **knot).count();
+				(i as f64 + (value - knots[i]) / (knots[i + 1] - knots[i])) / n as f64
+			},
+		};
+		return position.clamp(0.0, 1.0);
+	}
+	// inverse of to_position
+	pub fn from_position(&self, value_range: [f32; 2], position: f64) -> f64 {
+		let (min, max) = (value_range[0] as f64, value_range[1] as f64);
+		return match self {
+			ValueCurve::Linear => min + position * (max - min),
+			ValueCurve::Log { scale } => {
+				let f = |v: f64| v.signum() * (v.abs() / *scale as f64).ln_1p();
+				let s = f(min) + position * (f(max) - f(min));
+				s.signum() * (*scale as f64) * s.abs().exp_m1()
+			},
+			ValueCurve::Piecewise { .. } => {
+				let knots = self.knots(value_range);
+				let n = knots.len() - 1;
+				let i = ((position * n as f64).floor() as usize).min(n - 1);
+				knots[i] + (position * n as f64 - i as f64) * (knots[i + 1] - knots[i])
+			},
+		};
+	}
+	pub fn to_json(&self) -> JsonValue {
+		return match self {
+			ValueCurve::Linear => json::object! { "type": "linear" },
+			ValueCurve::Log { scale } => json::object! { "type": "log", scale: *scale },
+			ValueCurve::Piecewise { breakpoints } => json::object! { "type": "piecewise", breakpoints: breakpoints.clone() },
+		};
+	}
+}
+
+// the image of up to 3 channels, values are normalized to the value range of the channel
+pub(crate) fn build_image(encoding: PixelEncoding, channels: &[&Channel], size: u32) -> DynamicImage {
+	let pixel = |x: u32, y: u32, channel: &Channel| -> f64 {
+		let value = channel.data[(x + y * size) as usize];
+		let info = &channel.info;
+		if info.curve == ValueCurve::Linear {
+			return value.clamp(0.0, 1.0) as f64;
+		}
+		let [min, max] = info.value_range;
+		return info.curve.to_position(info.value_range, (min + value * (max - min)) as f64);
+	};
+	return match encoding {
+		PixelEncoding::Rgb8 => DynamicImage::ImageRgb8(ImageBuffer::from_fn(size, size, |x, y| {
+			let mut rgb = [0u8; 3];
+			for (i, channel) in channels.iter().enumerate() {
+				rgb[i] = (pixel(x, y, channel)*255.0) as u8;
+			}
+			return Rgb(rgb);
+		})),
+		PixelEncoding::Rg16 => DynamicImage::ImageRgb8(ImageBuffer::from_fn(size, size, |x, y| {
+			let value = (pixel(x, y, channels[0]) * 65535.0).round() as u16;
+			return Rgb([(value >> 8) as u8, value as u8, 0]);
+		})),
+		PixelEncoding::Gray16 => DynamicImage::ImageLuma16(ImageBuffer::from_fn(size, size, |x, y| {
+			return Luma([(pixel(x, y, channels[0]) * 65535.0).round() as u16]);
+		})),
+		PixelEncoding::Terrarium | PixelEncoding::Mapbox => {
+			let info = &channels[0].info;
+			let min = info.value_range[0] as f64;
+			let range = info.value_range[1] as f64 - min;
+			DynamicImage::ImageRgb8(ImageBuffer::from_fn(size, size, |x, y| {
+				let value = min + (channels[0].data[(x + y * size) as usize].clamp(0.0, 1.0) as f64) * range;
+				let steps = if encoding == PixelEncoding::Terrarium {
+					(value + 32768.0) * 256.0
+				} else {
+					(value + 10000.0) * 10.0
+				};
+				let steps = steps.round().clamp(0.0, 16777215.0) as u32;
+				return Rgb([(steps >> 16) as u8, (steps >> 8) as u8, steps as u8]);
+			}))
+		},
+	};
+}
+
+// the sidecar "metadata.json", so that a shader knows how to decode the tiles
+pub fn tile_metadata(format: TileFormat, encoding: PixelEncoding, infos: &[ChannelInfo]) -> JsonValue {
+	let per_image = encoding.channels_per_image();
+	let channels: Vec<JsonValue> = infos.iter().enumerate().map(|(index, info)| {
+		let (weights, offset) = encoding.decoder(info, index % per_image);
+		let mut channel = json::object! {
+			name: info.name.clone(),
+			value_range: info.value_range.to_vec(),
+			fill_value: info.fill_value,
+			// 0: "x.png", 1: "x-1.png", …
+			image: index / per_image,
+			weights: weights.to_vec(),
+			offset: offset,
+		};
+		if encoding.uses_curve() && (info.curve != ValueCurve::Linear) {
+			channel["curve"] = info.curve.to_json();
+		}
+		return channel;
+	}).collect();
+
+	return json::object! {
+		format: &format.extension()[1..],
+		encoding: encoding.name(),
+		decode: "value = weights · [R, G, B] + offset",
+		channels: channels,
+	};
+}
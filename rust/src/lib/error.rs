@@ -0,0 +1,72 @@
+/*
+	Errors of the library:
+	everything, that reads or writes files, parses arguments or combines tiles, returns a Result.
+	Panics are left for bugs.
+*/
+
+use crate::cli::{EXIT_FAILURE, EXIT_INVALID, EXIT_USAGE};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+	// reading or writing a file failed
+	Io { path: PathBuf, source: io::Error },
+	// a file has an unexpected content, e.g. invalid JSON, an unknown geometry type or a broken bin tile
+	Format { path: PathBuf, message: String },
+	// tiles, that do not fit together, e.g. in GeoImage::merge
+	Tile(String),
+	// unknown command, flag or argument
+	Usage(String),
+	// missing or invalid argument values
+	Invalid(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+	pub fn io(path: &Path, source: io::Error) -> Error {
+		return Error::Io { path: path.to_path_buf(), source };
+	}
+	pub fn format(path: &Path, message: impl fmt::Display) -> Error {
+		return Error::Format { path: path.to_path_buf(), message: message.to_string() };
+	}
+	// prefixes the message, e.g. with the position in the layers array
+	pub fn context(self, prefix: &str) -> Error {
+		return match self {
+			Error::Tile(message) => Error::Tile(format!("{}: {}", prefix, message)),
+			Error::Usage(message) => Error::Usage(format!("{}: {}", prefix, message)),
+			Error::Invalid(message) => Error::Invalid(format!("{}: {}", prefix, message)),
+			error => error,
+		};
+	}
+	pub fn exit_code(&self) -> i32 {
+		return match self {
+			Error::Usage(_) => EXIT_USAGE,
+			Error::Invalid(_) => EXIT_INVALID,
+			_ => EXIT_FAILURE,
+		};
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		return match self {
+			Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+			Error::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+			Error::Tile(message) => write!(f, "{}", message),
+			Error::Usage(message) => write!(f, "{}", message),
+			Error::Invalid(message) => write!(f, "{}", message),
+		};
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		return match self {
+			Error::Io { source, .. } => Some(source),
+			_ => None,
+		};
+	}
+}
@@ -0,0 +1,255 @@
+/*
+	Expressions for distance rules, with hub height "h" and rotor radius "r" as variables, e.g.:
+	- 1000
+	- 10 * (h + r)
+	- max(400, 2 * (h + r))
+	- (h + r <= 150) ? 750 : 1000
+	Supported: numbers, h, r, + - * /, parentheses, comparisons (< <= > >= == !=),
+	conditionals (a ? b : c), min(…) and max(…). "Math.min" and "Math.max" are accepted as well,
+	so the bodies of the rule functions in config.js can be used directly.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+	Equal,
+	NotEqual,
+}
+
+#[derive(Clone, Debug)]
+pub enum Expression {
+	Number(f32),
+	HubHeight,
+	RotorRadius,
+	Negate(Box<Expression>),
+	Binary(Operator, Box<Expression>, Box<Expression>),
+	Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+	Min(Vec<Expression>),
+	Max(Vec<Expression>),
+}
+
+impl Expression {
+	pub fn parse(text: &str) -> Result<Expression, String> {
+		let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+		let expression = parser.parse_conditional().map_err(|e| format!("{} in expression: {}", e, text))?;
+		if parser.pos < parser.tokens.len() {
+			return Err(format!("unexpected {:?} in expression: {}", parser.tokens[parser.pos], text));
+		}
+		return Ok(expression);
+	}
+	// accepts a number or a string
+	pub fn from_json(value: &json::JsonValue) -> Result<Expression, String> {
+		if let Some(number) = value.as_f32() {
+			return Ok(Expression::Number(number));
+		}
+		return match value.as_str() {
+			Some(text) => Expression::parse(text),
+			None => Err(format!("expected a number or an expression, found {}", value)),
+		};
+	}
+	// h = hub height, r = rotor radius
+	pub fn evaluate(&self, h: f32, r: f32) -> f32 {
+		return match self {
+			Expression::Number(value) => *value,
+			Expression::HubHeight => h,
+			Expression::RotorRadius => r,
+			Expression::Negate(a) => -a.evaluate(h, r),
+			Expression::Binary(operator, a, b) => {
+				let a = a.evaluate(h, r);
+				let b = b.evaluate(h, r);
+				match operator {
+					Operator::Add => a + b,
+					Operator::Sub => a - b,
+					Operator::Mul => a * b,
+					Operator::Div => a / b,
+					Operator::Less => bool_to_f32(a < b),
+					Operator::LessEqual => bool_to_f32(a <= b),
+					Operator::Greater => bool_to_f32(a > b),
+					Operator::GreaterEqual => bool_to_f32(a >= b),
+					Operator::Equal => bool_to_f32(a == b),
+					Operator::NotEqual => bool_to_f32(a != b),
+				}
+			},
+			Expression::Conditional(condition, a, b) => {
+				if condition.evaluate(h, r) != 0.0 { a.evaluate(h, r) } else { b.evaluate(h, r) }
+			},
+			Expression::Min(arguments) => arguments.iter().map(|a| a.evaluate(h, r)).fold(f32::INFINITY, f32::min),
+			Expression::Max(arguments) => arguments.iter().map(|a| a.evaluate(h, r)).fold(f32::NEG_INFINITY, f32::max),
+		};
+	}
+}
+
+fn bool_to_f32(value: bool) -> f32 {
+	return if value { 1.0 } else { 0.0 };
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+	Number(f32),
+	Name(String),
+	Symbol(&'static str),
+}
+
+// longer symbols first, so that "<=" is not read as "<"
+const SYMBOLS: [&str; 16] = ["<=", ">=", "==", "!=", "+", "-", "*", "/", "(", ")", ",", "?", ":", "<", ">", "="];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+	let chars: Vec<char> = text.chars().collect();
+	let mut tokens: Vec<Token> = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let c = chars[i];
+		if c.is_whitespace() {
+			i += 1;
+		} else if c.is_ascii_digit() || (c == '.') {
+			let start = i;
+			while (i < chars.len()) && (chars[i].is_ascii_digit() || (chars[i] == '.')) {
+				i += 1;
+			}
+			let number: String = chars[start..i].iter().collect();
+			match number.parse() {
+				Ok(value) => tokens.push(Token::Number(value)),
+				Err(_) => return Err(format!("invalid number '{}' in expression: {}", number, text)),
+			}
+		} else if c.is_alphabetic() {
+			let start = i;
+			while (i < chars.len()) && (chars[i].is_alphanumeric() || (chars[i] == '.') || (chars[i] == '_')) {
+				i += 1;
+			}
+			tokens.push(Token::Name(chars[start..i].iter().collect()));
+		} else {
+			let rest: String = chars[i..].iter().take(2).collect();
+			let symbol = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol));
+			match symbol {
+				Some(symbol) => {
+					tokens.push(Token::Symbol(symbol));
+					i += symbol.len();
+				},
+				None => return Err(format!("unexpected character '{}' in expression: {}", c, text)),
+			}
+		}
+	}
+	return Ok(tokens);
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	pos: usize,
+}
+
+impl Parser {
+	fn peek_symbol(&self, symbol: &str) -> bool {
+		return matches!(self.tokens.get(self.pos), Some(Token::Symbol(s)) if *s == symbol);
+	}
+	fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+		if !self.peek_symbol(symbol) {
+			return match self.tokens.get(self.pos) {
+				Some(token) => Err(format!("expected '{}', found {:?}", symbol, token)),
+				None => Err(format!("expected '{}', found the end", symbol)),
+			};
+		}
+		self.pos += 1;
+		return Ok(());
+	}
+	fn parse_conditional(&mut self) -> Result<Expression, String> {
+		let condition = self.parse_comparison()?;
+		if !self.peek_symbol("?") {
+			return Ok(condition);
+		}
+		self.pos += 1;
+		let a = self.parse_conditional()?;
+		self.expect_symbol(":")?;
+		let b = self.parse_conditional()?;
+		return Ok(Expression::Conditional(Box::new(condition), Box::new(a), Box::new(b)));
+	}
+	fn parse_comparison(&mut self) -> Result<Expression, String> {
+		let a = self.parse_sum()?;
+		let operator = match self.tokens.get(self.pos) {
+			Some(Token::Symbol("<")) => Operator::Less,
+			Some(Token::Symbol("<=")) => Operator::LessEqual,
+			Some(Token::Symbol(">")) => Operator::Greater,
+			Some(Token::Symbol(">=")) => Operator::GreaterEqual,
+			Some(Token::Symbol("==")) => Operator::Equal,
+			Some(Token::Symbol("!=")) => Operator::NotEqual,
+			_ => return Ok(a),
+		};
+		self.pos += 1;
+		// JavaScript's "===" and "!==" are read as "==" and "!="
+		if self.peek_symbol("=") {
+			self.pos += 1;
+		}
+		let b = self.parse_sum()?;
+		return Ok(Expression::Binary(operator, Box::new(a), Box::new(b)));
+	}
+	fn parse_sum(&mut self) -> Result<Expression, String> {
+		let mut a = self.parse_product()?;
+		loop {
+			let operator = match self.tokens.get(self.pos) {
+				Some(Token::Symbol("+")) => Operator::Add,
+				Some(Token::Symbol("-")) => Operator::Sub,
+				_ => return Ok(a),
+			};
+			self.pos += 1;
+			let b = self.parse_product()?;
+			a = Expression::Binary(operator, Box::new(a), Box::new(b));
+		}
+	}
+	fn parse_product(&mut self) -> Result<Expression, String> {
+		let mut a = self.parse_unary()?;
+		loop {
+			let operator = match self.tokens.get(self.pos) {
+				Some(Token::Symbol("*")) => Operator::Mul,
+				Some(Token::Symbol("/")) => Operator::Div,
+				_ => return Ok(a),
+			};
+			self.pos += 1;
+			let b = self.parse_unary()?;
+			a = Expression::Binary(operator, Box::new(a), Box::new(b));
+		}
+	}
+	fn parse_unary(&mut self) -> Result<Expression, String> {
+		if self.peek_symbol("-") {
+			self.pos += 1;
+			return Ok(Expression::Negate(Box::new(self.parse_unary()?)));
+		}
+		return self.parse_primary();
+	}
+	fn parse_primary(&mut self) -> Result<Expression, String> {
+		let token = self.tokens.get(self.pos).cloned();
+		self.pos += 1;
+		return match token {
+			Some(Token::Number(value)) => Ok(Expression::Number(value)),
+			Some(Token::Symbol("(")) => {
+				let expression = self.parse_conditional()?;
+				self.expect_symbol(")")?;
+				Ok(expression)
+			},
+			Some(Token::Name(name)) => match name.as_str() {
+				"h" => Ok(Expression::HubHeight),
+				"r" => Ok(Expression::RotorRadius),
+				"min" | "Math.min" => Ok(Expression::Min(self.parse_arguments()?)),
+				"max" | "Math.max" => Ok(Expression::Max(self.parse_arguments()?)),
+				_ => Err(format!("unknown name '{}'", name)),
+			},
+			Some(token) => Err(format!("unexpected {:?}", token)),
+			None => Err("unexpected end".to_string()),
+		};
+	}
+	fn parse_arguments(&mut self) -> Result<Vec<Expression>, String> {
+		self.expect_symbol("(")?;
+		let mut arguments = vec![self.parse_conditional()?];
+		while self.peek_symbol(",") {
+			self.pos += 1;
+			arguments.push(self.parse_conditional()?);
+		}
+		self.expect_symbol(")")?;
+		return Ok(arguments);
+	}
+}
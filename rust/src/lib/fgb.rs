@@ -0,0 +1,381 @@
+/*
+	FlatGeobuf reader:
+	1. magic bytes "fgb\x03fgb\x00"
+	2. size prefixed header (flatbuffer)
+	3. optional packed Hilbert R-tree, used for the bbox filter
+	4. size prefixed features (flatbuffers)
+	Only the parts of the flatbuffer format that are needed for geometries are implemented.
+	See: https://github.com/flatgeobuf/flatgeobuf/tree/master/src/fbs
+*/
+
+use crate::error::Error;
+use crate::geometry::{Bbox, Geometry, Point};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MAGIC: [u8; 8] = [b'f', b'g', b'b', 3, b'f', b'g', b'b', 0];
+const NODE_SIZE: u64 = 40;
+
+// field indexes of the flatbuffer tables
+const HEADER_GEOMETRY_TYPE: usize = 2;
+const HEADER_FEATURES_COUNT: usize = 8;
+const HEADER_INDEX_NODE_SIZE: usize = 9;
+const FEATURE_GEOMETRY: usize = 0;
+const GEOMETRY_ENDS: usize = 0;
+const GEOMETRY_XY: usize = 1;
+const GEOMETRY_TYPE: usize = 6;
+const GEOMETRY_PARTS: usize = 7;
+
+pub fn fill_from_fgb(geometry: &mut Geometry, filename: &Path, bbox: Option<&Bbox>) -> Result<(), Error> {
+	let mut file = BufReader::new(File::open(filename).map_err(|e| Error::io(filename, e))?);
+	return read_fgb(&mut file, geometry, bbox).map_err(|e| Error::format(filename, e));
+}
+
+fn read_fgb(file: &mut BufReader<File>, geometry: &mut Geometry, bbox: Option<&Bbox>) -> Result<(), String> {
+	let mut magic = [0u8; 8];
+	file.read_exact(&mut magic).map_err(truncated)?;
+	if magic[0..3] != MAGIC[0..3] || magic[4..7] != MAGIC[4..7] {
+		return Err("not a FlatGeobuf file".to_string());
+	}
+
+	let header_buffer = read_size_prefixed(file)?;
+	let header = Table::root(&header_buffer)?;
+	let geometry_type = header.get_u8(HEADER_GEOMETRY_TYPE, 0)?;
+	let features_count = header.get_u64(HEADER_FEATURES_COUNT, 0)?;
+	let index_node_size = header.get_u16(HEADER_INDEX_NODE_SIZE, 16)? as u64;
+
+	// every feature has at least its size prefix, so a larger count is corrupt (and its R-tree size would overflow)
+	let file_size = file.get_ref().metadata().map_err(truncated)?.len();
+	if features_count > file_size {
+		return Err(format!("corrupt FlatGeobuf file: {} features in {} bytes", features_count, file_size));
+	}
+
+	let index_start = 12 + header_buffer.len() as u64;
+	let mut features_start = index_start;
+	let mut offsets: Option<Vec<u64>> = None;
+
+	if (index_node_size > 0) && (features_count > 0) {
+		let tree = PackedRTree::new(features_count, index_node_size);
+		features_start += tree.num_nodes * NODE_SIZE;
+		if let Some(bbox) = bbox {
+			offsets = Some(tree.search(file, index_start, bbox)?);
+		}
+	}
+
+	match offsets {
+		Some(offsets) => {
+			for offset in offsets {
+				let start = features_start.checked_add(offset).ok_or(format!("corrupt FlatGeobuf file: feature offset {}", offset))?;
+				file.seek(SeekFrom::Start(start)).map_err(truncated)?;
+				read_feature(file, geometry, geometry_type)?;
+			}
+		},
+		None => {
+			file.seek(SeekFrom::Start(features_start)).map_err(truncated)?;
+			let mut size_buffer = [0u8; 4];
+			while file.read_exact(&mut size_buffer).is_ok() {
+				let buffer = read_buffer(file, u32::from_le_bytes(size_buffer))?;
+				add_feature(&buffer, geometry, geometry_type)?;
+			}
+		}
+	}
+	return Ok(());
+}
+
+fn truncated(error: io::Error) -> String {
+	return format!("truncated FlatGeobuf file: {}", error);
+}
+
+fn read_size_prefixed(file: &mut BufReader<File>) -> Result<Vec<u8>, String> {
+	let mut size_buffer = [0u8; 4];
+	file.read_exact(&mut size_buffer).map_err(truncated)?;
+	return read_buffer(file, u32::from_le_bytes(size_buffer));
+}
+
+// reads through take, so a corrupt size does not allocate gigabytes
+fn read_buffer(file: &mut BufReader<File>, size: u32) -> Result<Vec<u8>, String> {
+	let mut buffer: Vec<u8> = Vec::new();
+	file.take(size as u64).read_to_end(&mut buffer).map_err(truncated)?;
+	if buffer.len() != size as usize {
+		return Err(format!("truncated FlatGeobuf file: {} of {} bytes", buffer.len(), size));
+	}
+	return Ok(buffer);
+}
+
+fn read_feature(file: &mut BufReader<File>, geometry: &mut Geometry, geometry_type: u8) -> Result<(), String> {
+	let buffer = read_size_prefixed(file)?;
+	return add_feature(&buffer, geometry, geometry_type);
+}
+
+fn add_feature(buffer: &[u8], geometry: &mut Geometry, geometry_type: u8) -> Result<(), String> {
+	let feature = Table::root(buffer)?;
+	if let Some(table) = feature.get_table(FEATURE_GEOMETRY)? {
+		add_geometry(&table, geometry, geometry_type)?;
+	}
+	return Ok(());
+}
+
+fn add_geometry(table: &Table, geometry: &mut Geometry, geometry_type: u8) -> Result<(), String> {
+	// if the header defines no geometry type ("Unknown"), every geometry has its own
+	let geometry_type = if geometry_type == 0 {
+		table.get_u8(GEOMETRY_TYPE, 0)?
+	} else {
+		geometry_type
+	};
+
+	match geometry_type {
+		1 | 4 => {
+			for point in read_points(table)? {
+				geometry.add_point(point);
+			}
+		},
+		2 => geometry.add_line(read_points(table)?),
+		3 => {
+			let rings = read_rings(table)?;
+			if !rings.is_empty() {
+				geometry.add_polygon(rings);
+			}
+		},
+		5 => {
+			for line in read_rings(table)? {
+				geometry.add_line(line);
+			}
+		},
+		6 => {
+			for part in table.get_tables(GEOMETRY_PARTS)? {
+				add_geometry(&part, geometry, 3)?;
+			}
+		},
+		7 => {
+			for part in table.get_tables(GEOMETRY_PARTS)? {
+				add_geometry(&part, geometry, 0)?;
+			}
+		},
+		_ => return Err(format!("unknown FlatGeobuf geometry type: {}", geometry_type)),
+	}
+	return Ok(());
+}
+
+fn read_points(table: &Table) -> Result<Vec<Point>, String> {
+	let xy = table.get_f64_vector(GEOMETRY_XY)?;
+	return Ok(xy.chunks_exact(2).map(|c| Point::new(c[0], c[1])).collect());
+}
+
+// rings of a polygon or lines of a multilinestring
+fn read_rings(table: &Table) -> Result<Vec<Vec<Point>>, String> {
+	let points = read_points(table)?;
+	if points.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let mut ends = table.get_u32_vector(GEOMETRY_ENDS)?;
+	if ends.is_empty() {
+		ends.push(points.len() as u32);
+	}
+
+	let mut rings: Vec<Vec<Point>> = Vec::new();
+	let mut start = 0;
+	for end in ends {
+		match points.get(start..end as usize) {
+			Some(ring) => rings.push(ring.to_vec()),
+			None => return Err(format!("corrupt FlatGeobuf file: ring from {} to {} of {} points", start, end, points.len())),
+		}
+		start = end as usize;
+	}
+	return Ok(rings);
+}
+
+
+
+// minimal read only access to flatbuffer tables
+// every read is checked against the buffer, a corrupt file gives an error instead of a panic
+struct Table<'a> {
+	buffer: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Table<'a> {
+	fn root(buffer: &'a [u8]) -> Result<Table<'a>, String> {
+		return Ok(Table { buffer, pos: read_u32(buffer, 0)? as usize });
+	}
+	// position of a field, or None if it is not set
+	fn field(&self, index: usize) -> Result<Option<usize>, String> {
+		let vtable = self.pos as i64 - read_i32(self.buffer, self.pos)? as i64;
+		let vtable = usize::try_from(vtable).map_err(|_| outside(vtable, self.buffer))?;
+		let vtable_size = read_u16(self.buffer, vtable)? as usize;
+		let entry = 4 + index * 2;
+		if entry >= vtable_size {
+			return Ok(None);
+		}
+		let offset = read_u16(self.buffer, vtable + entry)? as usize;
+		if offset == 0 {
+			return Ok(None);
+		}
+		return Ok(Some(self.pos + offset));
+	}
+	fn get_u8(&self, index: usize, default: u8) -> Result<u8, String> {
+		return match self.field(index)? {
+			Some(pos) => Ok(read_bytes::<1>(self.buffer, pos)?[0]),
+			None => Ok(default),
+		};
+	}
+	fn get_u16(&self, index: usize, default: u16) -> Result<u16, String> {
+		return match self.field(index)? {
+			Some(pos) => read_u16(self.buffer, pos),
+			None => Ok(default),
+		};
+	}
+	fn get_u64(&self, index: usize, default: u64) -> Result<u64, String> {
+		return match self.field(index)? {
+			Some(pos) => Ok(u64::from_le_bytes(read_bytes(self.buffer, pos)?)),
+			None => Ok(default),
+		};
+	}
+	fn indirect(&self, pos: usize) -> Result<usize, String> {
+		return Ok(pos + read_u32(self.buffer, pos)? as usize);
+	}
+	fn get_table(&self, index: usize) -> Result<Option<Table<'a>>, String> {
+		return match self.field(index)? {
+			Some(pos) => Ok(Some(Table { buffer: self.buffer, pos: self.indirect(pos)? })),
+			None => Ok(None),
+		};
+	}
+	// returns position and length of a vector, whose elements have element_size bytes
+	fn get_vector(&self, index: usize, element_size: usize) -> Result<Option<(usize, usize)>, String> {
+		let pos = match self.field(index)? {
+			Some(pos) => pos,
+			None => return Ok(None),
+		};
+		let vector = self.indirect(pos)?;
+		let length = read_u32(self.buffer, vector)? as usize;
+		let end = vector + 4 + length * element_size;
+		if end > self.buffer.len() {
+			return Err(outside(end as i64, self.buffer));
+		}
+		return Ok(Some((vector + 4, length)));
+	}
+	fn get_tables(&self, index: usize) -> Result<Vec<Table<'a>>, String> {
+		return match self.get_vector(index, 4)? {
+			Some((start, length)) => (0..length)
+				.map(|i| Ok(Table { buffer: self.buffer, pos: self.indirect(start + i * 4)? }))
+				.collect(),
+			None => Ok(Vec::new()),
+		};
+	}
+	fn get_u32_vector(&self, index: usize) -> Result<Vec<u32>, String> {
+		return match self.get_vector(index, 4)? {
+			Some((start, length)) => (0..length).map(|i| read_u32(self.buffer, start + i * 4)).collect(),
+			None => Ok(Vec::new()),
+		};
+	}
+	fn get_f64_vector(&self, index: usize) -> Result<Vec<f64>, String> {
+		return match self.get_vector(index, 8)? {
+			Some((start, length)) => (0..length).map(|i| read_f64(self.buffer, start + i * 8)).collect(),
+			None => Ok(Vec::new()),
+		};
+	}
+}
+
+fn outside(pos: i64, buffer: &[u8]) -> String {
+	return format!("corrupt FlatGeobuf file: position {} is outside of a flatbuffer of {} bytes", pos, buffer.len());
+}
+
+fn read_bytes<const N: usize>(buffer: &[u8], pos: usize) -> Result<[u8; N], String> {
+	return match buffer.get(pos..pos + N) {
+		Some(bytes) => Ok(bytes.try_into().unwrap()),
+		None => Err(outside(pos as i64, buffer)),
+	};
+}
+
+fn read_u16(buffer: &[u8], pos: usize) -> Result<u16, String> {
+	return Ok(u16::from_le_bytes(read_bytes(buffer, pos)?));
+}
+
+fn read_u32(buffer: &[u8], pos: usize) -> Result<u32, String> {
+	return Ok(u32::from_le_bytes(read_bytes(buffer, pos)?));
+}
+
+fn read_i32(buffer: &[u8], pos: usize) -> Result<i32, String> {
+	return Ok(i32::from_le_bytes(read_bytes(buffer, pos)?));
+}
+
+fn read_f64(buffer: &[u8], pos: usize) -> Result<f64, String> {
+	return Ok(f64::from_le_bytes(read_bytes(buffer, pos)?));
+}
+
+
+
+// packed Hilbert R-tree: all nodes are stored level by level, root first, leaves last
+struct PackedRTree {
+	num_items: u64,
+	num_nodes: u64,
+	node_size: u64,
+	level_bounds: Vec<(u64, u64)>,
+}
+
+impl PackedRTree {
+	fn new(num_items: u64, node_size: u64) -> PackedRTree {
+		let node_size = node_size.max(2);
+
+		let mut n = num_items;
+		let mut num_nodes = n;
+		let mut level_num_nodes: Vec<u64> = vec![n];
+		loop {
+			n = n.div_ceil(node_size);
+			num_nodes += n;
+			level_num_nodes.push(n);
+			if n == 1 {
+				break;
+			}
+		}
+
+		let mut level_bounds: Vec<(u64, u64)> = Vec::new();
+		let mut offset = num_nodes;
+		for size in level_num_nodes {
+			offset -= size;
+			level_bounds.push((offset, offset + size));
+		}
+
+		return PackedRTree { num_items, num_nodes, node_size, level_bounds };
+	}
+	// returns the byte offsets of all features, whose bbox overlaps
+	fn search(&self, file: &mut BufReader<File>, index_start: u64, bbox: &Bbox) -> Result<Vec<u64>, String> {
+		let mut offsets: Vec<u64> = Vec::new();
+		let mut queue: Vec<(u64, usize)> = vec![(0, self.level_bounds.len() - 1)];
+
+		while let Some((node_index, level)) = queue.pop() {
+			let is_leaf = node_index >= self.num_nodes - self.num_items;
+			let end = (node_index + self.node_size).min(self.level_bounds[level].1);
+
+			let mut buffer = vec![0u8; ((end - node_index) * NODE_SIZE) as usize];
+			file.seek(SeekFrom::Start(index_start + node_index * NODE_SIZE)).map_err(truncated)?;
+			file.read_exact(&mut buffer).map_err(truncated)?;
+
+			for node in buffer.chunks_exact(NODE_SIZE as usize) {
+				let node_bbox = Bbox::from_coordinates(
+					read_f64(node, 0)?,
+					read_f64(node, 8)?,
+					read_f64(node, 16)?,
+					read_f64(node, 24)?,
+				);
+				if !node_bbox.overlaps_bbox(bbox) {
+					continue;
+				}
+				let offset = u64::from_le_bytes(read_bytes(node, 32)?);
+				if is_leaf {
+					offsets.push(offset);
+					continue;
+				}
+				// the children have to be on the level below
+				if (level == 0) || (offset < self.level_bounds[level - 1].0) || (offset >= self.level_bounds[level - 1].1) {
+					return Err(format!("corrupt FlatGeobuf file: R-tree node {} points to node {}", node_index, offset));
+				}
+				queue.push((offset, level - 1));
+			}
+		}
+
+		offsets.sort_unstable();
+		return Ok(offsets);
+	}
+}
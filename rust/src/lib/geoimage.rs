@@ -1,430 +1,689 @@
-#[path = "geometry.rs"]
-mod geometry;
-
-pub mod geoimage {
-	use crate::geometry::geometry::{Point, Collection, Geometry};
-	use image;
-	use serde::{Deserialize, Serialize};
-	use std::fs::{File,create_dir_all};
-	use std::io::{Read, Write};
-	use std::panic;
-	use std::path::{Path,PathBuf};
-
-	const PI: f32 = std::f32::consts::PI;
-
-	pub struct LayoutItem {
-		pub index: usize,
-		pub x: u32,
-		pub y: u32,
-	}
-	pub const LAYOUT:[LayoutItem;4] = [
-		LayoutItem{index:0, x:0, y:0},
-		LayoutItem{index:1, x:1, y:0},
-		LayoutItem{index:2, x:0, y:1},
-		LayoutItem{index:3, x:1, y:1}
-	];
-
-	#[derive(Serialize, Deserialize, PartialEq, Debug)]
-	pub struct Channel {
-		width: u32,
-		height: u32,
-		data: Vec<f32>,
-	}
-	impl Channel {
-		pub fn new(width: u32, height: u32, value:f32) -> Channel {
-			let length = (width*height) as usize;
-			let mut channel = Channel{
-				width,
-				height,
-				data:Vec::with_capacity(length)
-			};
-			channel.data.resize(length, value);
-			return channel;
-		}
-		pub fn set_pixel_value(&mut self, x: u32, y: u32, distance: f32) {
-			if x >= self.width {
-				panic!();
-			}
-
-			if y >= self.height {
-				panic!();
-			}
+use crate::bintile::{self, Compression};
+use crate::encoding::{build_image, PixelEncoding, ValueCurve};
+use crate::error::{Error, Result};
+use crate::geometry::{Point, Collection, Geometry};
+use crate::output::{DirectoryOutput, TileOutput};
+use crate::tiles::TileCoord;
+use image::{self, DynamicImage, ImageEncoder};
+use image::codecs::png::PngEncoder;
+use std::fmt;
+use std::panic;
+use std::path::{Path,PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::thread;
+
+const PI: f64 = std::f64::consts::PI;
+// of the sphere of web mercator, in meters
+const EARTH_RADIUS: f64 = 6378137.0;
+
+pub struct LayoutItem {
+	pub index: usize,
+	pub x: u32,
+	pub y: u32,
+}
+pub const LAYOUT:[LayoutItem;4] = [
+	LayoutItem{index:0, x:0, y:0},
+	LayoutItem{index:1, x:1, y:0},
+	LayoutItem{index:2, x:0, y:1},
+	LayoutItem{index:3, x:1, y:1}
+];
+
+// what a channel contains, saved in the header of bin tiles
+#[derive(Clone, PartialEq, Debug)]
+pub struct ChannelInfo {
+	pub name: String,
+	// the values, that the pixel values 0.0 and 1.0 stand for, e.g. [min_distance, max_distance] in meters
+	pub value_range: [f32; 2],
+	// initial value, also used for missing tiles when merging
+	pub fill_value: f32,
+	// how the values are spread over the pixel values of images, see encoding.rs
+	pub curve: ValueCurve,
+}
+impl ChannelInfo {
+	pub fn new(name: &str, value_range: [f32; 2], fill_value: f32) -> ChannelInfo {
+		return ChannelInfo { name: name.to_string(), value_range, fill_value, curve: ValueCurve::Linear };
+	}
+	pub fn with_curve(mut self, curve: ValueCurve) -> ChannelInfo {
+		self.curve = curve;
+		return self;
+	}
+}
 
-			let index:usize = (x + y * self.width) as usize;
-			self.data[index] = distance;
-		}
+#[derive(PartialEq, Debug)]
+pub struct Channel {
+	pub(crate) width: u32,
+	pub(crate) height: u32,
+	pub(crate) info: ChannelInfo,
+	pub(crate) data: Vec<f32>,
+}
+impl Channel {
+	pub fn new(width: u32, height: u32, value:f32) -> Channel {
+		return Channel::with_info(width, height, ChannelInfo::new("", [0.0, 1.0], value));
 	}
+	pub fn with_info(width: u32, height: u32, info: ChannelInfo) -> Channel {
+		let length = (width*height) as usize;
+		let mut channel = Channel{
+			width,
+			height,
+			data:Vec::with_capacity(length),
+			info,
+		};
+		channel.data.resize(length, channel.info.fill_value);
+		return channel;
+	}
+	pub fn set_pixel_value(&mut self, x: u32, y: u32, distance: f32) {
+		if x >= self.width {
+			panic!();
+		}
 
-	#[derive(Serialize, Deserialize, PartialEq, Debug)]
-	pub struct GeoImage {
-		pub size: u32,
-		zoom: u32,
-		x_offset: u32,
-		y_offset: u32,
-		x0: f32,
-		y0: f32,
-		pixel_scale: f32,
-		channels: Vec<Channel>,
-	}
-
-	impl GeoImage {
-		pub fn new(size: u32, zoom: u32, x_offset: u32, y_offset: u32) -> GeoImage {
-			let scale = (2.0_f32).powf(zoom as f32);
-			return GeoImage {
-				size,
-				zoom,
-				x_offset,
-				y_offset,
-				x0: (x_offset as f32) / scale,
-				y0: (y_offset as f32) / scale,
-				pixel_scale: 1.0 / (size as f32) / scale,
-				channels: Vec::from([
-					Channel::new(size, size, 1.0e6),
-					Channel::new(size, size, 0.0)
-				]),
-			};
+		if y >= self.height {
+			panic!();
 		}
-		fn get_pixel_as_point(&self, x: u32, y: u32) -> Point {
-			return Point::new(
-				demercator_x((x as f32) * self.pixel_scale + self.x0),
-				demercator_y((y as f32) * self.pixel_scale + self.y0),
-			);
+
+		let index:usize = (x + y * self.width) as usize;
+		self.data[index] = distance;
+	}
+	pub fn paste(&mut self, channel: &Channel, x0: u32, y0: u32) {
+		if (x0 + channel.width > self.width) || (y0 + channel.height > self.height) {
+			panic!();
 		}
-		pub fn get_point_min(&self) -> Point {
-			return self.get_pixel_as_point(0, self.size);
+
+		let width = channel.width as usize;
+		for y in 0..channel.height {
+			let index0 = (y * channel.width) as usize;
+			let index1 = ((y + y0) * self.width + x0) as usize;
+			self.data[index1..index1+width].copy_from_slice(&channel.data[index0..index0+width]);
 		}
-		pub fn get_point_max(&self) -> Point {
-			return self.get_pixel_as_point(self.size, 0);
+	}
+}
+
+// file format of exported tiles
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TileFormat {
+	// 3 channels per file as RGB, see export
+	Png,
+	// 3 channels per file as RGB, lossless for near_lossless = 100,
+	// lower values allow small changes of the pixel values for smaller files (like cwebp -near_lossless)
+	Webp { near_lossless: u8 },
+	// all channels as f32, see bintile.rs
+	Bin(Compression),
+}
+
+impl TileFormat {
+	pub fn extension(&self) -> &'static str {
+		return match self {
+			TileFormat::Png => ".png",
+			TileFormat::Webp { .. } => ".webp",
+			TileFormat::Bin(_) => ".bin",
+		};
+	}
+}
+
+// how the pixels of the lower zoom levels are calculated:
+// - Box: mean of all pixels of the block (default)
+// - Min: smallest value of the block, conservative for distances
+// - Max: largest value of the block, what Min means for coverages and masks (see Layer::channel_downsampling)
+// - Center: value at the center of the block, interpolated from the central pixel(s)
+// - Recompute: rendered again from the geometries, see tiles.rs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Downsampling {
+	Box,
+	Min,
+	Max,
+	Center,
+	Recompute,
+}
+
+impl Downsampling {
+	pub fn from_name(name: &str) -> Option<Downsampling> {
+		return match name {
+			"box" => Some(Downsampling::Box),
+			"min" => Some(Downsampling::Min),
+			"center" => Some(Downsampling::Center),
+			"recompute" => Some(Downsampling::Recompute),
+			_ => None,
+		};
+	}
+}
+
+#[derive(PartialEq, Debug)]
+pub struct GeoImage {
+	pub size: u32,
+	pub(crate) zoom: u32,
+	pub(crate) x_offset: u32,
+	pub(crate) y_offset: u32,
+	x0: f64,
+	y0: f64,
+	pixel_scale: f64,
+	pub(crate) channels: Vec<Channel>,
+}
+
+impl GeoImage {
+	// creates an image with one unnamed channel per fill value
+	pub fn new(size: u32, zoom: u32, x_offset: u32, y_offset: u32, fill_values: &[f32]) -> GeoImage {
+		let infos:Vec<ChannelInfo> = fill_values.iter().map(|value| ChannelInfo::new("", [0.0, 1.0], *value)).collect();
+		return GeoImage::with_channels(size, zoom, x_offset, y_offset, &infos);
+	}
+	pub fn with_channels(size: u32, zoom: u32, x_offset: u32, y_offset: u32, infos: &[ChannelInfo]) -> GeoImage {
+		let scale = (2.0_f64).powf(zoom as f64);
+		return GeoImage {
+			size,
+			zoom,
+			x_offset,
+			y_offset,
+			x0: (x_offset as f64) / scale,
+			y0: (y_offset as f64) / scale,
+			pixel_scale: 1.0 / (size as f64) / scale,
+			channels: infos.iter().map(|info| Channel::with_info(size, size, info.clone())).collect(),
+		};
+	}
+	pub fn channel_count(&self) -> usize {
+		return self.channels.len();
+	}
+	pub fn channel_infos(&self) -> Vec<ChannelInfo> {
+		return self.channels.iter().map(|channel| channel.info.clone()).collect();
+	}
+	// pixel values of a channel, row by row
+	pub fn channel_data(&self, index: usize) -> &[f32] {
+		return &self.channels[index].data;
+	}
+	fn get_pixel_as_point(&self, x: u32, y: u32) -> Point {
+		return self.get_position_as_point(x as f64, y as f64);
+	}
+	// (0, 0) is the top left corner of the image, (size, size) the bottom right one
+	pub(crate) fn get_position_as_point(&self, x: f64, y: f64) -> Point {
+		return Point::new(
+			demercator_x(x * self.pixel_scale + self.x0),
+			demercator_y(y * self.pixel_scale + self.y0),
+		);
+	}
+	// area of a pixel in row y in m², on the sphere of web mercator:
+	// R² × width in radians × difference of sin(latitude) between top and bottom of the row
+	pub fn pixel_area(&self, y: u32) -> f64 {
+		let top = self.get_position_as_point(0.0, y as f64).y.to_radians().sin();
+		let bottom = self.get_position_as_point(0.0, (y + 1) as f64).y.to_radians().sin();
+		return EARTH_RADIUS * EARTH_RADIUS * self.pixel_scale * 2.0 * PI * (top - bottom);
+	}
+	pub fn get_point_min(&self) -> Point {
+		return self.get_pixel_as_point(0, self.size);
+	}
+	pub fn get_point_max(&self) -> Point {
+		return self.get_pixel_as_point(self.size, 0);
+	}
+	pub fn tile_coord(&self) -> TileCoord {
+		return TileCoord { zoom: self.zoom, x: self.x_offset, y: self.y_offset };
+	}
+	// the files of the tile: one bin, or images with the channels encoded as pixels, see encoding.rs
+	// ("x.png", "x-1.png", … for rgb8 channels 0-2, 3-5, …)
+	pub fn encode_images(&self, format: &TileFormat, encoding: PixelEncoding) -> Result<Vec<Vec<u8>>> {
+		if let TileFormat::Bin(compression) = format {
+			return Ok(vec![bintile::encode(self, *compression)]);
 		}
-		fn export(&self, filename: &Path) {
-			let extension = filename.extension().unwrap().to_str().unwrap();
-			match extension {
-				"bin" => {
-					let buf: Vec<u8> = bincode::serialize(&self).unwrap();
-					let mut file = File::create(filename).unwrap();
-					let _result = file.write_all(&buf);
-				},
-				"png" => {
-					let size = self.size as u32;
-					let channel0 = &self.channels[0];
-					let channel1 = &self.channels[1];
-					let img = image::ImageBuffer::from_fn(size, size, |x, y| {
-						let index = (x + y * size) as usize;
-						let v0 = channel0.data[index].max(0.0).min(1.0);
-						let v1 = channel1.data[index].max(0.0).min(1.0);
-						return image::Rgb([
-							(v0*255.0) as u8,
-							(v1*255.0) as u8,
-							0u8,
-						]);
-					});
-					let _result = img.save(filename);
+
+		// an image without channels stays black
+		let encoding = if self.channels.is_empty() { PixelEncoding::Rgb8 } else { encoding };
+		let per_image = encoding.channels_per_image();
+		let group_count = self.channels.len().div_ceil(per_image).max(1);
+		let mut images: Vec<Vec<u8>> = Vec::with_capacity(group_count);
+		for group in 0..group_count {
+			let channels:Vec<&Channel> = self.channels.iter().skip(group*per_image).take(per_image).collect();
+			let img = build_image(encoding, &channels, self.size);
+			let buffer = match (format, img) {
+				(TileFormat::Webp { near_lossless }, DynamicImage::ImageRgb8(img)) => encode_webp(&img, *near_lossless),
+				(TileFormat::Webp { .. }, _) => Err(format!("webp does not support the pixel encoding {}", encoding.name())),
+				// write_image takes the samples in native byte order and writes 16 bit big endian, as png wants
+				(_, img) => {
+					let mut buffer: Vec<u8> = Vec::new();
+					PngEncoder::new(&mut buffer).write_image(img.as_bytes(), img.width(), img.height(), img.color()).map(|_| buffer).map_err(|e| e.to_string())
 				},
-				_ => {
-					println!("unknown extension: {}", extension);
-					panic!();
-				}
-			}
+			};
+			images.push(buffer.map_err(|e| Error::Tile(format!("tile {}/{}/{}: {}", self.zoom, self.y_offset, self.x_offset, e)))?);
 		}
-		pub fn load(filename: &Path) -> GeoImage {
-			let mut buffer: Vec<u8> = Vec::new();
-			let mut file = File::open(filename).unwrap();
-			let _result = file.read_to_end(&mut buffer);
-			let image: GeoImage = bincode::deserialize(&buffer).unwrap();
-			return image;
+		return Ok(images);
+	}
+	// every pixel has the fill value of its channel, so the tile looks like one far away from all geometries
+	pub fn is_uniform_default(&self) -> bool {
+		return self.channels.iter().all(|channel| channel.data.iter().all(|value| *value == channel.info.fill_value));
+	}
+	// reads bin tiles of all versions, see bintile.rs
+	pub fn load(filename: &Path) -> Result<GeoImage> {
+		let (image, _file_info) = bintile::load(filename)?;
+		return Ok(image);
+	}
+	pub fn scaled_down_clone(&self, new_size: u32) -> GeoImage {
+		return self.scaled_down(new_size, Downsampling::Box);
+	}
+	// the same tile with fewer pixels, every pixel is calculated from a block of f1×f1 pixels
+	pub fn scaled_down(&self, new_size: u32, downsampling: Downsampling) -> GeoImage {
+		return self.scaled_down_channels(new_size, &vec![downsampling; self.channels.len()]);
+	}
+	// like scaled_down, with the downsampling of every channel
+	pub fn scaled_down_channels(&self, new_size: u32, downsamplings: &[Downsampling]) -> GeoImage {
+		if new_size >= self.size {
+			panic!()
 		}
-		pub fn scaled_down_clone(&self, new_size: u32) -> GeoImage {
-			if new_size >= self.size {
-				panic!()
-			}
-
-			let f1 = self.size / new_size;
-			let f2 = (f1 * f1) as f32;
 
-			let mut clone = GeoImage::new(new_size, self.zoom, self.x_offset, self.y_offset);
+		let f1 = self.size / new_size;
+		let mut clone = GeoImage::with_channels(new_size, self.zoom, self.x_offset, self.y_offset, &self.channel_infos());
 
-			let channel_count = self.channels.len();
+		let clone_size = clone.size;
 
-			for i in 0..channel_count {
-				let channel0 = &self.channels[i];
-				let channel1 = &mut clone.channels[i];
-				let clone_size = clone.size;
-
-				for y0 in 0..clone_size {
-					for x0 in 0..clone_size {
-						let mut sum = 0.0f32;
-						for yd in 0..f1 {
-							for xd in 0..f1 {
-								let index = ((y0 * f1 + yd) * self.size + (x0 * f1 + xd)) as usize;
-								sum += channel0.data[index]
-							}
+		for ((channel0, channel1), &downsampling) in self.channels.iter().zip(clone.channels.iter_mut()).zip(downsamplings) {
+			// the pixels of the block, that are used: the center is a pixel or the corner of the central 2×2 pixels
+			let (start, count) = match downsampling {
+				Downsampling::Box | Downsampling::Min | Downsampling::Max => (0, f1),
+				Downsampling::Center => ((f1 - 1) / 2, 2 - f1 % 2),
+				Downsampling::Recompute => panic!("recompute needs the geometries, see tiles.rs"),
+			};
+			let f2 = (count * count) as f32;
+
+			for y0 in 0..clone_size {
+				for x0 in 0..clone_size {
+					let mut sum = 0.0f32;
+					let mut min = f32::INFINITY;
+					let mut max = f32::NEG_INFINITY;
+					for yd in start..start + count {
+						for xd in start..start + count {
+							let index = ((y0 * f1 + yd) * self.size + (x0 * f1 + xd)) as usize;
+							sum += channel0.data[index];
+							min = min.min(channel0.data[index]);
+							max = max.max(channel0.data[index]);
 						}
-						let index0 = (y0 * clone_size + x0) as usize;
-						channel1.data[index0] = sum / f2;
 					}
+					let index0 = (y0 * clone_size + x0) as usize;
+					channel1.data[index0] = match downsampling {
+						Downsampling::Min => min,
+						Downsampling::Max => max,
+						_ => sum / f2,
+					};
 				}
 			}
-
-			return clone;
 		}
-		pub fn merge(tiles: [Option<GeoImage>;4], size:u32, zoom:u32, x_offset:u32, y_offset:u32) -> GeoImage {
-			if tiles.len() != 4 {
-				panic!("need 4")
+
+		return clone;
+	}
+	// "infos" are the channels of the merged image, so it keeps them, even if all 4 tiles are missing.
+	// a missing tile is uniform default: filled with the fill values,
+	// exactly like a rendered tile without geometries nearby
+	pub fn merge(tiles: [Option<GeoImage>;4], infos: &[ChannelInfo], size:u32, zoom:u32, x_offset:u32, y_offset:u32) -> Result<GeoImage> {
+		let half_size = size/2;
+		let mut image = GeoImage::with_channels(size, zoom, x_offset, y_offset, infos);
+
+		for item in LAYOUT {
+			if tiles[item.index].is_none() {
+				continue;
+			}
+		
+			let tile: &GeoImage = tiles[item.index].as_ref().unwrap();
+
+			let position = format!("tile {}/{}/{}", zoom + 1, y_offset * 2 + item.y, x_offset * 2 + item.x);
+			if tile.size != half_size {
+				return Err(Error::Tile(format!("{}: wrong size {}, expected {}", position, tile.size, half_size)));
+			};
+			if tile.zoom != zoom + 1 {
+				return Err(Error::Tile(format!("{}: wrong zoom {}", position, tile.zoom)));
+			};
+			if tile.x_offset != x_offset * 2 + item.x {
+				return Err(Error::Tile(format!("{}: wrong x_offset {}", position, tile.x_offset)));
+			};
+			if tile.y_offset != y_offset * 2 + item.y {
+				return Err(Error::Tile(format!("{}: wrong y_offset {}", position, tile.y_offset)));
+			};
+			if tile.channels.len() != image.channels.len() {
+				return Err(Error::Tile(format!("{}: wrong channel count {}, expected {}", position, tile.channels.len(), image.channels.len())));
 			};
 			
-			let half_size = size/2;
-			let mut image = GeoImage::new(size, zoom, x_offset, y_offset);
-
-			for item in LAYOUT {
-				if tiles[item.index].is_none() {
-					continue;
-				}
+			let offset = item.x * half_size + item.y * half_size * size;
 			
-				let tile: &GeoImage = tiles[item.index].as_ref().unwrap();
-
-				if tile.size != half_size {
-					panic!("wrong size")
-				};
-				if tile.zoom != zoom + 1 {
-					panic!("wrong zoom")
-				};
-				if tile.x_offset != x_offset * 2 + item.x {
-					panic!("wrong x_offset")
-				};
-				if tile.y_offset != y_offset * 2 + item.y {
-					panic!("wrong y_offset")
-				};
-				
-				let offset = item.x * half_size + item.y * half_size * size;
-				
-				for i in 0..tile.channels.len() {
-					let channel0 = &tile.channels[i];
-					let channel1 = &mut image.channels[i];
-
-					for y in 0..half_size {
-						for x in 0..half_size {
-							let i0 = (y * size + x + offset) as usize;
-							let i1 = (y * half_size + x) as usize;
-							channel1.data[i0] = channel0.data[i1];
-						}
+			for i in 0..tile.channels.len() {
+				let channel0 = &tile.channels[i];
+				let channel1 = &mut image.channels[i];
+
+				for y in 0..half_size {
+					for x in 0..half_size {
+						let i0 = (y * size + x + offset) as usize;
+						let i1 = (y * half_size + x) as usize;
+						channel1.data[i0] = channel0.data[i1];
 					}
 				}
 			}
+		}
 
-			return image;
+		return Ok(image);
+	}
+	// with skip_uniform, tiles that are uniform default are not saved
+	pub fn export_tile_tree(&self, tile_size: u32, output: &dyn TileOutput, skip_uniform: bool) -> Result<()> {
+		// all tiles of all levels would be skipped
+		if skip_uniform && self.is_uniform_default() {
+			return Ok(());
 		}
-		pub fn export_tile_tree(&self, tile_size: u32, folder: &Path, extension: &str) {
-			self.export_tile_layer(tile_size, folder, extension);
+		self.export_tile_layer(tile_size, output, skip_uniform)?;
 
-			if self.size > tile_size {
-				let image = self.scaled_down_clone(self.size/2);
-				image.export_tile_tree(tile_size, folder, extension)
-			}
+		if self.size > tile_size {
+			let image = self.scaled_down_clone(self.size/2);
+			image.export_tile_tree(tile_size, output, skip_uniform)?;
 		}
-		fn export_tile_layer(&self, tile_size: u32, folder: &Path, extension: &str) {
-			let n = self.size / tile_size;
-			let dz = n.trailing_zeros();
+		return Ok(());
+	}
+	// like export_tile_tree, but every level is calculated from this image by downsample(image, size)
+	pub fn export_tile_tree_with<F>(&self, tile_size: u32, output: &dyn TileOutput, skip_uniform: bool, downsample: F) -> Result<()> where F: Fn(&GeoImage, u32) -> GeoImage {
+		if skip_uniform && self.is_uniform_default() {
+			return Ok(());
+		}
+		self.export_tile_layer(tile_size, output, skip_uniform)?;
 
-			if tile_size*2u32.pow(dz) != self.size {
-				println!("self.size {}, tile_size {}, n {}, dz {}", self.size, tile_size, n, dz);
-				panic!()
-			}
+		let mut size = self.size / 2;
+		while size >= tile_size {
+			downsample(self, size).export_tile_layer(tile_size, output, skip_uniform)?;
+			size /= 2;
+		}
+		return Ok(());
+	}
+	fn export_tile_layer(&self, tile_size: u32, output: &dyn TileOutput, skip_uniform: bool) -> Result<()> {
+		// the image has to split into n x n tiles, with n a power of 2
+		let n = self.size / tile_size;
+		if !n.is_power_of_two() || (tile_size * n != self.size) {
+			return Err(Error::Tile(format!("tile {}/{}/{}: size {} can not be split into tiles of size {}", self.zoom, self.y_offset, self.x_offset, self.size, tile_size)));
+		}
 
-			for dy in 0..n {
-				for dx in 0..n {
-					let tile = self.extract_subtile(dx, dy, tile_size);
-					tile.export_to(folder, extension);
+		for dy in 0..n {
+			for dx in 0..n {
+				let tile = self.extract_subtile(dx, dy, tile_size);
+				if skip_uniform && tile.is_uniform_default() {
+					continue;
 				}
+				output.write_image(&tile)?;
 			}
 		}
-		pub fn export_to(&self, folder: &Path, extension: &str) {
-			self.export(&self.get_path(&folder, extension).as_path());
-		}
-		pub fn calc_path(folder: &Path, z: u32, y: u32, x: u32, extension: &str) -> PathBuf {
-			let mut filename = PathBuf::from(folder);
-
-			filename.push(z.to_string());
-			filename.push(y.to_string());
+		return Ok(());
+	}
+	// saves the tile as "z/y/x.ext" in the folder, see DirectoryOutput
+	pub fn export_to(&self, folder: &Path, format: &TileFormat) -> Result<()> {
+		return DirectoryOutput::new(folder, *format, PixelEncoding::Rgb8).write_image(self);
+	}
+	pub fn calc_path(folder: &Path, z: u32, y: u32, x: u32, extension: &str) -> PathBuf {
+		let mut filename = PathBuf::from(folder);
 
-			if create_dir_all(filename.as_path()).is_err() {
-				panic!();
-			}
+		filename.push(z.to_string());
+		filename.push(y.to_string());
+		filename.push(x.to_string() + extension);
 
-			filename.push(x.to_string() + extension);
+		return filename;
+	}
+	fn extract_subtile(&self, dx: u32, dy: u32, tile_size: u32) -> GeoImage {
+		let n = self.size / tile_size;
+		let dz = n.trailing_zeros();
 
-			return filename;
+		if tile_size*2u32.pow(dz) != self.size {
+			panic!()
 		}
-		fn get_path(&self, folder: &Path, extension: &str) -> PathBuf {
-			return GeoImage::calc_path(folder, self.zoom, self.y_offset, self.x_offset, extension);
-		}
-		fn extract_subtile(&self, dx: u32, dy: u32, tile_size: u32) -> GeoImage {
-			let n = self.size / tile_size;
-			let dz = n.trailing_zeros();
-
-			if tile_size*2u32.pow(dz) != self.size {
-				panic!()
-			}
 
-			let mut clone = GeoImage::new(
-				tile_size,
-				self.zoom + dz,
-				self.x_offset*n + dx,
-				self.y_offset*n + dy
-			);
-
-			for i in 0..self.channels.len() {
-				let channel0 = &self.channels[i];
-				let channel1 = &mut clone.channels[i];
-
-				for y1 in 0..clone.size {
-					for x1 in 0..clone.size {
-						let y0 = dy*tile_size + y1;
-						let x0 = dx*tile_size + x1;
-						let index0 = (y0 *  self.size + x0) as usize;
-						let index1 = (y1 * clone.size + x1) as usize;
-						channel1.data[index1] = channel0.data[index0];
-					}
+		let mut clone = GeoImage::with_channels(
+			tile_size,
+			self.zoom + dz,
+			self.x_offset*n + dx,
+			self.y_offset*n + dy,
+			&self.channel_infos()
+		);
+
+		for i in 0..self.channels.len() {
+			let channel0 = &self.channels[i];
+			let channel1 = &mut clone.channels[i];
+
+			for y1 in 0..clone.size {
+				for x1 in 0..clone.size {
+					let y0 = dy*tile_size + y1;
+					let x0 = dx*tile_size + x1;
+					let index0 = (y0 *  self.size + x0) as usize;
+					let index1 = (y1 * clone.size + x1) as usize;
+					channel1.data[index1] = channel0.data[index0];
 				}
 			}
-
-			return clone;
 		}
-		pub fn draw_distances(&mut self, channel_index:usize, collection:&Collection, min_distance:f32, max_distance:f32) {
-			struct Env<'a> {
-				channel: &'a mut Channel,
-				collection: &'a Collection,
-				min_distance:f32,
-				max_distance:f32,
-				x0: f32,
-				y0: f32,
-				pixel_scale: f32,
-			}
-			let mut env = Env {
-				channel: &mut self.channels[channel_index],
-				collection,
-				min_distance,
-				max_distance,
-				x0: self.x0,
-				y0: self.y0,
-				pixel_scale: self.pixel_scale,
-			};
 
-			let x_lef = demercator_x(env.x0);
-			let x_rig = demercator_x((self.size as f32) * env.pixel_scale + env.x0);
-			let y_bot = demercator_y((self.size as f32) * env.pixel_scale + env.y0);
-			let y_top = demercator_y(env.y0);
-
-			let mut geometry = collection.geometry.clone_cut_top(y_top);
+		return clone;
+	}
+	pub fn draw_distances(&mut self, channel_index:usize, collection:&Collection, min_distance:f32, max_distance:f32, threads:usize) {
+		self.draw_blocks(channel_index, vec![&collection.geometry], threads, |point, geometries| {
+			let mut distance = collection.get_min_distance(point, max_distance as f64) as f32;
+			if geometries[0].contains_point(point) {
+				distance = -distance;
+			}
+			return (distance-min_distance)/(max_distance-min_distance);
+		});
+	}
+	// every pixel value is calculated by "pixel_value", using the pixel center and
+	// the geometries cut down to the surrounding of the pixel, so that contains_point is fast.
+	// the image is split into blocks, that are rendered on all threads
+	pub fn draw_blocks<F>(&mut self, channel_index:usize, geometries:Vec<&Geometry>, threads:usize, pixel_value:F) where F: Fn(&Point, &[Geometry]) -> f32 + Sync {
+		struct Env<F> {
+			pixel_value: F,
+			x0: f64,
+			y0: f64,
+			pixel_scale: f64,
+		}
+		struct Block {
+			xi: u32,
+			yi: u32,
+			width: u32,
+			height: u32,
+			geometries: Vec<Geometry>,
+		}
+		let env = Env {
+			pixel_value,
+			x0: self.x0,
+			y0: self.y0,
+			pixel_scale: self.pixel_scale,
+		};
+
+		let x_lef = demercator_x(env.x0);
+		let x_rig = demercator_x((self.size as f64) * env.pixel_scale + env.x0);
+		let y_bot = demercator_y((self.size as f64) * env.pixel_scale + env.y0);
+		let y_top = demercator_y(env.y0);
+
+		let geometries:Vec<Geometry> = geometries.iter().map(|geometry| {
+			let mut geometry = geometry.clone_cut_top(y_top);
 			geometry = geometry.clone_cut_bot(y_bot);
 			geometry = geometry.clone_cut_lef(x_lef);
-			geometry = geometry.clone_cut_rig(x_rig);
-		
-			recursion(&mut env, &geometry, 0, 0, self.size);
-
-			fn recursion(env:&mut Env, geometry:&Geometry, xi:u32, yi:u32, size:u32) {
-				let xc = demercator_x(((xi as f32) + (size as f32)/2.0) * env.pixel_scale + env.x0);
-				let yc = demercator_y(((yi as f32) + (size as f32)/2.0) * env.pixel_scale + env.y0);
+			return geometry.clone_cut_rig(x_rig);
+		}).collect();
+
+		let threads = if threads > 0 {
+			threads
+		} else {
+			thread::available_parallelism().map_or(1, |n| n.get())
+		};
+
+		// split the image into quadrants, until there are enough blocks to keep all threads busy.
+		// sizes, that are not a power of 2, give quadrants of different sizes: the first half gets the extra pixel
+		let mut blocks = vec![Block { xi:0, yi:0, width:self.size, height:self.size, geometries }];
+		while (blocks.len() < threads * 8) && (blocks[0].width >= 256) {
+			let mut sub_blocks:Vec<Block> = Vec::new();
+			for block in blocks {
+				let (w0, h0) = (block.width - block.width/2, block.height - block.height/2);
+				let (w1, h1) = (block.width/2, block.height/2);
+				let xc = demercator_x(((block.xi + w0) as f64) * env.pixel_scale + env.x0);
+				let yc = demercator_y(((block.yi + h0) as f64) * env.pixel_scale + env.y0);
+
+				let geometries_top = cut_all(&block.geometries, |g| g.clone_cut_bot(yc));
+				let geometries_bot = cut_all(&block.geometries, |g| g.clone_cut_top(yc));
+				sub_blocks.push(Block { xi:block.xi,    yi:block.yi,    width:w0, height:h0, geometries:cut_all(&geometries_top, |g| g.clone_cut_rig(xc)) });
+				sub_blocks.push(Block { xi:block.xi+w0, yi:block.yi,    width:w1, height:h0, geometries:cut_all(&geometries_top, |g| g.clone_cut_lef(xc)) });
+				sub_blocks.push(Block { xi:block.xi,    yi:block.yi+h0, width:w0, height:h1, geometries:cut_all(&geometries_bot, |g| g.clone_cut_rig(xc)) });
+				sub_blocks.push(Block { xi:block.xi+w0, yi:block.yi+h0, width:w1, height:h1, geometries:cut_all(&geometries_bot, |g| g.clone_cut_lef(xc)) });
+			}
+			blocks = sub_blocks;
+		}
 
-				if size == 1 {
-					let point = Point::new(xc, yc);
+		let next_block = AtomicUsize::new(0);
+		let channel = Mutex::new(&mut self.channels[channel_index]);
 
-					let mut distance = env.collection.get_min_distance(&point, env.max_distance);
-					if geometry.contains_point(&point) {
-						distance = -distance;
-					}
-					env.channel.set_pixel_value(xi, yi, (distance-env.min_distance)/(env.max_distance-env.min_distance));
-				} else {
-					let half_size = size/2;
-
-					if size < 128 {
-						{
-							recursion(env, &geometry, xi          , yi, half_size);
-							recursion(env, &geometry, xi+half_size, yi, half_size);
-							recursion(env, &geometry, xi          , yi+half_size, half_size);
-							recursion(env, &geometry, xi+half_size, yi+half_size, half_size);
-						}
-					} else {
-						{
-							let geometry_top = geometry.clone_cut_bot(yc);
-							recursion(env, &geometry_top.clone_cut_rig(xc), xi          , yi, half_size);
-							recursion(env, &geometry_top.clone_cut_lef(xc), xi+half_size, yi, half_size);
-						}
-						{
-							let geometry_bot = geometry.clone_cut_top(yc);
-							recursion(env, &geometry_bot.clone_cut_rig(xc), xi          , yi+half_size, half_size);
-							recursion(env, &geometry_bot.clone_cut_lef(xc), xi+half_size, yi+half_size, half_size);
+		thread::scope(|scope| {
+			for _ in 0..threads.min(blocks.len()) {
+				scope.spawn(|| {
+					loop {
+						let index = next_block.fetch_add(1, AtomicOrdering::Relaxed);
+						if index >= blocks.len() {
+							break;
 						}
+
+						let block = &blocks[index];
+						let mut result = Channel::new(block.width, block.height, 0.0);
+						recursion(&env, &mut result, block.xi, block.yi, &block.geometries, block.xi, block.yi, block.width, block.height);
+
+						channel.lock().unwrap().paste(&result, block.xi, block.yi);
 					}
-				}
+				});
 			}
+		});
+
+		fn cut_all<C>(geometries:&[Geometry], cut:C) -> Vec<Geometry> where C: Fn(&Geometry) -> Geometry {
+			return geometries.iter().map(cut).collect();
 		}
-		pub fn draw_geometry(&mut self, channel_index:usize, collection:&Collection) {
-			struct Env<'a> {
-				channel: &'a mut Channel,
-				x0: f32,
-				y0: f32,
-				pixel_scale: f32,
+
+		// result is a block of the image, starting at pixel (x_offset, y_offset).
+		// the block of width × height pixels at (xi, yi) is split into quadrants, empty ones are skipped
+		#[allow(clippy::too_many_arguments)]
+		fn recursion<F>(env:&Env<F>, result:&mut Channel, x_offset:u32, y_offset:u32, geometries:&[Geometry], xi:u32, yi:u32, width:u32, height:u32) where F: Fn(&Point, &[Geometry]) -> f32 {
+			if (width == 0) || (height == 0) {
+				return;
 			}
-			let mut env = Env {
-				channel: &mut self.channels[channel_index],
-				x0: self.x0,
-				y0: self.y0,
-				pixel_scale: self.pixel_scale,
-			};
-		
-			recursion(&mut env, &collection.geometry, 0, 0, self.size);
-
-			fn recursion(env:&mut Env, geometry:&Geometry, xi:u32, yi:u32, size:u32) {
-
-				if size == 1 {
-					let n = 4;
-					let nf = n as f32;
-					let mut sum = 0;
-					for xa in 0..n {
-						for ya in 0..n {
-							let xc = demercator_x(((xi as f32) + (xa as f32 + 0.5)/nf) * env.pixel_scale + env.x0);
-							let yc = demercator_y(((yi as f32) + (ya as f32 + 0.5)/nf) * env.pixel_scale + env.y0);
-							let point = Point::new(xc, yc);
-							if geometry.contains_point(&point) {
-								sum += 1;
-							}
+			if (width == 1) && (height == 1) {
+				let xc = demercator_x(((xi as f64) + 0.5) * env.pixel_scale + env.x0);
+				let yc = demercator_y(((yi as f64) + 0.5) * env.pixel_scale + env.y0);
+				let point = Point::new(xc, yc);
+				result.set_pixel_value(xi - x_offset, yi - y_offset, (env.pixel_value)(&point, geometries));
+				return;
+			}
+
+			let (w0, h0) = (width - width/2, height - height/2);
+			let (w1, h1) = (width/2, height/2);
+			if width.max(height) < 128 {
+				recursion(env, result, x_offset, y_offset, geometries, xi   , yi   , w0, h0);
+				recursion(env, result, x_offset, y_offset, geometries, xi+w0, yi   , w1, h0);
+				recursion(env, result, x_offset, y_offset, geometries, xi   , yi+h0, w0, h1);
+				recursion(env, result, x_offset, y_offset, geometries, xi+w0, yi+h0, w1, h1);
+			} else {
+				let xc = demercator_x(((xi + w0) as f64) * env.pixel_scale + env.x0);
+				let yc = demercator_y(((yi + h0) as f64) * env.pixel_scale + env.y0);
+				{
+					let geometries_top = cut_all(geometries, |g| g.clone_cut_bot(yc));
+					recursion(env, result, x_offset, y_offset, &cut_all(&geometries_top, |g| g.clone_cut_rig(xc)), xi   , yi, w0, h0);
+					recursion(env, result, x_offset, y_offset, &cut_all(&geometries_top, |g| g.clone_cut_lef(xc)), xi+w0, yi, w1, h0);
+				}
+				{
+					let geometries_bot = cut_all(geometries, |g| g.clone_cut_top(yc));
+					recursion(env, result, x_offset, y_offset, &cut_all(&geometries_bot, |g| g.clone_cut_rig(xc)), xi   , yi+h0, w0, h1);
+					recursion(env, result, x_offset, y_offset, &cut_all(&geometries_bot, |g| g.clone_cut_lef(xc)), xi+w0, yi+h0, w1, h1);
+				}
+			}
+		}
+
+	}
+	pub fn draw_geometry(&mut self, channel_index:usize, collection:&Collection) {
+		struct Env<'a> {
+			channel: &'a mut Channel,
+			x0: f64,
+			y0: f64,
+			pixel_scale: f64,
+		}
+		let mut env = Env {
+			channel: &mut self.channels[channel_index],
+			x0: self.x0,
+			y0: self.y0,
+			pixel_scale: self.pixel_scale,
+		};
+	
+		recursion(&mut env, &collection.geometry, 0, 0, self.size);
+
+		fn recursion(env:&mut Env, geometry:&Geometry, xi:u32, yi:u32, size:u32) {
+
+			if size == 1 {
+				let n = 4;
+				let nf = n as f64;
+				let mut sum = 0;
+				for xa in 0..n {
+					for ya in 0..n {
+						let xc = demercator_x(((xi as f64) + (xa as f64 + 0.5)/nf) * env.pixel_scale + env.x0);
+						let yc = demercator_y(((yi as f64) + (ya as f64 + 0.5)/nf) * env.pixel_scale + env.y0);
+						let point = Point::new(xc, yc);
+						if geometry.contains_point(&point) {
+							sum += 1;
 						}
 					}
-					env.channel.set_pixel_value(xi, yi, (sum as f32)/(nf*nf));
+				}
+				env.channel.set_pixel_value(xi, yi, ((sum as f64)/(nf*nf)) as f32);
+			} else {
+				let half_size = size/2;
+				let xc = demercator_x(((xi + half_size) as f64) * env.pixel_scale + env.x0);
+				let yc = demercator_y(((yi + half_size) as f64) * env.pixel_scale + env.y0);
+
+				if size < 16 {
+					{
+						recursion(env, geometry, xi          , yi, half_size);
+						recursion(env, geometry, xi+half_size, yi, half_size);
+						recursion(env, geometry, xi          , yi+half_size, half_size);
+						recursion(env, geometry, xi+half_size, yi+half_size, half_size);
+					}
 				} else {
-					let half_size = size/2;
-					let xc = demercator_x(((xi + half_size) as f32) * env.pixel_scale + env.x0);
-					let yc = demercator_y(((yi + half_size) as f32) * env.pixel_scale + env.y0);
-
-					if size < 16 {
-						{
-							recursion(env, &geometry, xi          , yi, half_size);
-							recursion(env, &geometry, xi+half_size, yi, half_size);
-							recursion(env, &geometry, xi          , yi+half_size, half_size);
-							recursion(env, &geometry, xi+half_size, yi+half_size, half_size);
-						}
-					} else {
-						{
-							let geometry_top = geometry.clone_cut_bot(yc);
-							recursion(env, &geometry_top.clone_cut_rig(xc), xi          , yi, half_size);
-							recursion(env, &geometry_top.clone_cut_lef(xc), xi+half_size, yi, half_size);
-						}
-						{
-							let geometry_bot = geometry.clone_cut_top(yc);
-							recursion(env, &geometry_bot.clone_cut_rig(xc), xi          , yi+half_size, half_size);
-							recursion(env, &geometry_bot.clone_cut_lef(xc), xi+half_size, yi+half_size, half_size);
-						}
+					{
+						let geometry_top = geometry.clone_cut_bot(yc);
+						recursion(env, &geometry_top.clone_cut_rig(xc), xi          , yi, half_size);
+						recursion(env, &geometry_top.clone_cut_lef(xc), xi+half_size, yi, half_size);
+					}
+					{
+						let geometry_bot = geometry.clone_cut_top(yc);
+						recursion(env, &geometry_bot.clone_cut_rig(xc), xi          , yi+half_size, half_size);
+						recursion(env, &geometry_bot.clone_cut_lef(xc), xi+half_size, yi+half_size, half_size);
 					}
 				}
 			}
 		}
 	}
+}
 
-	fn demercator_x(x: f32) -> f32 {
-		return x * 360.0 - 180.0;
+// position and statistics of every channel
+impl fmt::Display for GeoImage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let point_min = self.get_point_min();
+		let point_max = self.get_point_max();
+		writeln!(f, "tile:     {}/{}/{}", self.zoom, self.y_offset, self.x_offset)?;
+		writeln!(f, "size:     {}×{} pixels", self.size, self.size)?;
+		writeln!(f, "bbox:     [{}, {}, {}, {}]", point_min.x, point_min.y, point_max.x, point_max.y)?;
+		writeln!(f, "channels: {}", self.channels.len())?;
+		for (index, channel) in self.channels.iter().enumerate() {
+			let min = channel.data.iter().fold(f32::INFINITY, |a, b| a.min(*b));
+			let max = channel.data.iter().fold(f32::NEG_INFINITY, |a, b| a.max(*b));
+			let mean = channel.data.iter().map(|v| *v as f64).sum::<f64>() / channel.data.len().max(1) as f64;
+			let info = &channel.info;
+			write!(f, "   {}: \"{}\", range [{}, {}], min {}, max {}, mean {:.4}, fill value {}", index, info.name, info.value_range[0], info.value_range[1], min, max, mean, info.fill_value)?;
+			match &info.curve {
+				ValueCurve::Linear => writeln!(f)?,
+				curve => writeln!(f, ", curve {}", curve.to_json())?,
+			}
+		}
+		return Ok(());
 	}
+}
 
-	fn demercator_y(y: f32) -> f32 {
-		return (((1.0 - y * 2.0) * PI).exp().atan() * 4.0 / PI - 1.0) * 90.0;
-	}
+fn encode_webp(img: &image::RgbImage, near_lossless: u8) -> std::result::Result<Vec<u8>, String> {
+	let mut config = webp::WebPConfig::new().expect("libwebp version mismatch");
+	config.lossless = 1;
+	config.near_lossless = near_lossless as i32;
+	// slowest and smallest, like cwebp -m 6
+	config.method = 6;
+	let encoder = webp::Encoder::from_rgb(img.as_raw(), img.width(), img.height());
+	return match encoder.encode_advanced(&config) {
+		Ok(memory) => Ok(memory.to_vec()),
+		Err(error) => Err(format!("webp encoding failed: {:?}", error)),
+	};
+}
+
+fn demercator_x(x: f64) -> f64 {
+	return x * 360.0 - 180.0;
+}
+
+fn demercator_y(y: f64) -> f64 {
+	return (((1.0 - y * 2.0) * PI).exp().atan() * 4.0 / PI - 1.0) * 90.0;
 }
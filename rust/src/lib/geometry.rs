@@ -1,669 +1,995 @@
-
 // Warum auch immer Rust die Warnung zeigt, hiermit sind sie weg:
-#[allow(dead_code)]
-
-pub mod geometry {
-
-	use json;
-	use json::JsonValue;
-	use std::cmp::Ordering;
-	use std::collections::BinaryHeap;
-	use std::f32::consts::PI;
-	use std::fmt;
-	use std::fs;
-	use std::path::Path;
-	use std::rc::Rc;
-
-
+#![allow(dead_code)]
+
+use crate::error::{Error, Result};
+use crate::fgb::fill_from_fgb;
+use crate::gpkg::fill_from_gpkg;
+use geographiclib_rs::{Geodesic, InverseGeodesic};
+use json::JsonValue;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+
+
+const DEG2RAD: f64 = PI / 180.0;
+const DEG2METERS: f64 = 6378137.0 * DEG2RAD;
+
+// WGS84 ellipsoid, the difference to GRS80 (ETRS89) is irrelevant here
+const ELLIPSOID_A: f64 = 6378137.0;
+const ELLIPSOID_F: f64 = 1.0 / 298.257223563;
+
+// in meters. longer segments are split for the projected distance
+const MAX_PROJECTED_SEGMENT_LENGTH: f64 = 500.0;
+
+// how distances in meters are calculated from geographic coordinates:
+// - Approximate: equirectangular projection at the latitude of the pixel, on a sphere. Fast, but about 0.2% off
+// - Projected: transverse mercator on the ellipsoid, centered at the pixel. Error in the range of millimeters
+// - Geodesic: ellipsoidal geodesic (Karney) to the nearest point of the segment. Slowest, used as reference
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DistanceModel {
+	#[default]
+	Approximate,
+	Projected,
+	Geodesic,
+}
 
-	const DEG2RAD: f32 = PI / 180.0;
-	const DEG2METERS: f32 = 6378137.0 * DEG2RAD;
+impl DistanceModel {
+	pub fn from_name(name: &str) -> Option<DistanceModel> {
+		return match name {
+			"approximate" => Some(DistanceModel::Approximate),
+			"projected" => Some(DistanceModel::Projected),
+			"geodesic" => Some(DistanceModel::Geodesic),
+			_ => None,
+		};
+	}
+}
 
 
 
-	#[derive(Debug)]
-	pub struct Bbox {
-		x_min: f32,
-		y_min: f32,
-		x_max: f32,
-		y_max: f32,
-	}
+#[derive(Debug)]
+pub struct Bbox {
+	pub x_min: f64,
+	pub y_min: f64,
+	pub x_max: f64,
+	pub y_max: f64,
+}
 
-	impl Bbox {
-		fn new() -> Bbox {
-			Bbox {
-				x_min: 180.0,
-				y_min:  90.0,
-				x_max:-180.0,
-				y_max: -90.0,
-			}
-		}
-		pub fn from_coordinates(x_min:f32, y_min:f32, x_max:f32, y_max:f32) -> Bbox {
-			return Bbox {x_min, y_min, x_max, y_max}
-		}
-		fn from_segments(segments: &Vec<Rc<Segment>>) -> Bbox {
-			let mut bbox = Bbox::new();
-			for segment in segments {
-				bbox.add_point(&segment.p0);
-				bbox.add_point(&segment.p1);
-			}
-			return bbox;
-		}
-		fn add_point(&mut self, point: &Point) {
-			if self.x_min > point.x {
-				self.x_min = point.x
-			};
-			if self.y_min > point.y {
-				self.y_min = point.y
-			};
-			if self.x_max < point.x {
-				self.x_max = point.x
-			};
-			if self.y_max < point.y {
-				self.y_max = point.y
-			};
-		}
-		fn add_bbox(&mut self, bbox: &Bbox) {
-			if self.x_min > bbox.x_min {
-				self.x_min = bbox.x_min
-			};
-			if self.y_min > bbox.y_min {
-				self.y_min = bbox.y_min
-			};
-			if self.x_max < bbox.x_max {
-				self.x_max = bbox.x_max
-			};
-			if self.y_max < bbox.y_max {
-				self.y_max = bbox.y_max
-			};
+impl Bbox {
+	fn new() -> Bbox {
+		Bbox {
+			x_min: 180.0,
+			y_min:  90.0,
+			x_max:-180.0,
+			y_max: -90.0,
 		}
-		pub fn center(&self) -> Point {
-			return Point::new(
-				(self.x_min + self.x_max) / 2.0,
-				(self.y_min + self.y_max) / 2.0,
-			);
-		}
-		fn top_left(&self) -> Point {
-			return Point::new(
-				self.x_min,
-				self.y_max,
-			);
-		}
-		fn width(&self) -> f32 {
-			return self.x_max - self.x_min;
-		}
-		fn height(&self) -> f32 {
-			return self.y_max - self.y_min;
+	}
+	pub fn from_coordinates(x_min:f64, y_min:f64, x_max:f64, y_max:f64) -> Bbox {
+		return Bbox {x_min, y_min, x_max, y_max}
+	}
+	pub fn from_points(p0: &Point, p1: &Point) -> Bbox {
+		let mut bbox = Bbox::new();
+		bbox.add_point(p0);
+		bbox.add_point(p1);
+		return bbox;
+	}
+	fn from_segments(segments: &Vec<Arc<Segment>>) -> Bbox {
+		let mut bbox = Bbox::new();
+		for segment in segments {
+			bbox.add_point(&segment.p0);
+			bbox.add_point(&segment.p1);
 		}
-		fn distance_to(&self, point: &Point) -> f32 {
-			let dx = (self.x_min - point.x).max(point.x - self.x_max).max(0.0);
-			let dy = (self.y_min - point.y).max(point.y - self.y_max).max(0.0);
+		return bbox;
+	}
+	fn add_point(&mut self, point: &Point) {
+		if self.x_min > point.x {
+			self.x_min = point.x
+		};
+		if self.y_min > point.y {
+			self.y_min = point.y
+		};
+		if self.x_max < point.x {
+			self.x_max = point.x
+		};
+		if self.y_max < point.y {
+			self.y_max = point.y
+		};
+	}
+	fn add_bbox(&mut self, bbox: &Bbox) {
+		if self.x_min > bbox.x_min {
+			self.x_min = bbox.x_min
+		};
+		if self.y_min > bbox.y_min {
+			self.y_min = bbox.y_min
+		};
+		if self.x_max < bbox.x_max {
+			self.x_max = bbox.x_max
+		};
+		if self.y_max < bbox.y_max {
+			self.y_max = bbox.y_max
+		};
+	}
+	pub fn center(&self) -> Point {
+		return Point::new(
+			(self.x_min + self.x_max) / 2.0,
+			(self.y_min + self.y_max) / 2.0,
+		);
+	}
+	fn top_left(&self) -> Point {
+		return Point::new(
+			self.x_min,
+			self.y_max,
+		);
+	}
+	fn width(&self) -> f64 {
+		return self.x_max - self.x_min;
+	}
+	fn height(&self) -> f64 {
+		return self.y_max - self.y_min;
+	}
+	fn distance_to(&self, point: &Point) -> f64 {
+		let dx = (self.x_min - point.x).max(point.x - self.x_max).max(0.0);
+		let dy = (self.y_min - point.y).max(point.y - self.y_max).max(0.0);
 
-			return (dx * dx * point.scale_x2 + dy * dy).sqrt() * DEG2METERS;
+		return (dx * dx * point.scale_x2 + dy * dy).sqrt() * DEG2METERS;
+	}
+	// lower bound of the distance on the ellipsoid, for paths of up to max_distance.
+	// along a path ds² = M²·dφ² + p²·dλ², where the meridian radius M is smallest at the equator (a·(1 - e²))
+	// and the radius p of the parallel is smallest at the latitude closest to the pole, that the path can reach
+	fn ellipsoid_distance_bound(&self, point: &Point, max_distance: f64) -> f64 {
+		let dx = (self.x_min - point.x).max(point.x - self.x_max).max(0.0);
+		let dy = (self.y_min - point.y).max(point.y - self.y_max).max(0.0);
+
+		let e2 = ELLIPSOID_F * (2.0 - ELLIPSOID_F);
+		let m_min = ELLIPSOID_A * (1.0 - e2);
+		let max_lat = (point.y.abs() * DEG2RAD + max_distance / m_min).min(PI / 2.0);
+		let p_min = ELLIPSOID_A * max_lat.cos() / (1.0 - e2 * max_lat.sin().powi(2)).sqrt();
+
+		return ((dx * DEG2RAD * p_min).powi(2) + (dy * DEG2RAD * m_min).powi(2)).sqrt();
+	}
+	// grows the bbox by a distance in meters, large enough for all distance models:
+	// a degree of latitude is shortest at the equator of the ellipsoid, a degree of longitude at the buffered edge closest to the pole
+	pub fn buffered(&self, distance: f64) -> Bbox {
+		let e2 = ELLIPSOID_F * (2.0 - ELLIPSOID_F);
+		let dy = distance / (ELLIPSOID_A * (1.0 - e2) * DEG2RAD);
+		let max_lat = (self.y_min.abs().max(self.y_max.abs()) + dy).min(89.0);
+		let dx = dy / (max_lat * DEG2RAD).cos();
+		return Bbox {
+			x_min: self.x_min - dx,
+			y_min: self.y_min - dy,
+			x_max: self.x_max + dx,
+			y_max: self.y_max + dy,
+		};
+	}
+	fn contains_point(&self, point: &Point) -> bool {
+		if (point.x < self.x_min) || (point.y < self.y_min) {
+			return false;
 		}
-		fn contains_point(&self, point: &Point) -> bool {
-			if (point.x < self.x_min) || (point.y < self.y_min) {
-				return false;
-			}
-			if (point.x > self.x_max) || (point.y > self.y_max) {
-				return false;
-			}
-			return true;
+		if (point.x > self.x_max) || (point.y > self.y_max) {
+			return false;
 		}
-		fn overlaps_bbox(&self, bbox: &Bbox) -> bool {
-			if (bbox.x_min > self.x_max) || (bbox.x_max < self.x_min) {
-				return false;
-			}
-			if (bbox.y_min > self.y_max) || (bbox.y_max < self.y_min) {
-				return false;
-			}
-			return true;
+		return true;
+	}
+	pub fn overlaps_bbox(&self, bbox: &Bbox) -> bool {
+		if (bbox.x_min > self.x_max) || (bbox.x_max < self.x_min) {
+			return false;
 		}
-		fn covers_bbox(&self, bbox: &Bbox) -> bool {
-			if (bbox.x_min < self.x_min) || (bbox.x_max > self.x_max) {
-				return false;
-			}
-			if (bbox.y_min < self.y_min) || (bbox.y_max > self.y_max) {
-				return false;
-			}
-			return true;
+		if (bbox.y_min > self.y_max) || (bbox.y_max < self.y_min) {
+			return false;
 		}
+		return true;
 	}
-	impl fmt::Display for Bbox {
-		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-			write!(
-				f,
-				"{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Polygon\",\"coordinates\":[[[{},{}],[{},{}],[{},{}],[{},{}],[{},{}]]]}}}}",
-				self.x_min, self.y_min,
-				self.x_min, self.y_max,
-				self.x_max, self.y_max,
-				self.x_max, self.y_min,
-				self.x_min, self.y_min,
-			)
+	fn covers_bbox(&self, bbox: &Bbox) -> bool {
+		if (bbox.x_min < self.x_min) || (bbox.x_max > self.x_max) {
+			return false;
+		}
+		if (bbox.y_min < self.y_min) || (bbox.y_max > self.y_max) {
+			return false;
 		}
+		return true;
 	}
+}
+impl fmt::Display for Bbox {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Polygon\",\"coordinates\":[[[{},{}],[{},{}],[{},{}],[{},{}],[{},{}]]]}}}}",
+			self.x_min, self.y_min,
+			self.x_min, self.y_max,
+			self.x_max, self.y_max,
+			self.x_max, self.y_min,
+			self.x_min, self.y_min,
+		)
+	}
+}
 
 
 
-	#[derive(Copy, Clone, Debug)]
-	pub struct Point {
-		x: f32,
-		y: f32,
-		scale_x2: f32,
-	}
+#[derive(Copy, Clone, Debug)]
+pub struct Point {
+	pub x: f64,
+	pub y: f64,
+	scale_x2: f64,
+}
 
-	impl Point {
-		pub fn new(x: f32, y: f32) -> Point {
-			return Point {
-				x,
-				y,
-				scale_x2: (y * DEG2RAD).cos().powi(2),
-			};
-		}
-		pub fn clone(&self) -> Point {
-			return Point {
-				x: self.x,
-				y: self.y,
-				scale_x2: self.scale_x2,
-			};
-		}
-		fn import_from_json(coordinates_point: &JsonValue) -> Point {
-			return Point::new(
-				coordinates_point[0].as_f32().unwrap(),
-				coordinates_point[1].as_f32().unwrap(),
-			);
-		}
+impl Point {
+	pub fn new(x: f64, y: f64) -> Point {
+		return Point {
+			x,
+			y,
+			scale_x2: (y * DEG2RAD).cos().powi(2),
+		};
+	}
+	fn import_from_json(coordinates_point: &JsonValue) -> std::result::Result<Point, String> {
+		return match (coordinates_point[0].as_f64(), coordinates_point[1].as_f64()) {
+			(Some(x), Some(y)) => Ok(Point::new(x, y)),
+			_ => Err(format!("invalid coordinates: {}", coordinates_point)),
+		};
 	}
+}
 
 
-	#[derive(Debug)]
-	struct Polyline {
-		points: Vec<Point>,
-		bbox: Bbox,
-	}
+#[derive(Debug)]
+struct Polyline {
+	points: Vec<Point>,
+	bbox: Bbox,
+}
 
-	impl Polyline {
-		fn new() -> Polyline {
-			return Polyline {
-				points: Vec::new(),
-				bbox: Bbox::new(),
-			};
-		}
-		fn from_points(points: Vec<Point>) -> Polyline {
-			let mut polyline = Polyline {
-				points,
-				bbox: Bbox::new(),
-			};
-			polyline.update_bbox();
-			return polyline;
-		}
-		fn import_from_json(coordinates_line: &JsonValue) -> Polyline {
-			let mut polyline = Polyline::new();
-			for coordinates_point in coordinates_line.members() {
-				polyline
-					.points
-					.push(Point::import_from_json(coordinates_point))
-			}
-			polyline.update_bbox();
-			return polyline;
-		}
-		fn clone_cut<F>(&self, filter:F) -> Polyline where F: Fn(Point) -> bool {
-			let mut outside:Vec<bool> = Vec::new();
-			for i in 0..self.points.len()-1 {
-				outside.insert(i, filter(self.points[i]));
-			}
+// half-plane of a cut. the name is the side, that is removed:
+// Top(y) removes everything above y, Lef(x) everything left of x
+#[derive(Clone, Copy, Debug)]
+enum Cut {
+	Top(f64),
+	Bot(f64),
+	Lef(f64),
+	Rig(f64),
+}
 
-			let n = outside.len();
-			let mut points:Vec<Point> = Vec::new();
-			for i in 0..n {
-				let drop:bool = outside[i] && outside[(i+1) % n] && outside[(i+n-1) % n];
-				if !drop {
-					points.push(self.points[i].clone());
-				}
-			}
+impl Cut {
+	fn is_outside(&self, point:&Point) -> bool {
+		return match *self {
+			Cut::Top(y) => point.y > y,
+			Cut::Bot(y) => point.y < y,
+			Cut::Lef(x) => point.x < x,
+			Cut::Rig(x) => point.x > x,
+		};
+	}
+	// point of the edge p0-p1 on the cut line, p0 and p1 have to be on different sides
+	fn intersection(&self, p0:&Point, p1:&Point) -> Point {
+		return match *self {
+			Cut::Top(y) | Cut::Bot(y) => Point::new(p0.x + (y - p0.y) * (p1.x - p0.x) / (p1.y - p0.y), y),
+			Cut::Lef(x) | Cut::Rig(x) => Point::new(x, p0.y + (x - p0.x) * (p1.y - p0.y) / (p1.x - p0.x)),
+		};
+	}
+}
 
-			if points.len() > 0 {
-				points.push(points[0]);
+impl Polyline {
+	fn new() -> Polyline {
+		return Polyline {
+			points: Vec::new(),
+			bbox: Bbox::new(),
+		};
+	}
+	fn from_points(points: Vec<Point>) -> Polyline {
+		let mut polyline = Polyline {
+			points,
+			bbox: Bbox::new(),
+		};
+		polyline.update_bbox();
+		return polyline;
+	}
+	fn import_from_json(coordinates_line: &JsonValue) -> std::result::Result<Polyline, String> {
+		let mut polyline = Polyline::new();
+		for coordinates_point in coordinates_line.members() {
+			polyline
+				.points
+				.push(Point::import_from_json(coordinates_point)?)
+		}
+		polyline.update_bbox();
+		return Ok(polyline);
+	}
+	// Sutherland–Hodgman: the closed ring clipped to the inner side of the cut
+	fn clone_cut(&self, cut:&Cut) -> Polyline {
+		let n = self.points.len();
+		if n < 4 {
+			return Polyline::new();
+		}
+
+		let mut points:Vec<Point> = Vec::new();
+		// the first point is repeated at the end, so every edge is points[i-1] → points[i]
+		for i in 1..n {
+			let p0 = self.points[i-1];
+			let p1 = self.points[i];
+			match (cut.is_outside(&p0), cut.is_outside(&p1)) {
+				(false, false) => points.push(p1),
+				(false, true) => points.push(cut.intersection(&p0, &p1)),
+				(true, false) => {
+					points.push(cut.intersection(&p0, &p1));
+					points.push(p1);
+				},
+				(true, true) => {},
 			}
+		}
 
-			return Polyline::from_points(points);
+		if points.len() < 3 {
+			return Polyline::new();
 		}
-		fn update_bbox(&mut self) {
-			let bbox = &mut self.bbox;
-			for point in &self.points {
-				bbox.add_point(&point);
-			}
+		points.push(points[0]);
+
+		return Polyline::from_points(points);
+	}
+	fn update_bbox(&mut self) {
+		let bbox = &mut self.bbox;
+		for point in &self.points {
+			bbox.add_point(point);
 		}
-		fn extract_segments_to(&self, segments: &mut Segments) {
-			for i in 0..(self.points.len() - 2) {
-				let p0 = self.points[i];
-				let p1 = self.points[i + 1];
-				segments.add(p0, p1);
-			}
+	}
+	fn extract_segments_to(&self, segments: &mut Segments) {
+		for i in 1..self.points.len() {
+			let p0 = self.points[i - 1];
+			let p1 = self.points[i];
+			segments.add(p0, p1);
 		}
-		fn contains_point(&self, point:&Point) -> bool {
-			if !self.bbox.contains_point(point) {
-				return false;
-			}
-
-			// A point is in a polygon if a line from the point to infinity crosses the polygon an odd number of times
-			let mut odd:bool = false;
-			
-			//For each edge (In this case for each point of the polygon and the previous one)
-			for i in 0..self.points.len()-1 {
-				//If a line from the point into infinity crosses this edge
-				let p0 = self.points[i  ];
-				let p1 = self.points[i+1];
-
-				// One point needs to be above, one below our y coordinate
-				if (p1.y > point.y) != (p0.y > point.y) {
-					// ...and the edge doesn't cross our Y corrdinate before our x coordinate (but between our x coordinate and infinity)
-					if point.x < (p0.x - p1.x) * (point.y - p1.y) / (p0.y - p1.y) + p1.x {
-						odd = !odd; // negate odd
-					}
+	}
+	fn contains_point(&self, point:&Point) -> bool {
+		if !self.bbox.contains_point(point) {
+			return false;
+		}
+
+		// A point is in a polygon if a line from the point to infinity crosses the polygon an odd number of times
+		let mut odd:bool = false;
+		
+		//For each edge (In this case for each point of the polygon and the previous one)
+		for i in 0..self.points.len()-1 {
+			//If a line from the point into infinity crosses this edge
+			let p0 = self.points[i  ];
+			let p1 = self.points[i+1];
+
+			// One point needs to be above, one below our y coordinate
+			if (p1.y > point.y) != (p0.y > point.y) {
+				// ...and the edge doesn't cross our Y corrdinate before our x coordinate (but between our x coordinate and infinity)
+				if point.x < (p0.x - p1.x) * (point.y - p1.y) / (p0.y - p1.y) + p1.x {
+					odd = !odd; // negate odd
 				}
 			}
-			
-			//If the number of crossings was odd, the point is in the polygon
-			return odd;
-		}
-		pub fn point_count(&self) -> u32 {
-			return self.points.len() as u32;
 		}
+		
+		//If the number of crossings was odd, the point is in the polygon
+		return odd;
+	}
+	pub fn point_count(&self) -> u32 {
+		return self.points.len() as u32;
 	}
+}
 
 
-	#[derive(Debug)]
-	struct Polygon {
-		rings: Vec<Polyline>,
-		bbox: Bbox,
-	}
+#[derive(Debug)]
+struct Polygon {
+	rings: Vec<Polyline>,
+	bbox: Bbox,
+}
 
-	impl Polygon {
-		fn new() -> Polygon {
-			return Polygon {
-				rings: Vec::new(),
-				bbox: Bbox::new(),
-			};
+impl Polygon {
+	fn new() -> Polygon {
+		return Polygon {
+			rings: Vec::new(),
+			bbox: Bbox::new(),
+		};
+	}
+	fn from_rings(rings: Vec<Polyline>) -> Polygon {
+		let mut polygon = Polygon {
+			rings,
+			bbox: Bbox::new(),
+		};
+		polygon.update_bbox();
+		return polygon;
+	}
+	fn import_from_json(coordinates_polygon: &JsonValue) -> std::result::Result<Polygon, String> {
+		let mut polygon = Polygon::new();
+		for coordinates_ring in coordinates_polygon.members() {
+			polygon
+				.rings
+				.push(Polyline::import_from_json(coordinates_ring)?)
+		}
+		polygon.update_bbox();
+		return Ok(polygon);
+	}
+	fn clone_cut(&self, cut:&Cut) -> Polygon {
+		let mut rings:Vec<Polyline> = Vec::new();
+		for (index, ring) in self.rings.iter().enumerate() {
+			let ring_clone = ring.clone_cut(cut);
+			if !ring_clone.points.is_empty() {
+				rings.push(ring_clone);
+			} else if index == 0 {
+				// the outer ring is completely outside, and so are the holes
+				break;
+			}
+		}
+		return Polygon::from_rings(rings);
+	}
+	fn update_bbox(&mut self) {
+		let bbox = &mut self.bbox;
+		for ring in &self.rings {
+			bbox.add_bbox(&ring.bbox);
 		}
-		fn from_rings(rings: Vec<Polyline>) -> Polygon {
-			let mut polygon = Polygon {
-				rings,
-				bbox: Bbox::new(),
-			};
-			polygon.update_bbox();
-			return polygon;
-		}
-		fn import_from_json(coordinates_polygon: &JsonValue) -> Polygon {
-			let mut polygon = Polygon::new();
-			for coordinates_ring in coordinates_polygon.members() {
-				polygon
-					.rings
-					.push(Polyline::import_from_json(coordinates_ring))
-			}
-			polygon.update_bbox();
-			return polygon;
-		}
-		fn clone_cut<F>(&self, filter:&F) -> Polygon where F: Fn(Point) -> bool {
-			let mut rings:Vec<Polyline> = Vec::new();
-			for ring in &self.rings {
-				let ring_clone = ring.clone_cut(filter);
-				if ring_clone.points.len() > 0 {
-					rings.push(ring_clone);
-				}
-			}
-			return Polygon::from_rings(rings);
+	}
+	fn extract_segments_to(&self, segments: &mut Segments) {
+		for ring in &self.rings {
+			ring.extract_segments_to(segments);
 		}
-		fn update_bbox(&mut self) {
-			let bbox = &mut self.bbox;
-			for ring in &self.rings {
-				bbox.add_bbox(&ring.bbox);
-			}
+	}
+	fn contains_point(&self, point:&Point) -> bool {
+		if !self.bbox.contains_point(point) {
+			return false;
 		}
-		fn extract_segments_to(&self, segments: &mut Segments) {
-			for ring in &self.rings {
-				ring.extract_segments_to(segments);
-			}
+
+		if !self.rings[0].contains_point(point) {
+			return false;
 		}
-		fn contains_point(&self, point:&Point) -> bool {
-			if !self.bbox.contains_point(point) {
-				return false;
-			}
 
-			if !self.rings[0].contains_point(point) {
+		for i in 1..self.rings.len() {
+			let ring = &self.rings[i];
+			if ring.contains_point(point) {
 				return false;
 			}
-
-			for i in 1..self.rings.len() {
-				let ring = &self.rings[i];
-				if ring.contains_point(point) {
-					return false;
-				}
-			}
-
-			return true;
 		}
-		pub fn point_count(&self) -> u32 {
-			let mut sum:u32 = 0;
-			for ring in &self.rings {
-				sum += ring.point_count();
-			}
-			return sum;
+
+		return true;
+	}
+	pub fn point_count(&self) -> u32 {
+		let mut sum:u32 = 0;
+		for ring in &self.rings {
+			sum += ring.point_count();
 		}
+		return sum;
 	}
+}
 
-	pub struct Geometry {
-		polygons: Vec<Polygon>,
+// lines and points have no inside, so they are only used as sources for unsigned distances
+pub struct Geometry {
+	polygons: Vec<Polygon>,
+	lines: Vec<Polyline>,
+	points: Vec<Point>,
+}
+impl Default for Geometry {
+	fn default() -> Self {
+		return Self::new();
 	}
-	impl Geometry {
-		pub fn new() -> Geometry {
-			return Geometry {
-				polygons: Vec::new()
-			};
+}
+impl Geometry {
+	pub fn new() -> Geometry {
+		return Geometry {
+			polygons: Vec::new(),
+			lines: Vec::new(),
+			points: Vec::new(),
+		};
+	}
+	pub fn fill_from_json(&mut self, filename: &Path) -> Result<()> {
+		let contents: &str = &fs::read_to_string(filename).map_err(|e| Error::io(filename, e))?;
+		let data = json::parse(contents).map_err(|e| Error::format(filename, e))?;
+		let features = &data["features"];
+		for feature in features.members() {
+			self.add_json_geometry(&feature["geometry"]).map_err(|e| Error::format(filename, e))?;
+		}
+		return Ok(());
+	}
+	pub fn add_polygon(&mut self, rings: Vec<Vec<Point>>) {
+		let rings = rings.into_iter().map(Polyline::from_points).collect();
+		self.polygons.push(Polygon::from_rings(rings));
+	}
+	pub fn add_line(&mut self, points: Vec<Point>) {
+		if !points.is_empty() {
+			self.lines.push(Polyline::from_points(points));
 		}
-		pub fn fill_from_json(&mut self, filename: &Path) {
-			let contents: &str = &fs::read_to_string(filename).unwrap();
-			let data = json::parse(contents).unwrap();
-			let features = &data["features"];
-			for feature in features.members() {
-				self.add_json_geometry(&feature["geometry"]);
+	}
+	pub fn add_point(&mut self, point: Point) {
+		self.points.push(point);
+	}
+	// the segments of all polygons and lines, points are segments of length 0 (like in Collection)
+	pub(crate) fn for_each_segment<F>(&self, mut callback: F) where F: FnMut(&Point, &Point) {
+		for line in self.polygons.iter().flat_map(|polygon| &polygon.rings).chain(&self.lines) {
+			for pair in line.points.windows(2) {
+				callback(&pair[0], &pair[1]);
 			}
 		}
-		fn add_json_geometry(&mut self, geometry:&JsonValue) {
-			if !geometry["type"].is_string() {
-				println!("{}", geometry);
-			}
-
-			let geometry_type = geometry["type"].as_str().unwrap();
-
-			match geometry_type {
-				"Polygon" => {
+		for point in &self.points {
+			callback(point, point);
+		}
+	}
+	// drops all geometries outside of the bbox
+	pub fn retain_bbox(&mut self, bbox: &Bbox) {
+		self.polygons.retain(|polygon| polygon.bbox.overlaps_bbox(bbox));
+		self.lines.retain(|line| line.bbox.overlaps_bbox(bbox));
+		self.points.retain(|point| bbox.contains_point(point));
+	}
+	// the error is a message without the filename, see fill_from_json
+	pub fn add_json_geometry(&mut self, geometry:&JsonValue) -> std::result::Result<(), String> {
+		let geometry_type = match geometry["type"].as_str() {
+			Some(geometry_type) => geometry_type,
+			None => return Err(format!("geometry without type: {}", geometry)),
+		};
+
+		match geometry_type {
+			"Polygon" => {
+				self.polygons.push(
+					Polygon::import_from_json(&geometry["coordinates"])?
+				)
+			},
+			"MultiPolygon" => {
+				for polygon in geometry["coordinates"].members() {
 					self.polygons.push(
-						Polygon::import_from_json(&geometry["coordinates"])
+						Polygon::import_from_json(polygon)?
 					)
-				},
-				"MultiPolygon" => {
-					for polygon in geometry["coordinates"].members() {
-						self.polygons.push(
-							Polygon::import_from_json(polygon)
-						)
-					}
-				},
-				"GeometryCollection" => {
-					for sub_geometry in geometry["geometries"].members() {
-						self.add_json_geometry(sub_geometry);
-					}
-				},
-				"LineString" => { return },
-				_ => {
-					println!("{}", geometry);
-					panic!("unknown geometry_type: '{}'", geometry_type)
 				}
-			}
-		}
-		pub fn clone_cut<F>(&self, filter:&F) -> Geometry where F: Fn(Point) -> bool {
-			let mut polygons:Vec<Polygon> = Vec::new();
-			for polygon in &self.polygons {
-				let clone = polygon.clone_cut(filter);
-				if clone.rings.len() > 0 {
-					polygons.push(clone);
+			},
+			"GeometryCollection" => {
+				for sub_geometry in geometry["geometries"].members() {
+					self.add_json_geometry(sub_geometry)?;
 				}
-			}
-			return Geometry { polygons }
+			},
+			"LineString" => {
+				self.lines.push(
+					Polyline::import_from_json(&geometry["coordinates"])?
+				)
+			},
+			"MultiLineString" => {
+				for line in geometry["coordinates"].members() {
+					self.lines.push(
+						Polyline::import_from_json(line)?
+					)
+				}
+			},
+			"Point" => {
+				self.points.push(
+					Point::import_from_json(&geometry["coordinates"])?
+				)
+			},
+			"MultiPoint" => {
+				for point in geometry["coordinates"].members() {
+					self.points.push(
+						Point::import_from_json(point)?
+					)
+				}
+			},
+			_ => return Err(format!("unknown geometry_type: '{}'", geometry_type)),
 		}
-		pub fn clone_cut_top(&self, y:f32) -> Geometry {
-			return self.clone_cut(&|p:Point| -> bool { p.y > y });
+		return Ok(());
+	}
+	// the cut geometry is only used for contains_point, so lines and points are dropped
+	fn clone_cut(&self, cut:&Cut) -> Geometry {
+		let mut polygons:Vec<Polygon> = Vec::new();
+		for polygon in &self.polygons {
+			let clone = polygon.clone_cut(cut);
+			if !clone.rings.is_empty() {
+				polygons.push(clone);
+			}
 		}
-		pub fn clone_cut_bot(&self, y:f32) -> Geometry {
-			return self.clone_cut(&|p:Point| -> bool { p.y < y });
+		return Geometry { polygons, lines: Vec::new(), points: Vec::new() }
+	}
+	pub fn clone_cut_top(&self, y:f64) -> Geometry {
+		return self.clone_cut(&Cut::Top(y));
+	}
+	pub fn clone_cut_bot(&self, y:f64) -> Geometry {
+		return self.clone_cut(&Cut::Bot(y));
+	}
+	pub fn clone_cut_lef(&self, x:f64) -> Geometry {
+		return self.clone_cut(&Cut::Lef(x));
+	}
+	pub fn clone_cut_rig(&self, x:f64) -> Geometry {
+		return self.clone_cut(&Cut::Rig(x));
+	}
+	pub fn contains_point(&self, point: &Point) -> bool {
+		return self.polygons.iter().any(|polygon| polygon.contains_point(point));
+	}
+	pub fn bbox(&self) -> Bbox {
+		let mut bbox = Bbox::new();
+		for polygon in &self.polygons {
+			bbox.add_bbox(&polygon.bbox);
 		}
-		pub fn clone_cut_lef(&self, x:f32) -> Geometry {
-			return self.clone_cut(&|p:Point| -> bool { p.x < x });
+		for line in &self.lines {
+			bbox.add_bbox(&line.bbox);
 		}
-		pub fn clone_cut_rig(&self, x:f32) -> Geometry {
-			return self.clone_cut(&|p:Point| -> bool { p.x > x });
+		for point in &self.points {
+			bbox.add_point(point);
 		}
-		pub fn contains_point(&self, point: &Point) -> bool {
-			return self.polygons.iter().any(|polygon| polygon.contains_point(point));
+		return bbox;
+	}
+	pub fn point_count(&self) -> u32 {
+		let mut sum:u32 = 0;
+		for polygon in &self.polygons {
+			sum += polygon.point_count();
 		}
-		pub fn point_count(&self) -> u32 {
-			let mut sum:u32 = 0;
-			for polygon in &self.polygons {
-				sum += polygon.point_count();
-			}
-			return sum;
+		for line in &self.lines {
+			sum += line.point_count();
 		}
+		sum += self.points.len() as u32;
+		return sum;
 	}
+}
 
-	pub struct Collection {
-		pub geometry: Geometry,
-		segments: Segments
+pub struct Collection {
+	pub geometry: Geometry,
+	segments: Segments
+}
+
+impl Default for Collection {
+	fn default() -> Self {
+		return Self::new();
 	}
+}
 
-	impl Collection {
-		pub fn new() -> Collection {
-			return Collection {
-				geometry: Geometry::new(),
-				segments: Segments::new(),
-			};
+impl Collection {
+	pub fn new() -> Collection {
+		return Collection {
+			geometry: Geometry::new(),
+			segments: Segments::new(),
+		};
+	}
+	pub fn from_geometry(geometry: Geometry) -> Collection {
+		let mut collection = Collection::new();
+		collection.geometry = geometry;
+		collection.init_segments();
+		return collection;
+	}
+	pub fn set_distance_model(&mut self, model: DistanceModel) {
+		self.segments.model = model;
+	}
+	pub fn fill_from_json(&mut self, filename: &Path) -> Result<()> {
+		self.geometry.fill_from_json(filename)?;
+		self.init_segments();
+		return Ok(());
+	}
+	// reads GeoJSON, GeoPackage or FlatGeobuf, depending on the file extension
+	// if a bbox is given, only geometries overlapping it are used
+	pub fn fill_from_file(&mut self, filename: &Path, bbox: Option<&Bbox>) -> Result<()> {
+		let extension = filename.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+		match extension {
+			"geojson" | "json" => self.geometry.fill_from_json(filename)?,
+			"gpkg" => fill_from_gpkg(&mut self.geometry, filename, bbox)?,
+			"fgb" => fill_from_fgb(&mut self.geometry, filename, bbox)?,
+			_ => return Err(Error::format(filename, format!("unknown extension: \"{}\"", extension))),
 		}
-		pub fn fill_from_json(&mut self, filename: &Path) {
-			self.geometry.fill_from_json(filename);
-			for polygon in &self.geometry.polygons {
-				polygon.extract_segments_to(&mut self.segments);
-			}
-			self.segments.init_tree();
+
+		if let Some(bbox) = bbox {
+			self.geometry.retain_bbox(bbox);
 		}
-		pub fn get_min_distance(&self, point: &Point, max_distance:f32) -> f32 {
-			return self.segments.get_min_distance(point, max_distance);
+
+		self.init_segments();
+		return Ok(());
+	}
+	fn init_segments(&mut self) {
+		for polygon in &self.geometry.polygons {
+			polygon.extract_segments_to(&mut self.segments);
 		}
+		for line in &self.geometry.lines {
+			line.extract_segments_to(&mut self.segments);
+		}
+		// a point is a segment of length 0
+		for point in &self.geometry.points {
+			self.segments.add(*point, *point);
+		}
+		self.segments.init_tree();
 	}
+	pub fn get_min_distance(&self, point: &Point, max_distance:f64) -> f64 {
+		return self.segments.get_min_distance(point, max_distance);
+	}
+}
 
 
 
-	struct Segment {
-		p0: Point,
-		p1: Point,
-		center: Point,
-	}
+struct Segment {
+	p0: Point,
+	p1: Point,
+	center: Point,
+}
 
-	impl Segment {
-		fn new(p0: Point, p1: Point) -> Segment {
-			return Segment {
-				p0,
-				p1,
-				center: Point::new((p0.x + p1.x) / 2.0, (p0.y + p1.y) / 2.0),
-			};
-		}
+impl Segment {
+	fn new(p0: Point, p1: Point) -> Segment {
+		return Segment {
+			p0,
+			p1,
+			center: Point::new((p0.x + p1.x) / 2.0, (p0.y + p1.y) / 2.0),
+		};
 	}
+}
 
 
 
-	struct Segments {
-		segments: Vec<Rc<Segment>>,
-		root: Option<Rc<SegmentTreeNode>>,
-	}
+struct Segments {
+	segments: Vec<Arc<Segment>>,
+	root: Option<Arc<SegmentTreeNode>>,
+	model: DistanceModel,
+}
 
-	impl Segments {
-		fn add(&mut self, p0: Point, p1: Point) {
-			self.segments.push(Rc::new(Segment::new(p0, p1)));
-		}
-		pub fn new() -> Segments {
-			return Segments {
-				segments: Vec::new(),
-				root: None,
-			};
-		}
-		fn init_tree(&mut self) {
-			let node = self.create_node(&self.segments);
-			self.root = Some(Rc::new(node));
-		}
-		fn create_node(&self, segments: &Vec<Rc<Segment>>) -> SegmentTreeNode {
-			let bbox = Bbox::from_segments(segments);
-			let center = bbox.center();
+impl Segments {
+	fn add(&mut self, p0: Point, p1: Point) {
+		self.segments.push(Arc::new(Segment::new(p0, p1)));
+	}
+	pub fn new() -> Segments {
+		return Segments {
+			segments: Vec::new(),
+			root: None,
+			model: DistanceModel::Approximate,
+		};
+	}
+	fn init_tree(&mut self) {
+		let node = self.create_node(&self.segments);
+		self.root = Some(Arc::new(node));
+	}
+	fn create_node(&self, segments: &Vec<Arc<Segment>>) -> SegmentTreeNode {
+		let bbox = Bbox::from_segments(segments);
+		let center = bbox.center();
 
-			let mut segments1: Vec<Rc<Segment>> = Vec::new();
-			let mut segments2: Vec<Rc<Segment>> = Vec::new();
+		let mut segments1: Vec<Arc<Segment>> = Vec::new();
+		let mut segments2: Vec<Arc<Segment>> = Vec::new();
 
-			if bbox.width() > bbox.height() {
-				for segment in segments.iter() {
-					if segment.center.x < center.x {
-						segments1.push(segment.clone());
-					} else {
-						segments2.push(segment.clone());
-					}
-				}
-			} else {
-				for segment in segments.iter() {
-					if segment.center.y < center.y {
-						segments1.push(segment.clone());
-					} else {
-						segments2.push(segment.clone());
-					}
+		if bbox.width() > bbox.height() {
+			for segment in segments.iter() {
+				if segment.center.x < center.x {
+					segments1.push(segment.clone());
+				} else {
+					segments2.push(segment.clone());
 				}
 			}
-
-			if segments1.len() == 0 {
-				return SegmentTreeNode {
-					bbox,
-					is_leaf: true,
-					left: None,
-					right: None,
-					segments: Some(segments2),
-				};
+		} else {
+			for segment in segments.iter() {
+				if segment.center.y < center.y {
+					segments1.push(segment.clone());
+				} else {
+					segments2.push(segment.clone());
+				}
 			}
+		}
 
-			if segments2.len() == 0 {
-				return SegmentTreeNode {
-					bbox,
-					is_leaf: true,
-					left: None,
-					right: None,
-					segments: Some(segments1),
-				};
-			}
+		if segments1.is_empty() {
+			return SegmentTreeNode {
+				bbox,
+				is_leaf: true,
+				left: None,
+				right: None,
+				segments: Some(segments2),
+			};
+		}
 
+		if segments2.is_empty() {
 			return SegmentTreeNode {
 				bbox,
-				is_leaf: false,
-				left: Some(Rc::new(self.create_node(&segments1))),
-				right: Some(Rc::new(self.create_node(&segments2))),
-				segments: None,
+				is_leaf: true,
+				left: None,
+				right: None,
+				segments: Some(segments1),
 			};
 		}
-		pub fn get_min_distance(&self, point: &Point, max_distance:f32) -> f32 {
-			let mut heap = BinaryHeap::new();
-			let root = (self.root).as_ref().unwrap().clone();
-			heap.push(HeapNode::new(&root, point));
 
-			let mut min_distance: f32 = max_distance;
+		return SegmentTreeNode {
+			bbox,
+			is_leaf: false,
+			left: Some(Arc::new(self.create_node(&segments1))),
+			right: Some(Arc::new(self.create_node(&segments2))),
+			segments: None,
+		};
+	}
+	pub fn get_min_distance(&self, point: &Point, max_distance:f64) -> f64 {
+		let mut heap = BinaryHeap::new();
+		let root = (self.root).as_ref().unwrap().clone();
+		heap.push(HeapNode::new(&root, point, self.model, max_distance));
 
-			while !heap.is_empty() {
-				let heap_node = heap.pop().unwrap();
-				let tree_node = heap_node.tree_node;
+		let mut min_distance: f64 = max_distance;
 
-				let distance = heap_node.min_distance;
+		while !heap.is_empty() {
+			let heap_node = heap.pop().unwrap();
+			let tree_node = heap_node.tree_node;
 
-				if distance > min_distance {
-					break;
-				}
+			let distance = heap_node.min_distance;
 
-				if tree_node.is_leaf {
-					min_distance = distance;
-				} else {
-					heap.push(HeapNode::new(tree_node.left.as_ref().unwrap(), point));
-					heap.push(HeapNode::new(tree_node.right.as_ref().unwrap(), point));
-				}
+			if distance > min_distance {
+				break;
 			}
 
-			return min_distance;
+			if tree_node.is_leaf {
+				min_distance = distance;
+			} else {
+				heap.push(HeapNode::new(tree_node.left.as_ref().unwrap(), point, self.model, max_distance));
+				heap.push(HeapNode::new(tree_node.right.as_ref().unwrap(), point, self.model, max_distance));
+			}
 		}
+
+		return min_distance;
 	}
+}
 
 
 
-	struct SegmentTreeNode {
-		bbox: Bbox,
-		is_leaf: bool,
-		left: Option<Rc<SegmentTreeNode>>,
-		right: Option<Rc<SegmentTreeNode>>,
-		segments: Option<Vec<Rc<Segment>>>,
-	}
+struct SegmentTreeNode {
+	bbox: Bbox,
+	is_leaf: bool,
+	left: Option<Arc<SegmentTreeNode>>,
+	right: Option<Arc<SegmentTreeNode>>,
+	segments: Option<Vec<Arc<Segment>>>,
+}
 
 
 
-	struct HeapNode<'a> {
-		tree_node: &'a SegmentTreeNode,
-		min_distance: f32,
+struct HeapNode<'a> {
+	tree_node: &'a SegmentTreeNode,
+	min_distance: f64,
+}
+
+impl HeapNode<'_> {
+	fn new<'a>(tree_node: &'a SegmentTreeNode, point: &'a Point, model: DistanceModel, max_distance: f64) -> HeapNode<'a> {
+		let min_distance = if tree_node.is_leaf {
+			min_segments_distance(tree_node.segments.as_ref().unwrap(), point, model)
+		} else if model == DistanceModel::Approximate {
+			tree_node.bbox.distance_to(point)
+		} else {
+			// the geodesic is the shortest path, the projected distance is at least as long (scale >= 1).
+			// nodes further away than max_distance can not give the minimum, so the bound only has to hold up to there
+			tree_node.bbox.ellipsoid_distance_bound(point, max_distance)
+		};
+		return HeapNode {
+			tree_node,
+			min_distance,
+		};
+	}
+}
+
+impl PartialEq for HeapNode<'_> {
+	fn eq(&self, other: &Self) -> bool {
+		return self.min_distance == other.min_distance;
 	}
+}
 
-	impl HeapNode<'_> {
-		fn new<'a>(tree_node: &'a SegmentTreeNode, point: &'a Point) -> HeapNode<'a> {
-			let min_distance;
-			if tree_node.is_leaf {
-				min_distance = min_segments_distance(tree_node.segments.as_ref().unwrap(), &point);
-			} else {
-				min_distance = tree_node.bbox.distance_to(&point);
-			}
-			return HeapNode {
-				tree_node,
-				min_distance,
-			};
-		}
+impl Eq for HeapNode<'_> {}
+
+impl Ord for HeapNode<'_> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.min_distance.total_cmp(&self.min_distance)
 	}
+}
 
-	impl PartialEq for HeapNode<'_> {
-		fn eq(&self, other: &Self) -> bool {
-			return self.min_distance == other.min_distance;
-		}
+impl PartialOrd for HeapNode<'_> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
 	}
+}
 
-	impl Eq for HeapNode<'_> {}
 
-	impl Ord for HeapNode<'_> {
-		fn cmp(&self, other: &Self) -> Ordering {
-			other.min_distance.total_cmp(&self.min_distance)
+
+fn min_segments_distance(segments: &Vec<Arc<Segment>>, point: &Point, model: DistanceModel) -> f64 {
+	let mut min_distance = f64::MAX;
+	for segment in segments {
+		let distance = match model {
+			DistanceModel::Approximate => min_segment_distance(segment, point),
+			DistanceModel::Projected => projected_segment_distance(segment, point).0,
+			DistanceModel::Geodesic => geodesic_segment_distance(segment, point),
+		};
+		if distance < min_distance {
+			min_distance = distance;
 		}
 	}
+	return min_distance;
+}
 
-	impl PartialOrd for HeapNode<'_> {
-		fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-			Some(self.cmp(other))
-		}
+fn min_segment_distance(segment: &Segment, point: &Point) -> f64 {
+	let pv = segment.p0; // v
+	let pw = segment.p1; // w
+
+	let dxwv = pw.x - pv.x;
+	let dywv = pw.y - pv.y;
+	let dxpv = point.x - pv.x;
+	let dypv = point.y - pv.y;
+
+	// project the point onto the segment, in the same scaled coordinates as the distance
+	let l2 = dxwv * dxwv * point.scale_x2 + dywv * dywv;
+	if l2 == 0.0 {
+		return (dxpv * dxpv * point.scale_x2 + dypv * dypv).sqrt() * DEG2METERS;
 	}
 
+	let t = ((dxpv * dxwv * point.scale_x2 + dypv * dywv) / l2).clamp(0.0, 1.0);
 
+	let dx = pv.x + t * dxwv - point.x;
+	let dy = pv.y + t * dywv - point.y;
 
-	fn min_segments_distance(segments: &Vec<Rc<Segment>>, point: &Point) -> f32 {
-		let mut min_distance = f32::MAX;
-		for segment in segments {
-			let distance = min_segment_distance(&segment, &point);
-			if distance < min_distance {
-				min_distance = distance;
-			}
-		}
-		return min_distance;
+	return (dx * dx * point.scale_x2 + dy * dy).sqrt() * DEG2METERS;
+}
+
+// distance and position t (0..1) of the nearest point on the segment,
+// using a transverse mercator projection, that is centered at the point.
+// segments are straight in lon/lat, but not in the projection, so long segments are split into pieces
+fn projected_segment_distance(segment: &Segment, point: &Point) -> (f64, f64) {
+	let lon0 = point.x;
+	let p = transverse_mercator(lon0, point.x, point.y);
+	let v = transverse_mercator(lon0, segment.p0.x, segment.p0.y);
+	let w = transverse_mercator(lon0, segment.p1.x, segment.p1.y);
+
+	let length = ((w.0 - v.0).powi(2) + (w.1 - v.1).powi(2)).sqrt();
+	if length <= MAX_PROJECTED_SEGMENT_LENGTH {
+		return projected_piece_distance(p, v, w);
 	}
 
-	fn min_segment_distance(segment: &Segment, point: &Point) -> f32 {
-		let pv = segment.p0; // v
-		let pw = segment.p1; // w
+	let n = (length / MAX_PROJECTED_SEGMENT_LENGTH).ceil() as usize;
+	let mut result = (f64::MAX, 0.0);
+	let mut v = v;
+	for i in 1..=n {
+		let t = i as f64 / n as f64;
+		let w = transverse_mercator(
+			lon0,
+			segment.p0.x + t * (segment.p1.x - segment.p0.x),
+			segment.p0.y + t * (segment.p1.y - segment.p0.y),
+		);
+		let (distance, t_piece) = projected_piece_distance(p, v, w);
+		if distance < result.0 {
+			result = (distance, (i as f64 - 1.0 + t_piece) / n as f64);
+		}
+		v = w;
+	}
+	return result;
+}
 
-		let dxwv = pw.x - pv.x;
-		let dywv = pw.y - pv.y;
-		let dxpv = point.x - pv.x;
-		let dypv = point.y - pv.y;
+// planar distance from p to the piece v-w and the position t (0..1) of the nearest point
+fn projected_piece_distance(p: (f64, f64), v: (f64, f64), w: (f64, f64)) -> (f64, f64) {
+	let dxwv = w.0 - v.0;
+	let dywv = w.1 - v.1;
+	let l2 = dxwv * dxwv + dywv * dywv;
+	let t = if l2 == 0.0 {
+		0.0
+	} else {
+		(((p.0 - v.0) * dxwv + (p.1 - v.1) * dywv) / l2).clamp(0.0, 1.0)
+	};
+
+	let dx = v.0 + t * dxwv - p.0;
+	let dy = v.1 + t * dywv - p.1;
+	return ((dx * dx + dy * dy).sqrt(), t);
+}
 
-		let l2 = dxwv * dxwv + dywv * dywv;
-		if l2 == 0.0 {
-			return (dxpv * dxpv * point.scale_x2 + dypv * dypv).sqrt() * DEG2METERS;
-		}
+// geodesic distance to the nearest point of the segment. the nearest point is found with the projection,
+// an error in its position has only a second order effect on the distance
+fn geodesic_segment_distance(segment: &Segment, point: &Point) -> f64 {
+	static GEODESIC: OnceLock<Geodesic> = OnceLock::new();
+	let geodesic = GEODESIC.get_or_init(Geodesic::wgs84);
 
-		let t = ((dxpv * dxwv - dypv * dywv) / l2).max(0.0).min(1.0);
+	let (_, t) = projected_segment_distance(segment, point);
+	let lon = segment.p0.x + t * (segment.p1.x - segment.p0.x);
+	let lat = segment.p0.y + t * (segment.p1.y - segment.p0.y);
 
-		let dx = pv.x + t * dxwv - point.x;
-		let dy = pv.y + t * dywv - point.y;
+	let distance: f64 = geodesic.inverse(point.y, point.x, lat, lon);
+	return distance;
+}
 
-		return (dx * dx * point.scale_x2 + dy * dy).sqrt() * DEG2METERS;
+// transverse mercator with scale 1 at the central meridian lon0, using the Krüger series (4th order).
+// returns easting and northing in meters
+fn transverse_mercator(lon0: f64, lon: f64, lat: f64) -> (f64, f64) {
+	let n = ELLIPSOID_F / (2.0 - ELLIPSOID_F);
+	let n2 = n * n;
+	let n3 = n2 * n;
+	let n4 = n3 * n;
+	let a = ELLIPSOID_A / (1.0 + n) * (1.0 + n2 / 4.0 + n4 / 64.0);
+	let alpha = [
+		n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0 + 41.0 * n4 / 180.0,
+		13.0 * n2 / 48.0 - 3.0 * n3 / 5.0 + 557.0 * n4 / 1440.0,
+		61.0 * n3 / 240.0 - 103.0 * n4 / 140.0,
+		49561.0 * n4 / 161280.0,
+	];
+
+	let phi = lat.to_radians();
+	let lambda = (lon - lon0).to_radians();
+
+	let e = 2.0 * n.sqrt() / (1.0 + n);
+	let t = (phi.sin().atanh() - e * (e * phi.sin()).atanh()).sinh();
+	let xi0 = t.atan2(lambda.cos());
+	let eta0 = (lambda.sin() / (1.0 + t * t).sqrt()).atanh();
+
+	let mut xi = xi0;
+	let mut eta = eta0;
+	for (j, alpha) in alpha.iter().enumerate() {
+		let k = 2.0 * (j + 1) as f64;
+		xi += alpha * (k * xi0).sin() * (k * eta0).cosh();
+		eta += alpha * (k * xi0).cos() * (k * eta0).sinh();
 	}
+
+	return (a * eta, a * xi);
 }
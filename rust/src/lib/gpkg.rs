@@ -0,0 +1,195 @@
+/*
+	GeoPackage reader:
+	A GeoPackage is a SQLite database. Geometries are stored as "GeoPackageBinary":
+	a small header (magic "GP", flags, srs_id, optional envelope) followed by a WKB geometry.
+	If the table has a spatial index (rtree_<table>_<column>), it is used for the bbox filter.
+*/
+
+use crate::error::Error;
+use crate::geometry::{Bbox, Geometry, Point};
+use rusqlite::{Connection, OpenFlags};
+use std::fs;
+use std::path::Path;
+
+pub fn fill_from_gpkg(geometry: &mut Geometry, filename: &Path, bbox: Option<&Bbox>) -> Result<(), Error> {
+	// sqlite would only report "unable to open database file"
+	fs::metadata(filename).map_err(|e| Error::io(filename, e))?;
+	let blobs = read_blobs(filename, bbox).map_err(|e| Error::format(filename, e))?;
+
+	for blob in blobs {
+		let wkb = strip_header(&blob).map_err(|e| Error::format(filename, e))?;
+		if let Some(wkb) = wkb {
+			let mut reader = WkbReader::new(wkb);
+			reader.read_geometry(geometry).map_err(|e| Error::format(filename, e))?;
+		}
+	}
+	return Ok(());
+}
+
+// the geometry blobs of all features, or only of those in the bbox, if there is a spatial index
+fn read_blobs(filename: &Path, bbox: Option<&Bbox>) -> Result<Vec<Vec<u8>>, String> {
+	let connection = Connection::open_with_flags(filename, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(|e| e.to_string())?;
+
+	let (table, column, srs_id) = connection.query_row(
+		"SELECT table_name, column_name, srs_id FROM gpkg_geometry_columns",
+		[],
+		|row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)),
+	).map_err(|e| format!("not a GeoPackage: {}", e))?;
+
+	if srs_id != 4326 {
+		return Err(format!("GeoPackage must use EPSG:4326, but table '{}' uses srs_id {}", table, srs_id));
+	}
+
+	let rtree = format!("rtree_{}_{}", table, column);
+	let has_rtree = connection.query_row(
+		"SELECT count(*) FROM sqlite_master WHERE name = ?1",
+		[&rtree],
+		|row| row.get::<_, i64>(0),
+	).map_err(|e| e.to_string())? > 0;
+
+	let rows:rusqlite::Result<Vec<Option<Vec<u8>>>> = match (bbox, has_rtree) {
+		(Some(bbox), true) => {
+			let sql = format!(
+				"SELECT t.\"{}\" FROM \"{}\" t JOIN \"{}\" r ON t.rowid = r.id WHERE r.minx <= ?1 AND r.maxx >= ?2 AND r.miny <= ?3 AND r.maxy >= ?4",
+				column, table, rtree
+			);
+			let mut statement = connection.prepare(&sql).map_err(|e| e.to_string())?;
+			let params = [bbox.x_max, bbox.x_min, bbox.y_max, bbox.y_min];
+			let rows = statement.query_map(params, |row| row.get::<_, Option<Vec<u8>>>(0)).map_err(|e| e.to_string())?;
+			rows.collect()
+		},
+		_ => {
+			let sql = format!("SELECT \"{}\" FROM \"{}\"", column, table);
+			let mut statement = connection.prepare(&sql).map_err(|e| e.to_string())?;
+			let rows = statement.query_map([], |row| row.get::<_, Option<Vec<u8>>>(0)).map_err(|e| e.to_string())?;
+			rows.collect()
+		}
+	};
+
+	return Ok(rows.map_err(|e| e.to_string())?.into_iter().flatten().collect());
+}
+
+// returns the WKB part of a GeoPackageBinary, or None, if the geometry is empty
+fn strip_header(blob: &[u8]) -> Result<Option<&[u8]>, String> {
+	if (blob.len() < 8) || (&blob[0..2] != b"GP") {
+		return Err("invalid GeoPackage geometry".to_string());
+	}
+
+	let flags = blob[3];
+	if flags & 0x10 != 0 {
+		return Ok(None);
+	}
+
+	let envelope_size = match (flags >> 1) & 0x07 {
+		0 => 0,
+		1 => 32,
+		2 | 3 => 48,
+		4 => 64,
+		envelope => return Err(format!("invalid GeoPackage envelope type: {}", envelope)),
+	};
+
+	return match blob.get(8 + envelope_size..) {
+		Some(wkb) => Ok(Some(wkb)),
+		None => Err("invalid GeoPackage geometry".to_string()),
+	};
+}
+
+struct WkbReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+	little_endian: bool,
+}
+
+impl WkbReader<'_> {
+	fn new(data: &[u8]) -> WkbReader<'_> {
+		return WkbReader { data, pos: 0, little_endian: true };
+	}
+	fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+		let bytes: [u8; N] = match self.data.get(self.pos..self.pos + N) {
+			Some(bytes) => bytes.try_into().unwrap(),
+			None => return Err("truncated WKB geometry".to_string()),
+		};
+		self.pos += N;
+		return Ok(bytes);
+	}
+	fn read_u8(&mut self) -> Result<u8, String> {
+		return Ok(self.read_bytes::<1>()?[0]);
+	}
+	fn read_u32(&mut self) -> Result<u32, String> {
+		let bytes = self.read_bytes::<4>()?;
+		return Ok(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) });
+	}
+	fn read_f64(&mut self) -> Result<f64, String> {
+		let bytes = self.read_bytes::<8>()?;
+		return Ok(if self.little_endian { f64::from_le_bytes(bytes) } else { f64::from_be_bytes(bytes) });
+	}
+	fn read_points(&mut self, dimensions: usize) -> Result<Vec<Point>, String> {
+		let count = self.read_u32()?;
+		let mut points: Vec<Point> = Vec::new();
+		for _ in 0..count {
+			let x = self.read_f64()?;
+			let y = self.read_f64()?;
+			// skip z and m
+			self.pos += 8 * (dimensions - 2);
+			points.push(Point::new(x, y));
+		}
+		return Ok(points);
+	}
+	fn read_rings(&mut self, dimensions: usize) -> Result<Vec<Vec<Point>>, String> {
+		let count = self.read_u32()?;
+		return (0..count).map(|_| self.read_points(dimensions)).collect();
+	}
+	fn read_geometry(&mut self, geometry: &mut Geometry) -> Result<(), String> {
+		self.little_endian = self.read_u8()? == 1;
+
+		// supports ISO WKB (e.g. 1003 = Polygon Z) as well as EWKB flags
+		let wkb_type = self.read_u32()?;
+		let mut dimensions = 2;
+		if wkb_type & 0x80000000 != 0 {
+			dimensions += 1;
+		}
+		if wkb_type & 0x40000000 != 0 {
+			dimensions += 1;
+		}
+		if wkb_type & 0x20000000 != 0 {
+			// skip srid
+			self.pos += 4;
+		}
+		let wkb_type = wkb_type & 0x0fffffff;
+		dimensions += match wkb_type / 1000 {
+			1 | 2 => 1,
+			3 => 2,
+			_ => 0,
+		};
+
+		match wkb_type % 1000 {
+			1 => {
+				let x = self.read_f64()?;
+				let y = self.read_f64()?;
+				self.pos += 8 * (dimensions - 2);
+				// empty points are encoded as NaN
+				if !x.is_nan() && !y.is_nan() {
+					geometry.add_point(Point::new(x, y));
+				}
+			},
+			2 => {
+				let points = self.read_points(dimensions)?;
+				geometry.add_line(points);
+			},
+			3 => {
+				let rings = self.read_rings(dimensions)?;
+				if !rings.is_empty() {
+					geometry.add_polygon(rings);
+				}
+			},
+			4..=7 => {
+				let count = self.read_u32()?;
+				for _ in 0..count {
+					self.read_geometry(geometry)?;
+				}
+			},
+			_ => return Err(format!("unknown wkb geometry type: {}", wkb_type)),
+		}
+		return Ok(());
+	}
+}
@@ -0,0 +1,285 @@
+/*
+	Input layers:
+	Every layer is a geometry file, that is rendered into its own channel (rules layers can have several):
+	- "distance": normalized signed distance, with its own min/max distance
+	- "coverage": share of the pixel, that is covered by the geometry,
+	  or with a distance range a signed distance like "distance", so the frontend can buffer and anti-alias the areas
+	- "rules": distance rules per Bundesland, the file contains the state boundaries
+	Distances can be spread nonlinear over the pixel values of the images with a "curve", see ValueCurve.
+	They are rendered with the segment tree or, with "renderer": "edt", as distance transform, see edt.rs.
+	Every layer loads its geometries with a halo around the bbox: its own reach (e.g. max_distance) plus "halo" meters,
+	so tiles rendered on their own give the same pixels along their borders as one render of the whole area.
+*/
+
+use crate::cli::*;
+use crate::edt::Renderer;
+use crate::encoding::ValueCurve;
+use crate::error::{Error, Result};
+use crate::geoimage::{ChannelInfo, Downsampling};
+use crate::geometry::{Bbox, Collection, DistanceModel};
+use crate::rules::*;
+use std::path::{Path, PathBuf};
+
+// in meters, geometries touching the border of the bbox are complete, even with rounded coordinates in the spatial index
+pub const DEFAULT_HALO: f32 = 100.0;
+
+pub enum LayerKind {
+	Distance { min_distance: f32, max_distance: f32 },
+	Coverage,
+	Rules(Rules),
+}
+
+pub struct Layer {
+	pub name: String,
+	pub filename: PathBuf,
+	pub kind: LayerKind,
+	pub distance_model: DistanceModel,
+	// in meters, added to the reach of the layer
+	pub halo: f32,
+	// only for distances
+	pub curve: ValueCurve,
+	// only for distances
+	pub renderer: Renderer,
+	pub collection: Collection,
+}
+
+impl Layer {
+	pub fn new(name: &str, filename: &Path, kind: LayerKind) -> Layer {
+		return Layer {
+			name: name.to_string(),
+			filename: filename.to_path_buf(),
+			kind,
+			distance_model: DistanceModel::Approximate,
+			halo: DEFAULT_HALO,
+			curve: ValueCurve::Linear,
+			renderer: Renderer::Tree,
+			collection: Collection::new(),
+		};
+	}
+	// loads all geometries, that can affect a pixel inside the bbox
+	pub fn load(&mut self, bbox: &Bbox) -> Result<()> {
+		let bbox = bbox.buffered(self.halo as f64);
+		match &mut self.kind {
+			LayerKind::Distance { max_distance, .. } => {
+				self.collection.fill_from_file(&self.filename, Some(&bbox.buffered(*max_distance as f64)))?;
+			},
+			LayerKind::Coverage => {
+				self.collection.fill_from_file(&self.filename, Some(&bbox))?;
+			},
+			LayerKind::Rules(rules) => {
+				rules.load(&self.filename, &bbox, self.distance_model)?;
+			},
+		}
+		self.collection.set_distance_model(self.distance_model);
+		return Ok(());
+	}
+	pub fn channel_count(&self) -> usize {
+		return match &self.kind {
+			LayerKind::Rules(rules) => rules.channel_count(),
+			_ => 1,
+		};
+	}
+	// name, value range and fill value of every channel,
+	// the fill value is the pixel value far away from all geometries: max_distance for distances
+	pub fn channel_infos(&self) -> Vec<ChannelInfo> {
+		return match &self.kind {
+			LayerKind::Distance { min_distance, max_distance } => {
+				vec![ChannelInfo::new(&self.name, [*min_distance, *max_distance], 1.0).with_curve(self.curve.clone())]
+			},
+			LayerKind::Coverage => vec![ChannelInfo::new(&self.name, [0.0, 1.0], 0.0)],
+			LayerKind::Rules(rules) => rules.channel_infos(&self.name),
+		};
+	}
+	// the downsampling of every channel: for Min the conservative value of the block,
+	// the nearest distance, but the largest share of coverage.
+	// masks and fields of rules are 1 or far where turbines are allowed, so min stays conservative for them
+	pub fn channel_downsampling(&self, downsampling: Downsampling) -> Vec<Downsampling> {
+		let downsampling = match (&self.kind, downsampling) {
+			(LayerKind::Coverage, Downsampling::Min) => Downsampling::Max,
+			_ => downsampling,
+		};
+		return vec![downsampling; self.channel_count()];
+	}
+	// the curve only changes distances, it has to fit to their range
+	fn set_curve(&mut self, curve: ValueCurve) -> Result<()> {
+		if let LayerKind::Distance { min_distance, max_distance } = self.kind {
+			curve.check([min_distance, max_distance]).map_err(|e| Error::Invalid(format!("\"curve\": {}", e)))?;
+			self.curve = curve;
+		}
+		return Ok(());
+	}
+}
+
+// parses the "layers" array of the arguments:
+// [{ "name": "wohngebaeude", "filename": "…", "type": "distance", "min_distance": 0, "max_distance": 3000 }, …]
+// without "layers" the old arguments are used: filename_geo_dyn as distance and filename_geo_fix as coverage
+// "distance_model" ("approximate", "projected" or "geodesic") can be set for all layers or per layer, "halo" only per layer
+// "coverage_distance": [min, max] renders all coverage layers as signed distance, a coverage layer can set its own min/max_distance
+// "curve" (e.g. { "type": "log", "scale": 250 }) and "renderer" ("tree" or "edt") can be set for all distances or per layer
+pub fn parse_layers(obj: &json::JsonValue) -> Result<Vec<Layer>> {
+	let distance_model = parse_distance_model(&obj["distance_model"], DistanceModel::Approximate)?;
+	let renderer = parse_renderer(&obj["renderer"], Renderer::Tree)?;
+	let coverage_distance = parse_distance_range(&obj["coverage_distance"])?;
+	let curve = parse_curve(&obj["curve"], &ValueCurve::Linear)?;
+
+	if obj["layers"].is_null() {
+		let min_distance = get_f32(obj, "min_distance")?;
+		let max_distance = get_f32(obj, "max_distance")?;
+		check_min_max("min_distance", min_distance, "max_distance", max_distance)?;
+
+		let filename_dyn = PathBuf::from(get_str(obj, "filename_geo_dyn")?);
+		let filename_fix = PathBuf::from(get_str(obj, "filename_geo_fix")?);
+		check_file("filename_geo_dyn", &filename_dyn)?;
+		check_file("filename_geo_fix", &filename_fix)?;
+
+		let mut layers = vec![
+			Layer::new("dyn", &filename_dyn, LayerKind::Distance { min_distance, max_distance }),
+			Layer::new("fix", &filename_fix, coverage_kind(coverage_distance)),
+		];
+		for layer in layers.iter_mut() {
+			layer.distance_model = distance_model;
+			layer.renderer = renderer;
+			layer.set_curve(curve.clone())?;
+			check_renderer(layer)?;
+		}
+		return Ok(layers);
+	}
+
+	if !obj["layers"].is_array() || obj["layers"].is_empty() {
+		return Err(Error::Invalid("\"layers\" must be a non empty list".to_string()));
+	}
+
+	let mut layers:Vec<Layer> = Vec::new();
+	for (index, entry) in obj["layers"].members().enumerate() {
+		let layer = parse_layer(entry, distance_model, renderer, coverage_distance, &curve).map_err(|e| e.context(&format!("layers[{}]", index)))?;
+		layers.push(layer);
+	}
+	return Ok(layers);
+}
+
+fn parse_layer(entry: &json::JsonValue, distance_model: DistanceModel, renderer: Renderer, coverage_distance: Option<[f32; 2]>, curve: &ValueCurve) -> Result<Layer> {
+	let kind_name = get_str(entry, "type")?;
+	let known:&[&str] = match kind_name.as_str() {
+		"distance" => &["name", "filename", "type", "halo", "distance_model", "renderer", "curve", "min_distance", "max_distance"],
+		"coverage" => &["name", "filename", "type", "halo", "distance_model", "renderer", "curve", "min_distance", "max_distance"],
+		"rules" => &["name", "filename", "type", "halo", "distance_model", "output", "min_distance", "max_distance", "max_height", "rotor_ratio", "turbines", "rules"],
+		kind => return Err(Error::Invalid(format!("unknown layer type \"{}\", expected distance, coverage or rules", kind))),
+	};
+	check_keys(entry, known)?;
+
+	let filename = PathBuf::from(get_str(entry, "filename")?);
+	check_file("filename", &filename)?;
+	let name = match entry["name"].as_str() {
+		Some(name) => name.to_string(),
+		None => filename.file_stem().unwrap().to_string_lossy().to_string(),
+	};
+
+	let kind = match kind_name.as_str() {
+		"distance" => {
+			let min_distance = get_f32(entry, "min_distance")?;
+			let max_distance = get_f32(entry, "max_distance")?;
+			check_min_max("min_distance", min_distance, "max_distance", max_distance)?;
+			LayerKind::Distance { min_distance, max_distance }
+		},
+		"coverage" => {
+			if entry["min_distance"].is_null() && entry["max_distance"].is_null() {
+				coverage_kind(coverage_distance)
+			} else {
+				let min_distance = get_f32(entry, "min_distance")?;
+				let max_distance = get_f32(entry, "max_distance")?;
+				check_min_max("min_distance", min_distance, "max_distance", max_distance)?;
+				LayerKind::Distance { min_distance, max_distance }
+			}
+		},
+		_ => LayerKind::Rules(parse_rules(entry)?),
+	};
+
+	let mut layer = Layer::new(&name, &filename, kind);
+	layer.distance_model = parse_distance_model(&entry["distance_model"], distance_model)?;
+	layer.halo = get_f32_or(entry, "halo", DEFAULT_HALO)?;
+	if layer.halo < 0.0 {
+		return Err(Error::Invalid(format!("\"halo\" must not be negative, found {}", layer.halo)));
+	}
+	for key in ["curve", "renderer"] {
+		if !entry[key].is_null() && !matches!(layer.kind, LayerKind::Distance { .. }) {
+			return Err(Error::Invalid(format!("\"{}\" is only used for distances", key)));
+		}
+	}
+	layer.set_curve(parse_curve(&entry["curve"], curve)?)?;
+	layer.renderer = parse_renderer(&entry["renderer"], renderer)?;
+	check_renderer(&layer)?;
+	return Ok(layer);
+}
+
+// "linear", { "type": "log", "scale": meters } or { "type": "piecewise", "breakpoints": [meters, …] }
+fn parse_curve(value: &json::JsonValue, default: &ValueCurve) -> Result<ValueCurve> {
+	if value.is_null() {
+		return Ok(default.clone());
+	}
+	let name = match value.as_str() {
+		Some(name) => name,
+		None => value["type"].as_str().unwrap_or(""),
+	};
+	let curve = match name {
+		"linear" => ValueCurve::Linear,
+		"log" => ValueCurve::Log { scale: get_f32(value, "scale")? },
+		"piecewise" => ValueCurve::Piecewise { breakpoints: get_f32_list_or(value, "breakpoints", &[])? },
+		_ => return Err(Error::Invalid(format!("unknown curve {}, expected linear, log or piecewise", value))),
+	};
+	if value.is_object() {
+		check_keys(value, &["type", "scale", "breakpoints"])?;
+	}
+	return Ok(curve);
+}
+
+// a coverage layer with a distance range is rendered like a distance layer
+fn coverage_kind(distance_range: Option<[f32; 2]>) -> LayerKind {
+	return match distance_range {
+		Some([min_distance, max_distance]) => LayerKind::Distance { min_distance, max_distance },
+		None => LayerKind::Coverage,
+	};
+}
+
+fn parse_distance_range(value: &json::JsonValue) -> Result<Option<[f32; 2]>> {
+	if value.is_null() {
+		return Ok(None);
+	}
+	let invalid = || Error::Invalid(format!("\"coverage_distance\" must be [min_distance, max_distance] in meters, found {}", value));
+	if !value.is_array() || (value.len() != 2) {
+		return Err(invalid());
+	}
+	let range = [value[0].as_f32().ok_or_else(invalid)?, value[1].as_f32().ok_or_else(invalid)?];
+	if !range[0].is_finite() || !range[1].is_finite() || (range[0] >= range[1]) {
+		return Err(invalid());
+	}
+	return Ok(Some(range));
+}
+
+fn parse_renderer(value: &json::JsonValue, default: Renderer) -> Result<Renderer> {
+	if value.is_null() {
+		return Ok(default);
+	}
+	return match value.as_str().and_then(Renderer::from_name) {
+		Some(renderer) => Ok(renderer),
+		None => Err(Error::Invalid(format!("unknown renderer {}, expected tree or edt", value))),
+	};
+}
+
+// the distance transform measures between cells, not along the ellipsoid
+fn check_renderer(layer: &Layer) -> Result<()> {
+	let is_distance = matches!(layer.kind, LayerKind::Distance { .. });
+	if is_distance && matches!(layer.renderer, Renderer::Edt { .. }) && (layer.distance_model != DistanceModel::Approximate) {
+		return Err(Error::Invalid("renderer edt only supports the approximate distance_model".to_string()));
+	}
+	return Ok(());
+}
+
+fn parse_distance_model(value: &json::JsonValue, default: DistanceModel) -> Result<DistanceModel> {
+	if value.is_null() {
+		return Ok(default);
+	}
+	return match value.as_str().and_then(DistanceModel::from_name) {
+		Some(model) => Ok(model),
+		None => Err(Error::Invalid(format!("unknown distance_model {}, expected approximate, projected or geodesic", value))),
+	};
+}
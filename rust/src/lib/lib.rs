@@ -0,0 +1,32 @@
+/*
+	Renders distance fields of geometries as map tiles.
+	Used by the windradabstand binary (see commands.rs), but can be embedded as well:
+
+		let mut collection = Collection::new();
+		collection.fill_from_file(Path::new("wohngebaeude.gpkg"), Some(&bbox))?;
+		let mut image = GeoImage::new(512, 9, 275, 167, &[1.0]);
+		image.draw_distances(0, &collection, -500.0, 2000.0, 0);
+		image.export_to(Path::new("tiles"), &TileFormat::Png)?;
+*/
+
+pub mod bintile;
+pub mod cli;
+pub mod commands;
+pub mod contours;
+pub mod edt;
+pub mod encoding;
+pub mod error;
+pub mod expression;
+pub mod fgb;
+pub mod geoimage;
+pub mod geometry;
+pub mod gpkg;
+pub mod layers;
+pub mod output;
+pub mod rules;
+pub mod statistics;
+pub mod tiles;
+
+pub use error::{Error, Result};
+pub use geoimage::{Channel, ChannelInfo, Downsampling, GeoImage, TileFormat};
+pub use geometry::{Bbox, Collection, DistanceModel, Geometry, Point};
@@ -0,0 +1,587 @@
+/*
+	Where the tiles are saved to:
+	- a directory: "z/y/x.png", like before
+	- MBTiles: a SQLite file, tiles with identical content are saved only once (the "map" and "images" tables)
+	- PMTiles (version 3): a single file for static hosting, identical tiles share their data and
+	  neighbouring identical tiles are saved as one run. The file is written by finish, so it can not be resumed.
+	Images with more than 3 channels are split like the files of a directory: "tiles.mbtiles" holds channels 0-2,
+	"tiles-1.mbtiles" channels 3-5, …
+	The metadata (how to decode the pixels, see encoding.rs) is saved as "metadata.json" in a directory,
+	as "json" in the metadata table of MBTiles and as part of the PMTiles metadata.
+*/
+
+use crate::encoding::PixelEncoding;
+use crate::error::{Error, Result};
+use crate::geoimage::{GeoImage, TileFormat};
+use crate::tiles::{tile_bbox, TileCoord};
+use json::JsonValue;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs::{self, File, create_dir_all};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use twox_hash::XxHash3_128;
+
+pub trait TileOutput: Sync {
+	fn format(&self) -> TileFormat;
+	fn encoding(&self) -> PixelEncoding;
+	fn write_metadata(&self, metadata: &JsonValue) -> Result<()>;
+	fn contains(&self, tile: TileCoord) -> Result<bool>;
+	// images: the encoded files of the tile, one per 3 channels
+	fn write(&self, tile: TileCoord, images: &[Vec<u8>]) -> Result<()>;
+	// has to be called after the last tile
+	fn finish(&self) -> Result<()>;
+
+	fn write_image(&self, image: &GeoImage) -> Result<()> {
+		return self.write(image.tile_coord(), &image.encode_images(&self.format(), self.encoding())?);
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OutputKind {
+	Directory,
+	Mbtiles,
+	Pmtiles,
+}
+
+impl OutputKind {
+	// by file extension, everything else is a directory
+	pub fn from_path(path: &Path) -> OutputKind {
+		return match path.extension().and_then(|extension| extension.to_str()) {
+			Some("mbtiles") => OutputKind::Mbtiles,
+			Some("pmtiles") => OutputKind::Pmtiles,
+			_ => OutputKind::Directory,
+		};
+	}
+}
+
+pub fn open_output(path: &Path, format: TileFormat, encoding: PixelEncoding) -> Result<Box<dyn TileOutput>> {
+	return Ok(match OutputKind::from_path(path) {
+		OutputKind::Directory => Box::new(DirectoryOutput::new(path, format, encoding)),
+		OutputKind::Mbtiles => Box::new(MbtilesOutput::open(path, format, encoding)?),
+		OutputKind::Pmtiles => Box::new(PmtilesOutput::create(path, format, encoding)?),
+	});
+}
+
+// "tiles.png" for group 0, "tiles-1.png" for group 1, …
+pub fn group_path(path: &Path, group: usize) -> PathBuf {
+	if group == 0 {
+		return path.to_path_buf();
+	}
+	let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+	return match path.extension() {
+		Some(extension) => path.with_file_name(format!("{}-{}.{}", stem, group, extension.to_string_lossy())),
+		None => path.with_file_name(format!("{}-{}", stem, group)),
+	};
+}
+
+pub struct DirectoryOutput {
+	folder: PathBuf,
+	format: TileFormat,
+	encoding: PixelEncoding,
+}
+
+impl DirectoryOutput {
+	pub fn new(folder: &Path, format: TileFormat, encoding: PixelEncoding) -> DirectoryOutput {
+		return DirectoryOutput { folder: folder.to_path_buf(), format, encoding };
+	}
+}
+
+impl TileOutput for DirectoryOutput {
+	fn format(&self) -> TileFormat {
+		return self.format;
+	}
+	fn encoding(&self) -> PixelEncoding {
+		return self.encoding;
+	}
+	fn write_metadata(&self, metadata: &JsonValue) -> Result<()> {
+		create_dir_all(&self.folder).map_err(|e| Error::io(&self.folder, e))?;
+		let path = self.folder.join("metadata.json");
+		return fs::write(&path, metadata.pretty(1)).map_err(|e| Error::io(&path, e));
+	}
+	fn contains(&self, tile: TileCoord) -> Result<bool> {
+		return Ok(GeoImage::calc_path(&self.folder, tile.zoom, tile.y, tile.x, self.format.extension()).is_file());
+	}
+	// creates the folders of the tile, if needed
+	fn write(&self, tile: TileCoord, images: &[Vec<u8>]) -> Result<()> {
+		let filename = GeoImage::calc_path(&self.folder, tile.zoom, tile.y, tile.x, self.format.extension());
+		if let Some(folder) = filename.parent() {
+			create_dir_all(folder).map_err(|e| Error::io(folder, e))?;
+		}
+		// group 0 last, contains only checks it
+		for (group, data) in images.iter().enumerate().rev() {
+			let path = group_path(&filename, group);
+			fs::write(&path, data).map_err(|e| Error::io(&path, e))?;
+		}
+		return Ok(());
+	}
+	fn finish(&self) -> Result<()> {
+		return Ok(());
+	}
+}
+
+// XXH3 with 128 bit: the same on every platform and in every version of the program,
+// so that resumed MBTiles keep finding the tiles of earlier runs.
+// on a hit the stored data is still compared, see MbtilesOutput::write and PmtilesOutput::write
+fn content_hash(data: &[u8]) -> u128 {
+	return XxHash3_128::oneshot(data);
+}
+
+fn format_name(format: TileFormat) -> &'static str {
+	return &format.extension()[1..];
+}
+
+fn sqlite_error(path: &Path, error: rusqlite::Error) -> Error {
+	return Error::format(path, format!("sqlite: {}", error));
+}
+
+// every group is saved in its own archive, opened when the first tile of the group is written
+struct Archives<T> {
+	path: PathBuf,
+	archives: Vec<T>,
+}
+
+impl<T> Archives<T> {
+	fn get<F>(&mut self, group: usize, open: F) -> Result<&mut T> where F: Fn(&Path) -> Result<T> {
+		while self.archives.len() <= group {
+			let archive = open(&group_path(&self.path, self.archives.len()))?;
+			self.archives.push(archive);
+		}
+		return Ok(&mut self.archives[group]);
+	}
+}
+
+// tiles are committed one by one, so an interrupted run can be resumed
+pub struct MbtilesOutput {
+	format: TileFormat,
+	encoding: PixelEncoding,
+	metadata: Mutex<Option<String>>,
+	archives: Mutex<Archives<(PathBuf, Connection)>>,
+}
+
+impl MbtilesOutput {
+	pub fn open(path: &Path, format: TileFormat, encoding: PixelEncoding) -> Result<MbtilesOutput> {
+		let mut archives = Archives { path: path.to_path_buf(), archives: Vec::new() };
+		archives.get(0, open_mbtiles)?;
+		return Ok(MbtilesOutput { format, encoding, metadata: Mutex::new(None), archives: Mutex::new(archives) });
+	}
+}
+
+fn open_mbtiles(path: &Path) -> Result<(PathBuf, Connection)> {
+	if let Some(folder) = path.parent() {
+		if !folder.as_os_str().is_empty() {
+			create_dir_all(folder).map_err(|e| Error::io(folder, e))?;
+		}
+	}
+	let connection = Connection::open(path).map_err(|e| sqlite_error(path, e))?;
+	connection.execute_batch("
+		PRAGMA journal_mode = WAL;
+		PRAGMA synchronous = NORMAL;
+		CREATE TABLE IF NOT EXISTS metadata (name TEXT PRIMARY KEY, value TEXT);
+		CREATE TABLE IF NOT EXISTS map (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_id TEXT);
+		CREATE UNIQUE INDEX IF NOT EXISTS map_index ON map (zoom_level, tile_column, tile_row);
+		CREATE TABLE IF NOT EXISTS images (tile_id TEXT PRIMARY KEY, tile_data BLOB);
+		CREATE VIEW IF NOT EXISTS tiles AS
+			SELECT map.zoom_level AS zoom_level, map.tile_column AS tile_column, map.tile_row AS tile_row, images.tile_data AS tile_data
+			FROM map JOIN images ON images.tile_id = map.tile_id;
+	").map_err(|e| sqlite_error(path, e))?;
+	return Ok((path.to_path_buf(), connection));
+}
+
+// MBTiles counts the rows from the bottom (TMS)
+fn tms_row(tile: TileCoord) -> u32 {
+	return (1u32 << tile.zoom) - 1 - tile.y;
+}
+
+impl TileOutput for MbtilesOutput {
+	fn format(&self) -> TileFormat {
+		return self.format;
+	}
+	fn encoding(&self) -> PixelEncoding {
+		return self.encoding;
+	}
+	// saved by finish, in all archives
+	fn write_metadata(&self, metadata: &JsonValue) -> Result<()> {
+		let _metadata = self.metadata.lock().unwrap().insert(metadata.dump());
+		return Ok(());
+	}
+	fn contains(&self, tile: TileCoord) -> Result<bool> {
+		let mut archives = self.archives.lock().unwrap();
+		let (path, connection) = archives.get(0, open_mbtiles)?;
+		let found: Option<i64> = connection.query_row(
+			"SELECT 1 FROM map WHERE zoom_level = ?1 AND tile_column = ?2 AND tile_row = ?3",
+			params![tile.zoom, tile.x, tms_row(tile)],
+			|row| row.get(0),
+		).optional().map_err(|e| sqlite_error(path, e))?;
+		return Ok(found.is_some());
+	}
+	// every group is committed on its own, group 0 last: contains only checks it,
+	// so an interrupted write is repeated, when the run is resumed
+	fn write(&self, tile: TileCoord, images: &[Vec<u8>]) -> Result<()> {
+		let mut archives = self.archives.lock().unwrap();
+		for (group, data) in images.iter().enumerate().rev() {
+			let (path, connection) = archives.get(group, open_mbtiles)?;
+			let hash = format!("{:032x}", content_hash(data));
+			let transaction = connection.transaction().map_err(|e| sqlite_error(path, e))?;
+			// different data with the same hash gets the next free id: "<hash>-1", "<hash>-2", …
+			let mut tile_id = hash.clone();
+			for index in 1.. {
+				let stored: Option<Vec<u8>> = transaction.query_row("SELECT tile_data FROM images WHERE tile_id = ?1", params![tile_id], |row| row.get(0))
+					.optional().map_err(|e| sqlite_error(path, e))?;
+				match stored {
+					Some(stored) if stored == *data => break,
+					Some(_stored) => tile_id = format!("{}-{}", hash, index),
+					None => {
+						transaction.execute("INSERT INTO images (tile_id, tile_data) VALUES (?1, ?2)", params![tile_id, data])
+							.map_err(|e| sqlite_error(path, e))?;
+						break;
+					},
+				}
+			}
+			transaction.execute("INSERT OR REPLACE INTO map (zoom_level, tile_column, tile_row, tile_id) VALUES (?1, ?2, ?3, ?4)", params![tile.zoom, tile.x, tms_row(tile), tile_id])
+				.map_err(|e| sqlite_error(path, e))?;
+			transaction.commit().map_err(|e| sqlite_error(path, e))?;
+		}
+		return Ok(());
+	}
+	// metadata of all tiles, including the ones of earlier runs
+	fn finish(&self) -> Result<()> {
+		let archives = self.archives.lock().unwrap();
+		let json = self.metadata.lock().unwrap().clone();
+		for (path, connection) in archives.archives.iter() {
+			if let Some(json) = &json {
+				connection.execute("INSERT OR REPLACE INTO metadata (name, value) VALUES ('json', ?1)", params![json])
+					.map_err(|e| sqlite_error(path, e))?;
+			}
+			let zoom_range: (Option<u32>, Option<u32>) = connection.query_row("SELECT MIN(zoom_level), MAX(zoom_level) FROM map", [], |row| Ok((row.get(0)?, row.get(1)?)))
+				.map_err(|e| sqlite_error(path, e))?;
+			let (Some(min_zoom), Some(max_zoom)) = zoom_range else {
+				continue;
+			};
+			let range: (u32, u32, u32, u32) = connection.query_row(
+				"SELECT MIN(tile_column), MIN(tile_row), MAX(tile_column), MAX(tile_row) FROM map WHERE zoom_level = ?1",
+				params![max_zoom],
+				|row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+			).map_err(|e| sqlite_error(path, e))?;
+			let (x_min, row_min, x_max, row_max) = range;
+			let south_west = tile_bbox(TileCoord { zoom: max_zoom, x: x_min, y: (1u32 << max_zoom) - 1 - row_min });
+			let north_east = tile_bbox(TileCoord { zoom: max_zoom, x: x_max, y: (1u32 << max_zoom) - 1 - row_max });
+			let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+
+			let metadata = [
+				("name", name),
+				("format", format_name(self.format).to_string()),
+				("type", "overlay".to_string()),
+				("minzoom", min_zoom.to_string()),
+				("maxzoom", max_zoom.to_string()),
+				("bounds", format!("{},{},{},{}", south_west.x_min, south_west.y_min, north_east.x_max, north_east.y_max)),
+			];
+			for (key, value) in metadata {
+				connection.execute("INSERT OR REPLACE INTO metadata (name, value) VALUES (?1, ?2)", params![key, value])
+					.map_err(|e| sqlite_error(path, e))?;
+			}
+		}
+		return Ok(());
+	}
+}
+
+// the root directory has to be inside the first 16 KB of the file
+const PMTILES_HEADER_LENGTH: usize = 127;
+const PMTILES_MAX_ROOT_LENGTH: usize = 16384 - PMTILES_HEADER_LENGTH;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PmtilesEntry {
+	pub tile_id: u64,
+	pub offset: u64,
+	pub length: u32,
+	pub run_length: u32,
+}
+
+// the tile data is written to "tiles.pmtiles.tmp" at once, header and directories are added by finish
+struct PmtilesArchive {
+	path: PathBuf,
+	data_path: PathBuf,
+	data: BufWriter<File>,
+	// to compare tiles with the same hash
+	reader: File,
+	data_length: u64,
+	entries: HashMap<u64, (u64, u32)>,
+	contents: HashMap<u128, (u64, u32)>,
+	zoom_range: [u32; 2],
+	// west, south, east, north
+	bounds: [f64; 4],
+}
+
+impl PmtilesArchive {
+	fn append(&mut self, data: &[u8]) -> Result<(u64, u32)> {
+		self.data.write_all(data).map_err(|e| Error::io(&self.data_path, e))?;
+		let location = (self.data_length, data.len() as u32);
+		self.data_length += data.len() as u64;
+		return Ok(location);
+	}
+	fn stored_data(&mut self, (offset, length): (u64, u32)) -> Result<Vec<u8>> {
+		self.data.flush().map_err(|e| Error::io(&self.data_path, e))?;
+		let mut data = vec![0u8; length as usize];
+		self.reader.seek(SeekFrom::Start(offset)).map_err(|e| Error::io(&self.data_path, e))?;
+		self.reader.read_exact(&mut data).map_err(|e| Error::io(&self.data_path, e))?;
+		return Ok(data);
+	}
+}
+
+fn create_pmtiles(path: &Path) -> Result<PmtilesArchive> {
+	if let Some(folder) = path.parent() {
+		if !folder.as_os_str().is_empty() {
+			create_dir_all(folder).map_err(|e| Error::io(folder, e))?;
+		}
+	}
+	let mut data_path = path.as_os_str().to_os_string();
+	data_path.push(".tmp");
+	let data_path = PathBuf::from(data_path);
+	let file = File::create(&data_path).map_err(|e| Error::io(&data_path, e))?;
+	let reader = File::open(&data_path).map_err(|e| Error::io(&data_path, e))?;
+	return Ok(PmtilesArchive {
+		path: path.to_path_buf(),
+		data_path,
+		data: BufWriter::new(file),
+		reader,
+		data_length: 0,
+		entries: HashMap::new(),
+		contents: HashMap::new(),
+		zoom_range: [u32::MAX, 0],
+		bounds: [0.0; 4],
+	});
+}
+
+pub struct PmtilesOutput {
+	format: TileFormat,
+	encoding: PixelEncoding,
+	metadata: Mutex<JsonValue>,
+	archives: Mutex<Archives<PmtilesArchive>>,
+}
+
+impl PmtilesOutput {
+	// an existing file is replaced
+	pub fn create(path: &Path, format: TileFormat, encoding: PixelEncoding) -> Result<PmtilesOutput> {
+		let mut archives = Archives { path: path.to_path_buf(), archives: Vec::new() };
+		archives.get(0, create_pmtiles)?;
+		return Ok(PmtilesOutput { format, encoding, metadata: Mutex::new(JsonValue::new_object()), archives: Mutex::new(archives) });
+	}
+}
+
+impl TileOutput for PmtilesOutput {
+	fn format(&self) -> TileFormat {
+		return self.format;
+	}
+	fn encoding(&self) -> PixelEncoding {
+		return self.encoding;
+	}
+	// saved by finish
+	fn write_metadata(&self, metadata: &JsonValue) -> Result<()> {
+		*self.metadata.lock().unwrap() = metadata.clone();
+		return Ok(());
+	}
+	fn contains(&self, tile: TileCoord) -> Result<bool> {
+		let archives = self.archives.lock().unwrap();
+		return Ok(archives.archives[0].entries.contains_key(&zxy_to_tile_id(tile)));
+	}
+	fn write(&self, tile: TileCoord, images: &[Vec<u8>]) -> Result<()> {
+		let mut archives = self.archives.lock().unwrap();
+		let bbox = tile_bbox(tile);
+		for (group, data) in images.iter().enumerate() {
+			let archive = archives.get(group, create_pmtiles)?;
+			let hash = content_hash(data);
+			let location = match archive.contents.get(&hash).copied() {
+				Some(location) if archive.stored_data(location)? == *data => location,
+				// different data with the same hash is saved again, without sharing
+				Some(_location) => archive.append(data)?,
+				None => {
+					let location = archive.append(data)?;
+					archive.contents.insert(hash, location);
+					location
+				},
+			};
+			archive.entries.insert(zxy_to_tile_id(tile), location);
+
+			// bounds of the tiles of the highest zoom level, the lower ones cover a larger area
+			if archive.entries.len() == 1 || tile.zoom > archive.zoom_range[1] {
+				archive.bounds = [bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max];
+			} else if tile.zoom == archive.zoom_range[1] {
+				archive.bounds = [
+					archive.bounds[0].min(bbox.x_min),
+					archive.bounds[1].min(bbox.y_min),
+					archive.bounds[2].max(bbox.x_max),
+					archive.bounds[3].max(bbox.y_max),
+				];
+			}
+			archive.zoom_range = [archive.zoom_range[0].min(tile.zoom), archive.zoom_range[1].max(tile.zoom)];
+		}
+		return Ok(());
+	}
+	fn finish(&self) -> Result<()> {
+		let mut archives = self.archives.lock().unwrap();
+		let metadata = self.metadata.lock().unwrap();
+		for archive in archives.archives.iter_mut() {
+			finish_pmtiles(archive, self.format, &metadata)?;
+		}
+		return Ok(());
+	}
+}
+
+fn finish_pmtiles(archive: &mut PmtilesArchive, format: TileFormat, metadata: &JsonValue) -> Result<()> {
+	archive.data.flush().map_err(|e| Error::io(&archive.data_path, e))?;
+
+	let mut tiles: Vec<(u64, (u64, u32))> = archive.entries.iter().map(|(tile_id, location)| (*tile_id, *location)).collect();
+	tiles.sort_unstable_by_key(|(tile_id, _location)| *tile_id);
+
+	// neighbouring tiles with the same data become one entry
+	let mut entries: Vec<PmtilesEntry> = Vec::new();
+	for (tile_id, (offset, length)) in tiles {
+		if let Some(last) = entries.last_mut() {
+			if last.tile_id + last.run_length as u64 == tile_id && last.offset == offset && last.length == length {
+				last.run_length += 1;
+				continue;
+			}
+		}
+		entries.push(PmtilesEntry { tile_id, offset, length, run_length: 1 });
+	}
+	let (root, leaves) = build_directories(&entries);
+
+	let name = archive.path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+	let mut metadata = metadata.clone();
+	metadata["name"] = name.into();
+	metadata["type"] = "overlay".into();
+	let metadata = metadata.dump().into_bytes();
+
+	let root_offset = PMTILES_HEADER_LENGTH as u64;
+	let metadata_offset = root_offset + root.len() as u64;
+	let leaves_offset = metadata_offset + metadata.len() as u64;
+	let data_offset = leaves_offset + leaves.len() as u64;
+
+	let [west, south, east, north] = if entries.is_empty() { [0.0; 4] } else { archive.bounds };
+	let [min_zoom, max_zoom] = if entries.is_empty() { [0, 0] } else { archive.zoom_range };
+	let e7 = |value: f64| -> [u8; 4] { ((value * 1.0e7).round() as i32).to_le_bytes() };
+
+	let mut header: Vec<u8> = Vec::with_capacity(PMTILES_HEADER_LENGTH);
+	header.extend_from_slice(b"PMTiles");
+	header.push(3);
+	for value in [
+		root_offset, root.len() as u64,
+		metadata_offset, metadata.len() as u64,
+		leaves_offset, leaves.len() as u64,
+		data_offset, archive.data_length,
+		archive.entries.len() as u64, entries.len() as u64, archive.contents.len() as u64,
+	] {
+		header.extend_from_slice(&value.to_le_bytes());
+	}
+	// not clustered, internal compression none, tile compression none, tile type
+	header.extend_from_slice(&[0, 1, 1, pmtiles_tile_type(format)]);
+	header.extend_from_slice(&[min_zoom as u8, max_zoom as u8]);
+	for value in [west, south, east, north] {
+		header.extend_from_slice(&e7(value));
+	}
+	header.push(min_zoom as u8);
+	header.extend_from_slice(&e7((west + east) / 2.0));
+	header.extend_from_slice(&e7((south + north) / 2.0));
+
+	let path = &archive.path;
+	let file = File::create(path).map_err(|e| Error::io(path, e))?;
+	let mut writer = BufWriter::new(file);
+	for part in [&header, &root, &metadata, &leaves] {
+		writer.write_all(part).map_err(|e| Error::io(path, e))?;
+	}
+	let mut data = File::open(&archive.data_path).map_err(|e| Error::io(&archive.data_path, e))?;
+	io::copy(&mut data, &mut writer).map_err(|e| Error::io(path, e))?;
+	writer.flush().map_err(|e| Error::io(path, e))?;
+	fs::remove_file(&archive.data_path).map_err(|e| Error::io(&archive.data_path, e))?;
+	return Ok(());
+}
+
+fn pmtiles_tile_type(format: TileFormat) -> u8 {
+	return match format {
+		TileFormat::Png => 2,
+		TileFormat::Webp { .. } => 4,
+		TileFormat::Bin(_) => 0,
+	};
+}
+
+// root directory and leaf directories, the root has to fit into the first 16 KB
+fn build_directories(entries: &[PmtilesEntry]) -> (Vec<u8>, Vec<u8>) {
+	let root = serialize_directory(entries);
+	if root.len() <= PMTILES_MAX_ROOT_LENGTH {
+		return (root, Vec::new());
+	}
+
+	let mut leaf_size = 4096;
+	loop {
+		let mut leaves: Vec<u8> = Vec::new();
+		let mut root_entries: Vec<PmtilesEntry> = Vec::new();
+		for chunk in entries.chunks(leaf_size) {
+			let leaf = serialize_directory(chunk);
+			// run_length 0 marks a leaf directory
+			root_entries.push(PmtilesEntry { tile_id: chunk[0].tile_id, offset: leaves.len() as u64, length: leaf.len() as u32, run_length: 0 });
+			leaves.extend_from_slice(&leaf);
+		}
+		let root = serialize_directory(&root_entries);
+		if root.len() <= PMTILES_MAX_ROOT_LENGTH {
+			return (root, leaves);
+		}
+		leaf_size *= 2;
+	}
+}
+
+// column wise: tile id deltas, run lengths, lengths and offsets (0 = directly after the previous entry)
+pub fn serialize_directory(entries: &[PmtilesEntry]) -> Vec<u8> {
+	let mut buffer: Vec<u8> = Vec::new();
+	write_varint(&mut buffer, entries.len() as u64);
+	let mut last_id = 0;
+	for entry in entries {
+		write_varint(&mut buffer, entry.tile_id - last_id);
+		last_id = entry.tile_id;
+	}
+	for entry in entries {
+		write_varint(&mut buffer, entry.run_length as u64);
+	}
+	for entry in entries {
+		write_varint(&mut buffer, entry.length as u64);
+	}
+	for (index, entry) in entries.iter().enumerate() {
+		if index > 0 && entry.offset == entries[index - 1].offset + entries[index - 1].length as u64 {
+			write_varint(&mut buffer, 0);
+		} else {
+			write_varint(&mut buffer, entry.offset + 1);
+		}
+	}
+	return buffer;
+}
+
+// little endian base 128, like protobuf
+pub(crate) fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+	while value >= 0x80 {
+		buffer.push((value as u8 & 0x7f) | 0x80);
+		value >>= 7;
+	}
+	buffer.push(value as u8);
+}
+
+// position of the tile on the hilbert curve, after all tiles of the lower zoom levels
+pub fn zxy_to_tile_id(tile: TileCoord) -> u64 {
+	let mut id: u64 = ((1u64 << (2 * tile.zoom)) - 1) / 3;
+	let (mut x, mut y) = (tile.x as u64, tile.y as u64);
+	let mut s: u64 = 1u64 << tile.zoom;
+	while s > 1 {
+		s /= 2;
+		let rx = (x & s) > 0;
+		let ry = (y & s) > 0;
+		id += s * s * ((3 * rx as u64) ^ ry as u64);
+		// rotate the quadrant
+		if !ry {
+			if rx {
+				x = s - 1 - (x & (s - 1));
+				y = s - 1 - (y & (s - 1));
+			}
+			std::mem::swap(&mut x, &mut y);
+		}
+	}
+	return id;
+}
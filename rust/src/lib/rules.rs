@@ -0,0 +1,325 @@
+/*
+	Distance rules per Bundesland:
+	Every pixel uses the rules of the Bundesland it lies in. The distances are measured to all geometries
+	of a rule type, also to those on the other side of a border, since the law of the turbine location applies.
+	The distances are expressions of hub height h and rotor radius r (see expression.rs).
+	Output is either:
+	- "mask": 1 if the pixel is buildable, 0 if not, one channel per turbine
+	- "field": normalized margin to the nearest rule, like a distance channel. Negative margin = not buildable.
+	  One channel per turbine
+	- "critical_height": the largest turbine (h + r) that is still allowed, normalized by max_height.
+	  All turbines have the same ratio of rotor radius to total height
+*/
+
+use crate::cli::*;
+use crate::error::{Error, Result};
+use crate::expression::Expression;
+use crate::geoimage::{ChannelInfo, GeoImage};
+use crate::geometry::{Bbox, Collection, DistanceModel, Geometry, Point};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub struct State {
+	pub ags: u32,
+	pub geometry: Geometry,
+	// distance for every rule type, None if the rule type is not used in this state
+	distances: Vec<Option<Expression>>,
+}
+
+pub struct RuleType {
+	pub slug: String,
+	pub filename: PathBuf,
+	// distance in meters per ags
+	pub distances: HashMap<u32, Expression>,
+	pub collection: Collection,
+}
+
+pub enum RulesOutput {
+	Mask,
+	Field { min_distance: f32, max_distance: f32 },
+	CriticalHeight { max_height: f32, rotor_ratio: f32 },
+}
+
+#[derive(Clone, Copy)]
+pub struct Turbine {
+	pub hub_height: f32,
+	pub rotor_radius: f32,
+}
+
+impl Turbine {
+	// turbine with total height h + r, the rotor radius is "rotor_ratio" of the total height
+	fn from_height(height: f32, rotor_ratio: f32) -> Turbine {
+		return Turbine { hub_height: height * (1.0 - rotor_ratio), rotor_radius: height * rotor_ratio };
+	}
+}
+
+pub struct Rules {
+	pub states: Vec<State>,
+	pub rule_types: Vec<RuleType>,
+	pub output: RulesOutput,
+	pub turbines: Vec<Turbine>,
+}
+
+impl Rules {
+	// loads the states and all rule type geometries, that can affect a pixel inside the bbox
+	pub fn load(&mut self, filename_states: &Path, bbox: &Bbox, distance_model: DistanceModel) -> Result<()> {
+		self.states = load_states(filename_states, bbox)?;
+
+		let range = self.field_range();
+		let turbines = self.largest_turbines();
+		for rule_type in self.rule_types.iter_mut() {
+			let max_distance = rule_type.distances.values()
+				.flat_map(|expression| turbines.iter().map(|t| expression.evaluate(t.hub_height, t.rotor_radius)))
+				.fold(0.0f32, f32::max);
+			rule_type.collection.fill_from_file(&rule_type.filename, Some(&bbox.buffered((max_distance + range) as f64)))?;
+			rule_type.collection.set_distance_model(distance_model);
+		}
+
+		for state in self.states.iter_mut() {
+			state.distances = self.rule_types.iter().map(|rule_type| rule_type.distances.get(&state.ags).cloned()).collect();
+		}
+		return Ok(());
+	}
+	pub fn channel_count(&self) -> usize {
+		return match self.output {
+			RulesOutput::CriticalHeight { .. } => 1,
+			_ => self.turbines.len(),
+		};
+	}
+	// one channel per turbine, named after the layer and the turbine
+	pub fn channel_infos(&self, name: &str) -> Vec<ChannelInfo> {
+		let value_range = match self.output {
+			RulesOutput::Mask => [0.0, 1.0],
+			RulesOutput::Field { min_distance, max_distance } => [min_distance, max_distance],
+			RulesOutput::CriticalHeight { max_height, .. } => return vec![ChannelInfo::new(name, [0.0, max_height], 0.0)],
+		};
+		return self.turbines.iter()
+			.map(|turbine| ChannelInfo::new(&format!("{} {}+{}", name, turbine.hub_height, turbine.rotor_radius), value_range, 0.0))
+			.collect();
+	}
+	pub fn draw_to(&self, image: &mut GeoImage, channel_index: usize, threads: usize) {
+		// the first geometries are the states, followed by the rule types
+		let mut geometries:Vec<&Geometry> = self.states.iter().map(|state| &state.geometry).collect();
+		geometries.extend(self.rule_types.iter().map(|rule_type| &rule_type.collection.geometry));
+
+		let state_count = self.states.len();
+		let get_state = |point: &Point, geometries: &[Geometry]| -> Option<&State> {
+			return (0..state_count).find(|i| geometries[*i].contains_point(point)).map(|i| &self.states[i]);
+		};
+
+		if let RulesOutput::CriticalHeight { max_height, rotor_ratio } = self.output {
+			let largest = Turbine::from_height(max_height, rotor_ratio);
+			image.draw_blocks(channel_index, geometries.clone(), threads, |point, geometries| {
+				return match get_state(point, geometries) {
+					Some(state) => self.get_critical_height(point, state, &geometries[state_count..], largest, rotor_ratio) / max_height,
+					None => 0.0,
+				};
+			});
+			return;
+		}
+
+		for (index, turbine) in self.turbines.iter().enumerate() {
+			image.draw_blocks(channel_index + index, geometries.clone(), threads, |point, geometries| {
+				let margin = match get_state(point, geometries) {
+					Some(state) => self.get_margin(point, state, &geometries[state_count..], turbine),
+					None => f32::NEG_INFINITY,
+				};
+
+				return match self.output {
+					RulesOutput::Field { min_distance, max_distance } => {
+						(margin.clamp(min_distance, max_distance)-min_distance)/(max_distance-min_distance)
+					},
+					_ => if margin >= 0.0 { 1.0 } else { 0.0 },
+				};
+			});
+		}
+	}
+	// signed distance to a rule type, limited to max_distance
+	fn get_distance(&self, point: &Point, index: usize, geometries: &[Geometry], max_distance: f32) -> f32 {
+		let distance = self.rule_types[index].collection.get_min_distance(point, max_distance as f64) as f32;
+		return if geometries[index].contains_point(point) { -distance } else { distance };
+	}
+	// smallest difference between the distance to a rule type and its required distance
+	fn get_margin(&self, point: &Point, state: &State, geometries: &[Geometry], turbine: &Turbine) -> f32 {
+		let range = self.field_range();
+		let mut margin = f32::INFINITY;
+		for (index, expression) in state.distances.iter().enumerate() {
+			if let Some(expression) = expression {
+				let distance = expression.evaluate(turbine.hub_height, turbine.rotor_radius);
+				margin = margin.min(self.get_distance(point, index, geometries, distance + range) - distance);
+			}
+		}
+		return margin;
+	}
+	// largest total height, that is allowed at this point, found by bisection.
+	// assumes that the distances of the rules are growing with the turbine size
+	fn get_critical_height(&self, point: &Point, state: &State, geometries: &[Geometry], largest: Turbine, rotor_ratio: f32) -> f32 {
+		let mut rules:Vec<(&Expression, f32)> = Vec::new();
+		for (index, expression) in state.distances.iter().enumerate() {
+			if let Some(expression) = expression {
+				let max_distance = expression.evaluate(largest.hub_height, largest.rotor_radius);
+				rules.push((expression, self.get_distance(point, index, geometries, max_distance)));
+			}
+		}
+
+		let is_allowed = |height: f32| -> bool {
+			let turbine = Turbine::from_height(height, rotor_ratio);
+			return rules.iter().all(|(expression, distance)| *distance >= expression.evaluate(turbine.hub_height, turbine.rotor_radius));
+		};
+
+		let max_height = largest.hub_height + largest.rotor_radius;
+		if is_allowed(max_height) {
+			return max_height;
+		}
+		if !is_allowed(0.0) {
+			return 0.0;
+		}
+
+		let mut lower = 0.0f32;
+		let mut upper = max_height;
+		for _ in 0..16 {
+			let height = (lower + upper) / 2.0;
+			if is_allowed(height) {
+				lower = height;
+			} else {
+				upper = height;
+			}
+		}
+		return lower;
+	}
+	// how far beyond the rule distances the field has to be calculated
+	fn field_range(&self) -> f32 {
+		return match self.output {
+			RulesOutput::Field { max_distance, .. } => max_distance.max(0.0),
+			_ => 0.0,
+		};
+	}
+	// the turbines, that define how far the geometries have an effect
+	fn largest_turbines(&self) -> Vec<Turbine> {
+		return match self.output {
+			RulesOutput::CriticalHeight { max_height, rotor_ratio } => vec![Turbine::from_height(max_height, rotor_ratio)],
+			_ => self.turbines.clone(),
+		};
+	}
+}
+
+// reads the land areas (GF = 4) of bundeslaender.geojson, one geometry per state
+fn load_states(filename: &Path, bbox: &Bbox) -> Result<Vec<State>> {
+	let contents = fs::read_to_string(filename).map_err(|e| Error::io(filename, e))?;
+	let data = json::parse(&contents).map_err(|e| Error::format(filename, e))?;
+
+	let mut states:Vec<State> = Vec::new();
+	for feature in data["features"].members() {
+		let properties = &feature["properties"];
+		if properties["GF"].as_u32() != Some(4) {
+			continue;
+		}
+
+		let ags:u32 = match properties["AGS"].as_str().and_then(|ags| ags.parse().ok()) {
+			Some(ags) => ags,
+			None => return Err(Error::format(filename, format!("invalid AGS: {}", properties["AGS"]))),
+		};
+		let index = match states.iter().position(|state| state.ags == ags) {
+			Some(index) => index,
+			None => {
+				states.push(State { ags, geometry: Geometry::new(), distances: Vec::new() });
+				states.len() - 1
+			}
+		};
+		states[index].geometry.add_json_geometry(&feature["geometry"]).map_err(|e| Error::format(filename, e))?;
+	}
+
+	for state in states.iter_mut() {
+		state.geometry.retain_bbox(bbox);
+	}
+
+	return Ok(states);
+}
+
+// parses a rules layer:
+// { "type": "rules", "filename": "bundeslaender.geojson", "output": "mask", "turbines": [{ "hub_height": 142, "rotor_radius": 58 }, …], "rules": […] }
+// { "type": "rules", "filename": "bundeslaender.geojson", "output": "field", "min_distance": -500, "max_distance": 500, "turbines": […], "rules": "rules.json" }
+// { "type": "rules", "filename": "bundeslaender.geojson", "output": "critical_height", "max_height": 300, "rotor_ratio": 0.29, "rules": "rules.json" }
+// "rules" is a list or the filename of a JSON file containing the list. distances are numbers or expressions:
+// [{ "slug": "wohngebaeude", "filename": "wohngebaeude.gpkg", "distances": { "1": 400, "9": "10 * (h + r)", … } }, …]
+pub fn parse_rules(entry: &json::JsonValue) -> Result<Rules> {
+	let output = match entry["output"].as_str().unwrap_or("mask") {
+		"mask" => RulesOutput::Mask,
+		"field" => {
+			let min_distance = get_f32(entry, "min_distance")?;
+			let max_distance = get_f32(entry, "max_distance")?;
+			check_min_max("min_distance", min_distance, "max_distance", max_distance)?;
+			RulesOutput::Field { min_distance, max_distance }
+		},
+		"critical_height" => {
+			let max_height = get_f32(entry, "max_height")?;
+			let rotor_ratio = get_f32(entry, "rotor_ratio")?;
+			if max_height <= 0.0 {
+				return Err(Error::Invalid(format!("\"max_height\" must be positive, found {}", max_height)));
+			}
+			if !(0.0..1.0).contains(&rotor_ratio) {
+				return Err(Error::Invalid(format!("\"rotor_ratio\" must be between 0 and 1, found {}", rotor_ratio)));
+			}
+			RulesOutput::CriticalHeight { max_height, rotor_ratio }
+		},
+		output => return Err(Error::Invalid(format!("unknown rules output \"{}\", expected mask, field or critical_height", output))),
+	};
+
+	let mut turbines:Vec<Turbine> = Vec::new();
+	for (index, turbine) in entry["turbines"].members().enumerate() {
+		let context = format!("turbines[{}]", index);
+		check_keys(turbine, &["hub_height", "rotor_radius"]).map_err(|e| e.context(&context))?;
+		turbines.push(Turbine {
+			hub_height: get_f32(turbine, "hub_height").map_err(|e| e.context(&context))?,
+			rotor_radius: get_f32(turbine, "rotor_radius").map_err(|e| e.context(&context))?,
+		});
+	}
+
+	if turbines.is_empty() && !matches!(output, RulesOutput::CriticalHeight { .. }) {
+		return Err(Error::Invalid("rules layer needs at least one turbine".to_string()));
+	}
+
+	let table = match entry["rules"].as_str() {
+		Some(filename) => {
+			let content = fs::read_to_string(filename)
+				.map_err(|e| Error::Invalid(format!("can not read rules file \"{}\": {}", filename, e)))?;
+			json::parse(&content).map_err(|e| Error::Invalid(format!("rules file \"{}\": {}", filename, e)))?
+		},
+		None => entry["rules"].clone(),
+	};
+	if !table.is_array() || table.is_empty() {
+		return Err(Error::Invalid("\"rules\" must be a non empty list or the filename of one".to_string()));
+	}
+
+	let mut rule_types:Vec<RuleType> = Vec::new();
+	for (index, rule) in table.members().enumerate() {
+		let context = format!("rules[{}]", index);
+		let rule_type = parse_rule_type(rule).map_err(|e| e.context(&context))?;
+		rule_types.push(rule_type);
+	}
+
+	return Ok(Rules { states: Vec::new(), rule_types, output, turbines });
+}
+
+fn parse_rule_type(rule: &json::JsonValue) -> Result<RuleType> {
+	check_keys(rule, &["slug", "filename", "distances"])?;
+	let filename = PathBuf::from(get_str(rule, "filename")?);
+	check_file("filename", &filename)?;
+
+	let mut distances:HashMap<u32, Expression> = HashMap::new();
+	for (ags, distance) in rule["distances"].entries() {
+		let ags_number:u32 = ags.parse()
+			.map_err(|_| Error::Invalid(format!("\"distances\": \"{}\" is not an AGS number", ags)))?;
+		let expression = Expression::from_json(distance)
+			.map_err(|e| Error::Invalid(format!("\"distances\".{}: {}", ags, e)))?;
+		distances.insert(ags_number, expression);
+	}
+
+	return Ok(RuleType {
+		slug: get_str(rule, "slug")?,
+		filename,
+		distances,
+		collection: Collection::new(),
+	});
+}
@@ -0,0 +1,242 @@
+/*
+	Buildable area per region (Bundesland or municipality) for a sweep of residential distances:
+	1. load the land areas (GF = 4) of a VG250 GeoJSON, e.g. bundeslaender.geojson, one region per AGS
+	2. for every bin tile of a zoom level and every region overlapping it:
+		2.1. draw the share of every pixel inside the region (see GeoImage::draw_geometry)
+		2.2. weight it with the area of the pixel (see GeoImage::pixel_area), so the stretching of web mercator is corrected
+		2.3. a pixel is buildable at a distance, if the distance channel is at least this distance
+		     and no excluded channel (e.g. coverage of nature reserves) covers it: a share of at least 0.5,
+		     or for coverage layers with coverage_distance a signed distance of at most 0
+	Missing tiles are uniform default, like in merge (see skip_uniform_tiles).
+*/
+
+use crate::error::{Error, Result};
+use crate::geoimage::{ChannelInfo, GeoImage};
+use crate::geometry::{Bbox, Collection, Geometry};
+use crate::tiles::{list_tiles, run_queue, tile_bbox, TileCoord};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub struct Region {
+	pub ags: String,
+	pub name: String,
+	pub collection: Collection,
+	bbox: Bbox,
+}
+
+pub struct Sweep {
+	// the distance channel, e.g. residential buildings
+	pub channel: usize,
+	// in meters, inside the value range of the channel
+	pub distances: Vec<f32>,
+	// coverage channels, that are not buildable
+	pub exclude_channels: Vec<usize>,
+}
+
+// how an excluded channel covers a pixel
+enum Exclusion {
+	// share of the pixel in [0, 1]
+	Share { channel: usize },
+	// normalized signed distance, the pixel value of the distance 0
+	Inside { channel: usize, threshold: f32 },
+}
+
+impl Exclusion {
+	fn covers(&self, image: &GeoImage, index: usize) -> bool {
+		return match self {
+			Exclusion::Share { channel } => image.channel_data(*channel)[index] >= 0.5,
+			Exclusion::Inside { channel, threshold } => image.channel_data(*channel)[index] <= *threshold,
+		};
+	}
+}
+
+#[derive(Clone)]
+pub struct RegionArea {
+	pub ags: String,
+	pub name: String,
+	// in km²
+	pub area: f64,
+	// in km², one per distance of the sweep
+	pub buildable: Vec<f64>,
+}
+
+pub struct AreaStatistics {
+	pub regions: Vec<RegionArea>,
+	pub tiles: usize,
+	pub missing_tiles: usize,
+}
+
+// reads the land areas (GF = 4), features with the same AGS are one region
+pub fn load_regions(filename: &Path) -> Result<Vec<Region>> {
+	let contents = fs::read_to_string(filename).map_err(|e| Error::io(filename, e))?;
+	let data = json::parse(&contents).map_err(|e| Error::format(filename, e))?;
+
+	let mut regions:Vec<(String, String, Geometry)> = Vec::new();
+	for feature in data["features"].members() {
+		let properties = &feature["properties"];
+		if properties["GF"].as_u32() != Some(4) {
+			continue;
+		}
+		let ags = match properties["AGS"].as_str() {
+			Some(ags) => ags.to_string(),
+			None => return Err(Error::format(filename, format!("invalid AGS: {}", properties["AGS"]))),
+		};
+		let index = match regions.iter().position(|region| region.0 == ags) {
+			Some(index) => index,
+			None => {
+				let name = properties["GEN"].as_str().unwrap_or("").to_string();
+				regions.push((ags, name, Geometry::new()));
+				regions.len() - 1
+			}
+		};
+		regions[index].2.add_json_geometry(&feature["geometry"]).map_err(|e| Error::format(filename, e))?;
+	}
+	if regions.is_empty() {
+		return Err(Error::format(filename, "no land areas (GF = 4) found"));
+	}
+
+	return Ok(regions.into_iter().map(|(ags, name, geometry)| {
+		let bbox = geometry.bbox();
+		return Region { ags, name, collection: Collection::from_geometry(geometry), bbox };
+	}).collect());
+}
+
+impl Sweep {
+	// the normalized pixel values of the distances
+	fn thresholds(&self, infos: &[ChannelInfo]) -> Result<Vec<f32>> {
+		let info = infos.get(self.channel).ok_or_else(|| {
+			return Error::Invalid(format!("channel {} does not exist, the tiles have {} channels", self.channel, infos.len()));
+		})?;
+		let [min, max] = info.value_range;
+		return self.distances.iter().map(|distance| {
+			if (*distance <= min) || (*distance > max) {
+				return Err(Error::Invalid(format!("distance {} is outside of the value range [{}, {}] of channel \"{}\"", distance, min, max, info.name)));
+			}
+			return Ok((distance - min) / (max - min));
+		}).collect();
+	}
+	// coverages have the value range [0, 1], coverage layers with coverage_distance a range of distances
+	fn exclusions(&self, infos: &[ChannelInfo]) -> Result<Vec<Exclusion>> {
+		return self.exclude_channels.iter().map(|channel| {
+			let info = infos.get(*channel).ok_or_else(|| {
+				return Error::Invalid(format!("excluded channel {} does not exist, the tiles have {} channels", channel, infos.len()));
+			})?;
+			let [min, max] = info.value_range;
+			if [min, max] == [0.0, 1.0] {
+				return Ok(Exclusion::Share { channel: *channel });
+			}
+			if (min > 0.0) || (max <= 0.0) {
+				return Err(Error::Invalid(format!("excluded channel \"{}\" is neither a coverage nor a signed distance, its value range is [{}, {}]", info.name, min, max)));
+			}
+			return Ok(Exclusion::Inside { channel: *channel, threshold: -min / (max - min) });
+		}).collect();
+	}
+}
+
+impl GeoImage {
+	// adds the areas of this tile to the areas of the regions
+	pub fn add_buildable_areas(&self, regions: &[Region], sweep: &Sweep, areas: &mut [RegionArea]) -> Result<()> {
+		let thresholds = sweep.thresholds(&self.channel_infos())?;
+		let exclusions = sweep.exclusions(&self.channel_infos())?;
+		let size = self.size as usize;
+		let data = self.channel_data(sweep.channel);
+		let excluded:Vec<bool> = (0..size * size).map(|i| {
+			return exclusions.iter().any(|exclusion| exclusion.covers(self, i));
+		}).collect();
+		let bbox = tile_bbox(self.tile_coord());
+
+		for (region, area) in regions.iter().zip(areas.iter_mut()) {
+			if !region.bbox.overlaps_bbox(&bbox) {
+				continue;
+			}
+			let mut share = GeoImage::with_channels(self.size, self.zoom, self.x_offset, self.y_offset, &[ChannelInfo::new("region", [0.0, 1.0], 0.0)]);
+			share.draw_geometry(0, &region.collection);
+			let share = share.channel_data(0);
+
+			for y in 0..size {
+				// km²
+				let pixel_area = self.pixel_area(y as u32) / 1.0e6;
+				for i in y * size..(y + 1) * size {
+					if share[i] == 0.0 {
+						continue;
+					}
+					let weight = (share[i] as f64) * pixel_area;
+					area.area += weight;
+					if excluded[i] {
+						continue;
+					}
+					for (buildable, threshold) in area.buildable.iter_mut().zip(&thresholds) {
+						if data[i] >= *threshold {
+							*buildable += weight;
+						}
+					}
+				}
+			}
+		}
+		return Ok(());
+	}
+}
+
+// walks all bin tiles of a zoom level, that cover the regions
+pub fn area_statistics(folder_bin: &Path, zoom: u32, regions: &[Region], sweep: &Sweep, threads: usize) -> Result<AreaStatistics> {
+	let mut bbox:[f64; 4] = [180.0, 90.0, -180.0, -90.0];
+	for region in regions {
+		bbox = [bbox[0].min(region.bbox.x_min), bbox[1].min(region.bbox.y_min), bbox[2].max(region.bbox.x_max), bbox[3].max(region.bbox.y_max)];
+	}
+	let tiles = list_tiles(&bbox, zoom);
+	let path = |tile: &TileCoord| GeoImage::calc_path(folder_bin, tile.zoom, tile.y, tile.x, ".bin");
+
+	// missing tiles get the channels of the first one
+	let first = match tiles.iter().map(path).find(|path| path.is_file()) {
+		Some(path) => GeoImage::load(&path)?,
+		None => return Err(Error::Invalid(format!("no bin tiles of zoom level {} in {}", zoom, folder_bin.display()))),
+	};
+	let infos = first.channel_infos();
+	sweep.thresholds(&infos)?;
+	sweep.exclusions(&infos)?;
+
+	let empty:Vec<RegionArea> = regions.iter().map(|region| RegionArea {
+		ags: region.ags.clone(),
+		name: region.name.clone(),
+		area: 0.0,
+		buildable: vec![0.0; sweep.distances.len()],
+	}).collect();
+	let result = Mutex::new((empty.clone(), 0usize));
+	let first_error: Mutex<Option<Error>> = Mutex::new(None);
+
+	run_queue(&tiles, threads, |_index, tile| {
+		if first_error.lock().unwrap().is_some() {
+			return;
+		}
+		let path = path(tile);
+		let missing = !path.is_file();
+		let image = if missing {
+			Ok(GeoImage::with_channels(first.size, tile.zoom, tile.x, tile.y, &infos))
+		} else {
+			GeoImage::load(&path)
+		};
+		let mut areas = empty.clone();
+		if let Err(error) = image.and_then(|image| image.add_buildable_areas(regions, sweep, &mut areas)) {
+			let _error = first_error.lock().unwrap().get_or_insert(error);
+			return;
+		}
+
+		let mut result = result.lock().unwrap();
+		for (sum, area) in result.0.iter_mut().zip(areas) {
+			sum.area += area.area;
+			for (sum, buildable) in sum.buildable.iter_mut().zip(area.buildable) {
+				*sum += buildable;
+			}
+		}
+		if missing {
+			result.1 += 1;
+		}
+	});
+
+	if let Some(error) = first_error.into_inner().unwrap() {
+		return Err(error);
+	}
+	let (regions, missing_tiles) = result.into_inner().unwrap();
+	return Ok(AreaStatistics { regions, tiles: tiles.len(), missing_tiles });
+}
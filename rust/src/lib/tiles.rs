@@ -0,0 +1,217 @@
+use crate::bintile::Compression;
+use crate::edt::Renderer;
+use crate::encoding::PixelEncoding;
+use crate::error::Result;
+use crate::geoimage::*;
+use crate::geometry::*;
+use crate::layers::*;
+use crate::output::TileOutput;
+use std::f64::consts::PI;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+pub struct TileOptions {
+	// folder, .mbtiles or .pmtiles file, see output.rs
+	pub output: PathBuf,
+	pub folder_bin: PathBuf,
+	pub size: u32,
+	pub threads: usize,
+	// format of the tiles in output and how the channels are saved as pixels
+	pub image_format: TileFormat,
+	pub pixel_encoding: PixelEncoding,
+	pub bin_compression: Compression,
+	// tiles, that are uniform default (see GeoImage::is_uniform_default), are neither saved as image nor as bin.
+	// merge_tile treats them like all other missing tiles
+	pub skip_uniform_tiles: bool,
+	// how the lower zoom levels and the bin thumbnails are calculated
+	pub downsampling: Downsampling,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TileCoord {
+	pub zoom: u32,
+	pub x: u32,
+	pub y: u32,
+}
+
+// renders a block of n×n tiles with the channels of all layers and saves the image tiles of all zoom levels down to "zoom",
+// plus a half sized thumbnail as bin, that can be used by merge_tile.
+// the bin is saved first: pyramid counts a tile as done, when its image exists, so an interrupted run never lacks the bin
+pub fn render_tile(layers: &[Layer], options: &TileOptions, output: &dyn TileOutput, tile: TileCoord, n: u32) -> Result<()> {
+	let image = render_image(layers, tile, options.size * n, options.threads);
+
+	let thumb = scale_down(layers, options, &image, options.size / 2);
+	if !(options.skip_uniform_tiles && thumb.is_uniform_default()) {
+		thumb.export_to(&options.folder_bin, &TileFormat::Bin(options.bin_compression))?;
+	}
+
+	if options.downsampling == Downsampling::Box {
+		image.export_tile_tree(options.size, output, options.skip_uniform_tiles)?;
+	} else {
+		image.export_tile_tree_with(options.size, output, options.skip_uniform_tiles, |image, size| {
+			return scale_down(layers, options, image, size);
+		})?;
+	}
+	return Ok(());
+}
+
+// the image of a tile with the channels of all layers
+pub fn render_image(layers: &[Layer], tile: TileCoord, size: u32, threads: usize) -> GeoImage {
+	let infos:Vec<ChannelInfo> = layers.iter().flat_map(|layer| layer.channel_infos()).collect();
+	let mut image = GeoImage::with_channels(size, tile.zoom, tile.x, tile.y, &infos);
+
+	let mut index = 0;
+	for layer in layers {
+		match &layer.kind {
+			LayerKind::Distance { min_distance, max_distance } => match layer.renderer {
+				Renderer::Tree => image.draw_distances(index, &layer.collection, *min_distance, *max_distance, threads),
+				Renderer::Edt { oversampling } => image.draw_distances_edt(index, &layer.collection, *min_distance, *max_distance, oversampling, threads),
+			},
+			LayerKind::Coverage => {
+				image.draw_geometry(index, &layer.collection);
+			},
+			LayerKind::Rules(rules) => {
+				rules.draw_to(&mut image, index, threads);
+			},
+		}
+		index += layer.channel_count();
+	}
+	return image;
+}
+
+// the image with fewer pixels, see Downsampling
+fn scale_down(layers: &[Layer], options: &TileOptions, image: &GeoImage, new_size: u32) -> GeoImage {
+	return match options.downsampling {
+		Downsampling::Recompute => render_image(layers, image.tile_coord(), new_size, options.threads),
+		downsampling => {
+			let downsamplings: Vec<Downsampling> = layers.iter().flat_map(|layer| layer.channel_downsampling(downsampling)).collect();
+			image.scaled_down_channels(new_size, &downsamplings)
+		},
+	};
+}
+
+// merges the 4 bin thumbnails of the next zoom level into one tile, missing ones are uniform default.
+// with Downsampling::Recompute the tile is rendered from the layers instead
+pub fn merge_tile(layers: &[Layer], options: &TileOptions, output: &dyn TileOutput, tile: TileCoord) -> Result<()> {
+	let image = if options.downsampling == Downsampling::Recompute {
+		render_image(layers, tile, options.size, options.threads)
+	} else {
+		match merge_thumbnails(layers, options, tile)? {
+			Some(image) => image,
+			None => return Ok(()),
+		}
+	};
+	if options.skip_uniform_tiles && image.is_uniform_default() {
+		return Ok(());
+	}
+
+	// the bin first, like in render_tile
+	let thumb = scale_down(layers, options, &image, image.size/2);
+	thumb.export_to(&options.folder_bin, &TileFormat::Bin(options.bin_compression))?;
+	return output.write_image(&image);
+}
+
+// None, if all are missing and skip_uniform_tiles is set
+fn merge_thumbnails(layers: &[Layer], options: &TileOptions, tile: TileCoord) -> Result<Option<GeoImage>> {
+	let x = tile.x*2;
+	let y = tile.y*2;
+	let z = tile.zoom+1;
+
+	let mut images:[Option<GeoImage>;4] = [None,None,None,None];
+
+	for item in LAYOUT {
+		let path_buf = GeoImage::calc_path(&options.folder_bin, z, y + item.y, x + item.x, ".bin");
+		let path = path_buf.as_path();
+		if path.is_file() {
+			let _image = images[item.index].insert(GeoImage::load(path)?);
+		}
+	}
+	if options.skip_uniform_tiles && images.iter().all(|image| image.is_none()) {
+		return Ok(None);
+	}
+	let infos:Vec<ChannelInfo> = layers.iter().flat_map(|layer| layer.channel_infos()).collect();
+	return Ok(Some(GeoImage::merge(images, &infos, options.size, tile.zoom, tile.x, tile.y)?));
+}
+
+// geographic bbox of a tile
+pub fn tile_bbox(tile: TileCoord) -> Bbox {
+	let scale = 2.0_f64.powi(tile.zoom as i32);
+	return Bbox::from_coordinates(
+		demercator_x(tile.x as f64 / scale),
+		demercator_y((tile.y + 1) as f64 / scale),
+		demercator_x((tile.x + 1) as f64 / scale),
+		demercator_y(tile.y as f64 / scale),
+	);
+}
+
+// same as bbox2Tiles in lib/geohelper.js: [x_min, y_min, x_max, y_max], max values are exclusive
+pub fn bbox_to_tiles(bbox: &[f64; 4], zoom: u32) -> [u32; 4] {
+	let scale = 2.0_f64.powi(zoom as i32);
+	return [
+		(mercator_x(bbox[0]) * scale).floor() as u32,
+		(mercator_y(bbox[3]) * scale).floor() as u32,
+		(mercator_x(bbox[2]) * scale).ceil() as u32,
+		(mercator_y(bbox[1]) * scale).ceil() as u32,
+	];
+}
+
+// all tiles of a zoom level inside the bbox, the center tiles first
+pub fn list_tiles(bbox: &[f64; 4], zoom: u32) -> Vec<TileCoord> {
+	let [x_min, y_min, x_max, y_max] = bbox_to_tiles(bbox, zoom);
+	let xc = (x_min + x_max) as f64 / 2.0;
+	let yc = (y_min + y_max) as f64 / 2.0;
+
+	let mut tiles:Vec<TileCoord> = Vec::new();
+	for y in y_min..y_max {
+		for x in x_min..x_max {
+			tiles.push(TileCoord { zoom, x, y });
+		}
+	}
+
+	let order = |tile: &TileCoord| -> f64 { (tile.x as f64 - xc).powi(2) + (tile.y as f64 - yc).powi(2) };
+	tiles.sort_by(|a, b| order(a).total_cmp(&order(b)));
+
+	return tiles;
+}
+
+// a simple work queue: every thread takes the next todo, until all are done
+pub fn run_queue<T, F>(todos: &[T], threads: usize, callback: F) where T: Sync, F: Fn(usize, &T) + Sync {
+	let threads = if threads > 0 {
+		threads
+	} else {
+		thread::available_parallelism().map_or(1, |n| n.get())
+	};
+
+	let next_todo = AtomicUsize::new(0);
+
+	thread::scope(|scope| {
+		for _ in 0..threads.min(todos.len()) {
+			scope.spawn(|| {
+				loop {
+					let index = next_todo.fetch_add(1, Ordering::Relaxed);
+					if index >= todos.len() {
+						break;
+					}
+					callback(index, &todos[index]);
+				}
+			});
+		}
+	});
+}
+
+fn mercator_x(x: f64) -> f64 {
+	return (x + 180.0) / 360.0;
+}
+
+fn mercator_y(y: f64) -> f64 {
+	return 0.5 * (1.0 - (PI * (1.0 + y / 90.0) / 4.0).tan().ln() / PI);
+}
+
+fn demercator_x(x: f64) -> f64 {
+	return x * 360.0 - 180.0;
+}
+
+fn demercator_y(y: f64) -> f64 {
+	return (((1.0 - y * 2.0) * PI).exp().atan() * 4.0 / PI - 1.0) * 90.0;
+}
@@ -0,0 +1,16 @@
+/*
+	Same as "windradabstand pyramid", kept for the Node scripts:
+	pyramid '{"layers":[…],"folder_png":"…","bbox":[5.8,47.2,15.1,55.1],"max_zoom":14,…}'
+	See lib/commands.rs
+*/
+
+use std::env;
+use std::process;
+
+use windradabstand::commands::run;
+
+fn main() {
+	let mut args: Vec<String> = env::args().skip(1).collect();
+	args.insert(0, "pyramid".to_string());
+	process::exit(run(&args));
+}
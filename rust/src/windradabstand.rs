@@ -0,0 +1,16 @@
+/*
+	Renders the distance tiles, see lib/commands.rs for the commands and lib/cli.rs for the arguments:
+	windradabstand render '{"layers":[…],"zoom":9,…}'
+	windradabstand pyramid --config pyramid.json --threads 4
+	windradabstand inspect --filename tiles/bin/9/167/275.bin
+*/
+
+use std::env;
+use std::process;
+
+use windradabstand::commands::run;
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	process::exit(run(&args));
+}
@@ -0,0 +1,165 @@
+/*
+	File format of the bin tiles: round trip with all compressions, migration of old versions and refusal of unknown versions.
+*/
+
+use windradabstand::bintile::{decode, encode, Compression, MAX_SIZE, VERSION};
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point};
+
+mod common;
+
+fn test_image() -> GeoImage {
+	return common::test_image(4, &[
+		ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0e6),
+		ChannelInfo::new("naturschutz", [0.0, 1.0], 0.0),
+		ChannelInfo::new("", [0.0, 300.0], 0.0),
+	]);
+}
+
+// bincode dump of the old GeoImage: size, zoom, x, y, 3 coordinates as f32 or f64 and the channels
+fn version_0(coordinates_f64: bool, fill_values: Option<&[f32]>, channels: &[Vec<f32>]) -> Vec<u8> {
+	let mut buffer: Vec<u8> = Vec::new();
+	for value in [4u32, 9, 275, 167] {
+		buffer.extend_from_slice(&value.to_le_bytes());
+	}
+	for value in [0.537f64, 0.326, 0.00049] {
+		if coordinates_f64 {
+			buffer.extend_from_slice(&value.to_le_bytes());
+		} else {
+			buffer.extend_from_slice(&(value as f32).to_le_bytes());
+		}
+	}
+	buffer.extend_from_slice(&(channels.len() as u64).to_le_bytes());
+	for (index, data) in channels.iter().enumerate() {
+		buffer.extend_from_slice(&4u32.to_le_bytes());
+		buffer.extend_from_slice(&4u32.to_le_bytes());
+		if let Some(fill_values) = fill_values {
+			buffer.extend_from_slice(&fill_values[index].to_le_bytes());
+		}
+		buffer.extend_from_slice(&(data.len() as u64).to_le_bytes());
+		for value in data {
+			buffer.extend_from_slice(&value.to_le_bytes());
+		}
+	}
+	return buffer;
+}
+
+#[test]
+fn round_trip() {
+	let image = test_image();
+	for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+		let buffer = encode(&image, compression);
+		// without curves the tile stays readable for version 2
+		assert_eq!(&buffer[0..8], b"WRADTILE");
+		assert_eq!(u16::from_le_bytes([buffer[8], buffer[9]]), 2);
+
+		let (decoded, file_info) = decode(&buffer).unwrap();
+		assert_eq!(file_info.version, 2);
+		assert_eq!(file_info.compression, compression);
+		assert_eq!(decoded, image);
+		assert_eq!(decoded.channel_infos()[0], ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0e6));
+	}
+}
+
+// a distance field, that is limited to max_distance in most of the tile, has to get much smaller
+#[test]
+fn compression_ratio() {
+	let mut image = GeoImage::with_channels(256, 9, 275, 167, &[ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0e6)]);
+	let mut geometry = Geometry::new();
+	geometry.add_line(vec![Point::new(13.5, 52.6), Point::new(13.9, 52.8)]);
+	image.draw_distances(0, &Collection::from_geometry(geometry), -500.0, 2000.0, 1);
+
+	let uncompressed = encode(&image, Compression::None).len();
+	for compression in [Compression::Lz4, Compression::Zstd] {
+		let compressed = encode(&image, compression).len();
+		assert!(compressed * 2 < uncompressed, "{:?}: {} of {} bytes", compression, compressed, uncompressed);
+	}
+}
+
+// version 1 has no compression and no data length
+#[test]
+fn read_version_1() {
+	let image = test_image();
+	let buffer = encode(&image, Compression::None);
+	let header_length = buffer.len() - 9 - image.channel_count() * 16 * 4;
+	let mut buffer_1 = [&buffer[..header_length], &buffer[header_length + 9..]].concat();
+	buffer_1[8..10].copy_from_slice(&1u16.to_le_bytes());
+
+	let (decoded, file_info) = decode(&buffer_1).unwrap();
+	assert_eq!(file_info.version, 1);
+	assert_eq!(decoded, image);
+}
+
+#[test]
+fn migrate_version_0() {
+	let dyn_data: Vec<f32> = (0..16).map(|i| i as f32 / 16.0).collect();
+	let fix_data: Vec<f32> = (0..16).map(|i| (i % 2) as f32).collect();
+	let extra_data: Vec<f32> = vec![0.25; 16];
+
+	// first layout: always "dyn" and "fix", without fill values
+	let (image, file_info) = decode(&version_0(false, None, &[dyn_data.clone(), fix_data.clone()])).unwrap();
+	assert_eq!(file_info.version, 0);
+	assert_eq!(image.channel_infos(), vec![
+		ChannelInfo::new("dyn", [0.0, 1.0], 1.0e6),
+		ChannelInfo::new("fix", [0.0, 1.0], 0.0),
+	]);
+	assert_eq!(image.channel_data(0), dyn_data.as_slice());
+	assert_eq!(image.channel_data(1), fix_data.as_slice());
+	let (reloaded, _file_info) = decode(&encode(&image, Compression::Zstd)).unwrap();
+	assert_eq!(reloaded, image);
+	// and merges like a current tile, the missing quadrants get the fill values
+	let infos = image.channel_infos();
+	let merged = GeoImage::merge([None, None, None, Some(image)], &infos, 8, 8, 137, 83).unwrap();
+	assert_eq!(merged.channel_data(0)[0], 1.0e6);
+	assert_eq!(merged.channel_data(0)[63], dyn_data[15]);
+
+	// fill values per channel, coordinates as f32 and as f64
+	for coordinates_f64 in [false, true] {
+		let fill_values = [1.0e6, 0.0, 0.5];
+		let buffer = version_0(coordinates_f64, Some(&fill_values), &[dyn_data.clone(), fix_data.clone(), extra_data.clone()]);
+		let (image, file_info) = decode(&buffer).unwrap();
+		assert_eq!(file_info.version, 0);
+		let infos = image.channel_infos();
+		assert_eq!(infos.len(), 3);
+		assert_eq!(infos.iter().map(|info| info.fill_value).collect::<Vec<f32>>(), fill_values);
+		assert_eq!(image.channel_data(2), extra_data.as_slice());
+
+		let (decoded, _file_info) = decode(&encode(&image, Compression::Lz4)).unwrap();
+		assert_eq!(decoded, image);
+	}
+}
+
+#[test]
+fn refuse_broken_tiles() {
+	let buffer = encode(&test_image(), Compression::None);
+
+	let mut newer = buffer.clone();
+	newer[8..10].copy_from_slice(&(VERSION + 1).to_le_bytes());
+	let error = decode(&newer).unwrap_err();
+	assert!(error.contains("unsupported bin tile version"), "{}", error);
+
+	assert!(decode(&buffer[..buffer.len() - 1]).is_err());
+	assert!(decode(&[buffer.as_slice(), &[0]].concat()).is_err());
+	assert!(decode(b"garbage").is_err());
+
+	// broken compressed data
+	let mut broken = encode(&test_image(), Compression::Zstd);
+	let length = broken.len();
+	broken[length - 4..].copy_from_slice(&[0xff; 4]);
+	assert!(decode(&broken).is_err());
+
+	// a size in the header, that the pixel data can not fill, is refused before allocating the image
+	for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+		let mut large = encode(&test_image(), compression);
+		large[22..26].copy_from_slice(&MAX_SIZE.to_le_bytes());
+		let error = decode(&large).unwrap_err();
+		assert!(error.contains("can not hold"), "{:?}: {}", compression, error);
+	}
+	let mut huge = buffer.clone();
+	huge[22..26].copy_from_slice(&(MAX_SIZE * 2).to_le_bytes());
+	let error = decode(&huge).unwrap_err();
+	assert!(error.contains("invalid tile size"), "{}", error);
+
+	// a version 0 tile with channels, that do not fit the size
+	let error = decode(&version_0(true, Some(&[0.0]), &[vec![0.0; 15]])).unwrap_err();
+	assert!(error.contains("pixels"), "{}", error);
+}
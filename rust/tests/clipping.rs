@@ -0,0 +1,133 @@
+/*
+	Clipping of polygons with clone_cut_top/bot/lef/rig:
+	on the kept side of a cut, contains_point must give the same result as before the cut,
+	on the removed side it must always be false.
+*/
+
+use windradabstand::geometry::*;
+
+// deterministic pseudo random numbers in 0..1
+struct Random {
+	state: u64,
+}
+
+impl Random {
+	fn next(&mut self) -> f64 {
+		self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+		return (self.state >> 11) as f64 / (1u64 << 53) as f64;
+	}
+}
+
+// closed star shaped ring around (cx, cy), with a random radius between r_min and r_max per vertex
+fn star(random: &mut Random, cx: f64, cy: f64, r_min: f64, r_max: f64, count: usize) -> Vec<Point> {
+	let mut points: Vec<Point> = (0..count).map(|i| {
+		let angle = i as f64 / count as f64 * std::f64::consts::TAU;
+		let radius = r_min + (r_max - r_min) * random.next();
+		return Point::new(cx + radius * angle.cos(), cy + radius * angle.sin());
+	}).collect();
+	points.push(points[0]);
+	return points;
+}
+
+// non convex polygons with holes, some overlapping each other
+fn test_geometry() -> Geometry {
+	let mut random = Random { state: 42 };
+	let mut geometry = Geometry::new();
+	for _ in 0..6 {
+		let cx = 10.0 + random.next();
+		let cy = 51.0 + random.next();
+		let outer = star(&mut random, cx, cy, 0.2, 0.5, 40);
+		let hole = star(&mut random, cx, cy, 0.05, 0.15, 12);
+		geometry.add_polygon(vec![outer, hole]);
+	}
+	return geometry;
+}
+
+// grid of points around the geometry, shifted so that no point is on a cut line
+fn test_points() -> Vec<Point> {
+	let mut points: Vec<Point> = Vec::new();
+	for i in 0..120 {
+		for j in 0..120 {
+			points.push(Point::new(9.4 + (i as f64 + 0.37) * 0.02, 50.4 + (j as f64 + 0.61) * 0.02));
+		}
+	}
+	return points;
+}
+
+#[test]
+fn cut_keeps_inside_outside() {
+	let geometry = test_geometry();
+	let points = test_points();
+
+	for value in [50.7, 51.0, 51.3, 51.6] {
+		let top = geometry.clone_cut_top(value);
+		let bot = geometry.clone_cut_bot(value);
+		for point in &points {
+			let expected = geometry.contains_point(point);
+			if point.y < value {
+				assert_eq!(top.contains_point(point), expected, "top {} at {:?}", value, point);
+				assert!(!bot.contains_point(point), "bot {} at {:?}", value, point);
+			} else {
+				assert_eq!(bot.contains_point(point), expected, "bot {} at {:?}", value, point);
+				assert!(!top.contains_point(point), "top {} at {:?}", value, point);
+			}
+		}
+	}
+
+	for value in [9.7, 10.0, 10.3, 10.6] {
+		let lef = geometry.clone_cut_lef(value);
+		let rig = geometry.clone_cut_rig(value);
+		for point in &points {
+			let expected = geometry.contains_point(point);
+			if point.x < value {
+				assert_eq!(rig.contains_point(point), expected, "rig {} at {:?}", value, point);
+				assert!(!lef.contains_point(point), "lef {} at {:?}", value, point);
+			} else {
+				assert_eq!(lef.contains_point(point), expected, "lef {} at {:?}", value, point);
+				assert!(!rig.contains_point(point), "rig {} at {:?}", value, point);
+			}
+		}
+	}
+}
+
+// cutting down to a cell, like the quadtree in draw_blocks and draw_geometry
+#[test]
+fn cut_to_cell() {
+	let geometry = test_geometry();
+	let points = test_points();
+
+	for (x_min, y_min, size) in [(9.9, 50.9, 0.4), (10.2, 51.2, 0.1), (10.45, 51.05, 0.03), (8.0, 50.0, 0.5)] {
+		let cell = geometry
+			.clone_cut_top(y_min + size)
+			.clone_cut_bot(y_min)
+			.clone_cut_lef(x_min)
+			.clone_cut_rig(x_min + size);
+
+		for point in &points {
+			let inside_cell = (point.x > x_min) && (point.x < x_min + size) && (point.y > y_min) && (point.y < y_min + size);
+			let expected = inside_cell && geometry.contains_point(point);
+			assert_eq!(cell.contains_point(point), expected, "cell {} {} {} at {:?}", x_min, y_min, size, point);
+		}
+	}
+}
+
+// the clipped rings must not keep the vertices outside of the cell
+#[test]
+fn cut_reduces_points() {
+	let mut random = Random { state: 7 };
+	let mut geometry = Geometry::new();
+	geometry.add_polygon(vec![star(&mut random, 10.0, 51.0, 0.9, 1.0, 1000)]);
+
+	let cell = geometry
+		.clone_cut_top(51.05)
+		.clone_cut_bot(50.95)
+		.clone_cut_lef(10.9)
+		.clone_cut_rig(11.1);
+	assert!(cell.point_count() < 100, "{} points", cell.point_count());
+
+	let outside = geometry
+		.clone_cut_top(51.05)
+		.clone_cut_bot(50.95)
+		.clone_cut_lef(11.5);
+	assert_eq!(outside.point_count(), 0);
+}
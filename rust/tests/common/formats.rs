@@ -0,0 +1,355 @@
+/*
+	Writes GeoJSON geometries as GeoPackage and FlatGeobuf, with only the parts, that gpkg.rs and fgb.rs read.
+	dimensions 3 adds z = 1000 to every coordinate, 4 also m = 7, so a reader that does not skip them, gets wrong points.
+*/
+
+use json::JsonValue;
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+
+const Z: f64 = 1000.0;
+const M: f64 = 7.0;
+
+fn geometry_code(geometry: &JsonValue) -> u8 {
+	return match geometry["type"].as_str().unwrap() {
+		"Point" => 1,
+		"LineString" => 2,
+		"Polygon" => 3,
+		"MultiPoint" => 4,
+		"MultiLineString" => 5,
+		"MultiPolygon" => 6,
+		"GeometryCollection" => 7,
+		geometry_type => panic!("unknown geometry type {}", geometry_type),
+	};
+}
+
+// all coordinates of a geometry, of its coordinates or of a GeometryCollection
+fn collect_points(value: &JsonValue, points: &mut Vec<[f64; 2]>) {
+	if value.has_key("geometries") {
+		value["geometries"].members().for_each(|geometry| collect_points(geometry, points));
+	} else if value.has_key("coordinates") {
+		collect_points(&value["coordinates"], points);
+	} else if value[0].is_number() {
+		points.push([value[0].as_f64().unwrap(), value[1].as_f64().unwrap()]);
+	} else {
+		value.members().for_each(|member| collect_points(member, points));
+	}
+}
+
+// [x_min, y_min, x_max, y_max]
+fn geometry_bbox(geometry: &JsonValue) -> [f64; 4] {
+	let mut points = Vec::new();
+	collect_points(geometry, &mut points);
+	let mut bbox = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+	for [x, y] in points {
+		bbox = [bbox[0].min(x), bbox[1].min(y), bbox[2].max(x), bbox[3].max(y)];
+	}
+	return bbox;
+}
+
+
+
+// GeoPackage with the table "features", with_index adds the R-tree "rtree_features_geom"
+// a feature without geometry and one with an empty geometry are added, the reader has to skip them
+pub fn write_gpkg(filename: &Path, geometries: &[JsonValue], dimensions: usize, with_index: bool) {
+	let _result = fs::remove_file(filename);
+	let connection = Connection::open(filename).unwrap();
+	connection.execute_batch("
+		CREATE TABLE gpkg_geometry_columns (table_name TEXT, column_name TEXT, geometry_type_name TEXT, srs_id INTEGER, z TINYINT, m TINYINT);
+		CREATE TABLE features (fid INTEGER PRIMARY KEY AUTOINCREMENT, geom BLOB);
+	").unwrap();
+	connection.execute(
+		"INSERT INTO gpkg_geometry_columns VALUES ('features', 'geom', 'GEOMETRY', 4326, ?1, ?2)",
+		[(dimensions > 2) as i64, (dimensions > 3) as i64],
+	).unwrap();
+	if with_index {
+		connection.execute_batch("CREATE VIRTUAL TABLE rtree_features_geom USING rtree(id, minx, maxx, miny, maxy)").unwrap();
+	}
+
+	for geometry in geometries {
+		let bbox = geometry_bbox(geometry);
+		let mut blob = b"GP\x00\x03".to_vec();
+		blob.extend(4326i32.to_le_bytes());
+		for value in [bbox[0], bbox[2], bbox[1], bbox[3]] {
+			blob.extend(value.to_le_bytes());
+		}
+		write_wkb(&mut blob, geometry, dimensions);
+		connection.execute("INSERT INTO features (geom) VALUES (?1)", [&blob]).unwrap();
+		if with_index {
+			connection.execute(
+				"INSERT INTO rtree_features_geom VALUES (?1, ?2, ?3, ?4, ?5)",
+				(connection.last_insert_rowid(), bbox[0], bbox[2], bbox[1], bbox[3]),
+			).unwrap();
+		}
+	}
+
+	// empty flag, followed by an empty point
+	let mut empty = b"GP\x00\x11".to_vec();
+	empty.extend(4326i32.to_le_bytes());
+	empty.push(1);
+	empty.extend(1u32.to_le_bytes());
+	empty.extend(f64::NAN.to_le_bytes());
+	empty.extend(f64::NAN.to_le_bytes());
+	connection.execute("INSERT INTO features (geom) VALUES (?1)", [&empty]).unwrap();
+	connection.execute("INSERT INTO features (geom) VALUES (NULL)", []).unwrap();
+}
+
+// little endian ISO WKB: 1000 is added to the type for z, 3000 for z and m
+fn write_wkb(wkb: &mut Vec<u8>, geometry: &JsonValue, dimensions: usize) {
+	let code = geometry_code(geometry);
+	wkb.push(1);
+	wkb.extend((code as u32 + [0, 1000, 3000][dimensions - 2]).to_le_bytes());
+
+	let coordinates = &geometry["coordinates"];
+	let write_points = |wkb: &mut Vec<u8>, points: &JsonValue| {
+		wkb.extend((points.len() as u32).to_le_bytes());
+		points.members().for_each(|point| write_wkb_point(wkb, point, dimensions));
+	};
+	match code {
+		1 => write_wkb_point(wkb, coordinates, dimensions),
+		2 => write_points(wkb, coordinates),
+		3 => {
+			wkb.extend((coordinates.len() as u32).to_le_bytes());
+			coordinates.members().for_each(|ring| write_points(wkb, ring));
+		},
+		4..=6 => {
+			// every member is a geometry of its own
+			let member_type = ["Point", "LineString", "Polygon"][code as usize - 4];
+			wkb.extend((coordinates.len() as u32).to_le_bytes());
+			for member in coordinates.members() {
+				write_wkb(wkb, &json::object! { "type": member_type, "coordinates": member.clone() }, dimensions);
+			}
+		},
+		_ => {
+			wkb.extend((geometry["geometries"].len() as u32).to_le_bytes());
+			geometry["geometries"].members().for_each(|member| write_wkb(wkb, member, dimensions));
+		},
+	}
+}
+
+fn write_wkb_point(wkb: &mut Vec<u8>, point: &JsonValue, dimensions: usize) {
+	let values = [point[0].as_f64().unwrap(), point[1].as_f64().unwrap(), Z, M];
+	for value in &values[0..dimensions] {
+		wkb.extend(value.to_le_bytes());
+	}
+}
+
+
+
+// FlatGeobuf with an R-tree of index_node_size (0: without index)
+// the header has the geometry type, if all geometries have the same, otherwise "Unknown" and every geometry has its own
+pub fn write_fgb(filename: &Path, geometries: &[JsonValue], dimensions: usize, index_node_size: u16) {
+	let codes: Vec<u8> = geometries.iter().map(geometry_code).collect();
+	let geometry_type = if codes.iter().all(|code| *code == codes[0]) { codes[0] } else { 0 };
+
+	let mut features: Vec<u8> = Vec::new();
+	let mut offsets: Vec<u64> = Vec::new();
+	for geometry in geometries {
+		offsets.push(features.len() as u64);
+		let feature = flatbuffer(&[Field::Table(fgb_geometry(geometry, dimensions))]);
+		features.extend((feature.len() as u32).to_le_bytes());
+		features.extend(feature);
+	}
+
+	let header = flatbuffer(&[
+		Field::Empty, // name
+		Field::Empty, // envelope
+		Field::U8(geometry_type),
+		Field::U8((dimensions > 2) as u8),
+		Field::U8((dimensions > 3) as u8),
+		Field::Empty, // has_t
+		Field::Empty, // has_tm
+		Field::Empty, // columns
+		Field::U64(geometries.len() as u64),
+		Field::U16(index_node_size),
+	]);
+
+	let mut file = vec![b'f', b'g', b'b', 3, b'f', b'g', b'b', 0];
+	file.extend((header.len() as u32).to_le_bytes());
+	file.extend(header);
+	if index_node_size > 0 {
+		let bboxes: Vec<[f64; 4]> = geometries.iter().map(geometry_bbox).collect();
+		file.extend(packed_rtree(&bboxes, &offsets, index_node_size as usize));
+	}
+	file.extend(features);
+	fs::write(filename, file).unwrap();
+}
+
+// fields of the flatbuffer table "Geometry": ends, xy, z, m, t, tm, type, parts
+fn fgb_geometry(geometry: &JsonValue, dimensions: usize) -> Vec<Field> {
+	let code = geometry_code(geometry);
+	let coordinates = &geometry["coordinates"];
+	let mut points: Vec<[f64; 2]> = Vec::new();
+	let mut ends: Vec<u32> = Vec::new();
+	let mut parts: Vec<Vec<Field>> = Vec::new();
+	match code {
+		1 | 2 | 4 => collect_points(coordinates, &mut points),
+		3 | 5 => {
+			for ring in coordinates.members() {
+				collect_points(ring, &mut points);
+				ends.push(points.len() as u32);
+			}
+		},
+		6 => {
+			for polygon in coordinates.members() {
+				parts.push(fgb_geometry(&json::object! { "type": "Polygon", "coordinates": polygon.clone() }, dimensions));
+			}
+		},
+		_ => {
+			for member in geometry["geometries"].members() {
+				parts.push(fgb_geometry(member, dimensions));
+			}
+		},
+	}
+
+	let vector = |value: f64, used: bool| {
+		return if used && !points.is_empty() { Field::F64s(vec![value; points.len()]) } else { Field::Empty };
+	};
+	return vec![
+		// like other writers: the ends only, if there is more than one ring or line
+		if ends.len() > 1 { Field::U32s(ends) } else { Field::Empty },
+		if points.is_empty() { Field::Empty } else { Field::F64s(points.iter().flatten().copied().collect()) },
+		vector(Z, dimensions > 2),
+		vector(M, dimensions > 3),
+		Field::Empty,
+		Field::Empty,
+		Field::U8(code),
+		if parts.is_empty() { Field::Empty } else { Field::Tables(parts) },
+	];
+}
+
+// nodes of the packed R-tree level by level, root first, like PackedRTree in fgb.rs
+// the leaves are in the order of the features, fgb.rs does not need the Hilbert sorting
+fn packed_rtree(bboxes: &[[f64; 4]], offsets: &[u64], node_size: usize) -> Vec<u8> {
+	let mut level_sizes = vec![bboxes.len()];
+	loop {
+		let size = level_sizes.last().unwrap().div_ceil(node_size);
+		level_sizes.push(size);
+		if size == 1 {
+			break;
+		}
+	}
+	let num_nodes: usize = level_sizes.iter().sum();
+	let mut level_starts: Vec<usize> = Vec::new();
+	let mut start = num_nodes;
+	for size in &level_sizes {
+		start -= size;
+		level_starts.push(start);
+	}
+
+	let mut nodes: Vec<([f64; 4], u64)> = vec![([0.0; 4], 0); num_nodes];
+	for (index, (bbox, offset)) in bboxes.iter().zip(offsets).enumerate() {
+		nodes[level_starts[0] + index] = (*bbox, *offset);
+	}
+	for level in 1..level_sizes.len() {
+		let child_end = level_starts[level - 1] + level_sizes[level - 1];
+		for index in 0..level_sizes[level] {
+			let first = level_starts[level - 1] + index * node_size;
+			let mut bbox = [f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY];
+			for (child, _offset) in &nodes[first..(first + node_size).min(child_end)] {
+				bbox = [bbox[0].min(child[0]), bbox[1].min(child[1]), bbox[2].max(child[2]), bbox[3].max(child[3])];
+			}
+			nodes[level_starts[level] + index] = (bbox, first as u64);
+		}
+	}
+
+	let mut buffer: Vec<u8> = Vec::new();
+	for (bbox, offset) in nodes {
+		bbox.iter().for_each(|value| buffer.extend(value.to_le_bytes()));
+		buffer.extend(offset.to_le_bytes());
+	}
+	return buffer;
+}
+
+
+
+enum Field {
+	Empty,
+	U8(u8),
+	U16(u16),
+	U64(u64),
+	Table(Vec<Field>),
+	Tables(Vec<Vec<Field>>),
+	U32s(Vec<u32>),
+	F64s(Vec<f64>),
+}
+
+// a flatbuffer, written front to back: the root offset, then every table as vtable, table and its children
+fn flatbuffer(fields: &[Field]) -> Vec<u8> {
+	let mut buffer = vec![0u8; 4];
+	let table = write_table(&mut buffer, fields);
+	set_offset(&mut buffer, 0, table);
+	return buffer;
+}
+
+fn set_offset(buffer: &mut [u8], pos: usize, target: usize) {
+	buffer[pos..pos + 4].copy_from_slice(&((target - pos) as u32).to_le_bytes());
+}
+
+// returns the position of the table
+fn write_table(buffer: &mut Vec<u8>, fields: &[Field]) -> usize {
+	let sizes: Vec<usize> = fields.iter().map(|field| match field {
+		Field::Empty => 0,
+		Field::U8(_) => 1,
+		Field::U16(_) => 2,
+		Field::U64(_) => 8,
+		_ => 4,
+	}).collect();
+
+	let vtable = buffer.len();
+	buffer.extend((4 + 2 * fields.len() as u16).to_le_bytes());
+	buffer.extend((4 + sizes.iter().sum::<usize>() as u16).to_le_bytes());
+	let mut offset = 4;
+	for size in &sizes {
+		buffer.extend((if *size == 0 { 0 } else { offset as u16 }).to_le_bytes());
+		offset += size;
+	}
+
+	let table = buffer.len();
+	buffer.extend(((table - vtable) as i32).to_le_bytes());
+	let mut children: Vec<(usize, &Field)> = Vec::new();
+	for field in fields {
+		match field {
+			Field::Empty => {},
+			Field::U8(value) => buffer.push(*value),
+			Field::U16(value) => buffer.extend(value.to_le_bytes()),
+			Field::U64(value) => buffer.extend(value.to_le_bytes()),
+			_ => {
+				children.push((buffer.len(), field));
+				buffer.extend([0u8; 4]);
+			},
+		}
+	}
+
+	for (pos, field) in children {
+		let target = match field {
+			Field::Table(fields) => write_table(buffer, fields),
+			Field::Tables(tables) => {
+				let vector = buffer.len();
+				buffer.extend((tables.len() as u32).to_le_bytes());
+				buffer.extend(vec![0u8; 4 * tables.len()]);
+				for (index, fields) in tables.iter().enumerate() {
+					let table = write_table(buffer, fields);
+					set_offset(buffer, vector + 4 + 4 * index, table);
+				}
+				vector
+			},
+			Field::U32s(values) => {
+				let vector = buffer.len();
+				buffer.extend((values.len() as u32).to_le_bytes());
+				values.iter().for_each(|value| buffer.extend(value.to_le_bytes()));
+				vector
+			},
+			Field::F64s(values) => {
+				let vector = buffer.len();
+				buffer.extend((values.len() as u32).to_le_bytes());
+				values.iter().for_each(|value| buffer.extend(value.to_le_bytes()));
+				vector
+			},
+			_ => unreachable!(),
+		};
+		set_offset(buffer, pos, target);
+	}
+	return table;
+}
@@ -0,0 +1,56 @@
+/*
+	Helpers, that several tests share. Every test file uses only some of them.
+*/
+
+#![allow(dead_code)]
+
+pub mod formats;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point};
+
+// an empty folder "windradabstand-<name>-<pid>" in the temp directory, the test removes it at the end
+pub fn temp_folder(name: &str) -> PathBuf {
+	let folder = std::env::temp_dir().join(format!("windradabstand-{}-{}", name, std::process::id()));
+	let _result = fs::remove_dir_all(&folder);
+	fs::create_dir_all(&folder).unwrap();
+	return folder;
+}
+
+// tile 9/275/167 with a line and a triangle: coverage for channels with the value range [0, 1], distances for the others
+pub fn test_image(size: u32, infos: &[ChannelInfo]) -> GeoImage {
+	let mut image = GeoImage::with_channels(size, 9, 275, 167, infos);
+	let mut geometry = Geometry::new();
+	geometry.add_line(vec![Point::new(13.5, 52.6), Point::new(13.9, 52.8)]);
+	geometry.add_polygon(vec![vec![Point::new(13.6, 52.55), Point::new(13.8, 52.6), Point::new(13.7, 52.75), Point::new(13.6, 52.55)]]);
+	let collection = Collection::from_geometry(geometry);
+	for (index, info) in infos.iter().enumerate() {
+		if info.value_range == [0.0, 1.0] {
+			image.draw_geometry(index, &collection);
+		} else {
+			image.draw_distances(index, &collection, info.value_range[0], info.value_range[1], 1);
+		}
+	}
+	return image;
+}
+
+pub fn write_geojson(filename: &Path, features: Vec<json::JsonValue>) {
+	let geojson = json::object! { "type": "FeatureCollection", "features": features };
+	fs::write(filename, geojson.dump()).unwrap();
+}
+
+// protobuf and PMTiles varint
+pub fn read_varint(buffer: &[u8], position: &mut usize) -> u64 {
+	let mut value = 0u64;
+	let mut shift = 0;
+	loop {
+		let byte = buffer[*position];
+		*position += 1;
+		value |= ((byte & 0x7f) as u64) << shift;
+		if byte < 0x80 {
+			return value;
+		}
+		shift += 7;
+	}
+}
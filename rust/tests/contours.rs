@@ -0,0 +1,211 @@
+/*
+	Contours: the polygons around a point have to be circles with the right area, rings have to be closed
+	along the border of the tile, and GeoJSON and vector tiles need the right orientation.
+*/
+
+use windradabstand::contours::{contours_to_geojson, contours_to_mvt, Contour, DEFAULT_DISTANCES};
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point};
+
+mod common;
+use common::read_varint;
+
+const SIZE: u32 = 256;
+
+// a tile of zoom level 11 with about 46 m per pixel
+fn render(geometry: Geometry) -> GeoImage {
+	let mut image = GeoImage::with_channels(SIZE, 11, 1101, 669, &[ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0)]);
+	image.draw_distances(0, &Collection::from_geometry(geometry), -500.0, 2000.0, 1);
+	return image;
+}
+
+fn center(image: &GeoImage) -> Point {
+	let (min, max) = (image.get_point_min(), image.get_point_max());
+	return Point::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0);
+}
+
+// size of a pixel in meters at the center of the tile
+fn pixel_size(image: &GeoImage) -> f64 {
+	let (min, max) = (image.get_point_min(), image.get_point_max());
+	return (max.x - min.x) / (SIZE as f64) * 111_319.5 * center(image).y.to_radians().cos();
+}
+
+fn area_m2(image: &GeoImage, contour: &Contour) -> f64 {
+	return contour.area() * pixel_size(image).powi(2);
+}
+
+// a square around the center with a side of 2 * half meters
+fn square(image: &GeoImage, half: f64) -> Vec<Point> {
+	let c = center(image);
+	let dx = half / (111_319.5 * c.y.to_radians().cos());
+	let dy = half / 111_319.5;
+	return vec![
+		Point::new(c.x - dx, c.y - dy), Point::new(c.x + dx, c.y - dy), Point::new(c.x + dx, c.y + dy),
+		Point::new(c.x - dx, c.y + dy), Point::new(c.x - dx, c.y - dy),
+	];
+}
+
+// the fields of a protobuf message as (field, varint or bytes)
+fn read_message(buffer: &[u8]) -> Vec<(u64, u64, &[u8])> {
+	let mut fields = Vec::new();
+	let mut position = 0;
+	while position < buffer.len() {
+		let key = read_varint(buffer, &mut position);
+		match key & 7 {
+			0 => fields.push((key >> 3, read_varint(buffer, &mut position), &buffer[0..0])),
+			1 => {
+				fields.push((key >> 3, 0, &buffer[position..position + 8]));
+				position += 8;
+			},
+			2 => {
+				let length = read_varint(buffer, &mut position) as usize;
+				fields.push((key >> 3, 0, &buffer[position..position + length]));
+				position += length;
+			},
+			wire_type => panic!("unexpected wire type {}", wire_type),
+		}
+	}
+	return fields;
+}
+
+#[test]
+fn circles_around_a_point() {
+	let mut geometry = Geometry::new();
+	let image = render(Geometry::new());
+	geometry.add_point(center(&image));
+	let image = render(geometry);
+
+	let contours = image.contours(0, &DEFAULT_DISTANCES).unwrap();
+	assert_eq!(contours.len(), 5);
+	let mut previous = 0.0;
+	for contour in &contours {
+		assert_eq!(contour.polygons.len(), 1);
+		assert_eq!(contour.polygons[0].len(), 1);
+		let expected = std::f64::consts::PI * (contour.distance as f64).powi(2);
+		let area = area_m2(&image, contour);
+		assert!((area / expected - 1.0).abs() < 0.02, "{} m: {} m² instead of {} m²", contour.distance, area, expected);
+		assert!(area > previous);
+		previous = area;
+	}
+
+	assert!(image.contours(0, &[2500.0]).is_err());
+	assert!(image.contours(0, &[-500.0]).is_err());
+	assert!(image.contours(1, &[400.0]).is_err());
+}
+
+#[test]
+fn holes_and_borders() {
+	// the outline of a square of 4 km: a band with a hole
+	let image = render(Geometry::new());
+	let (half, r) = (2000.0, 400.0);
+	let mut geometry = Geometry::new();
+	geometry.add_line(square(&image, half));
+	let image = render(geometry);
+
+	let contour = &image.contours(0, &[r as f32]).unwrap()[0];
+	assert_eq!(contour.polygons.len(), 1);
+	assert_eq!(contour.polygons[0].len(), 2);
+	let side = 2.0 * half;
+	let expected = side * side + 4.0 * side * r + std::f64::consts::PI * r * r - (side - 2.0 * r).powi(2);
+	let area = area_m2(&image, contour);
+	assert!((area / expected - 1.0).abs() < 0.03, "{} m² instead of {} m²", area, expected);
+
+	// a polygon covering the whole tile: the ring is the border of the tile
+	let mut geometry = Geometry::new();
+	geometry.add_polygon(vec![square(&image, 20_000.0)]);
+	let contour = &render(geometry).contours(0, &[-100.0]).unwrap()[0];
+	assert_eq!(contour.polygons.len(), 1);
+	assert_eq!(contour.polygons[0][0].len(), 4);
+	assert_eq!(contour.area(), (SIZE * SIZE) as f64);
+}
+
+#[test]
+fn geojson() {
+	// the 1000 m contour closes the hole of the square with 1.6 km
+	let image = render(Geometry::new());
+	let mut geometry = Geometry::new();
+	geometry.add_line(square(&image, 800.0));
+	let image = render(geometry);
+	let contours = image.contours(0, &[400.0, 1000.0]).unwrap();
+
+	let geojson = contours_to_geojson(&image, &contours);
+	assert_eq!(geojson["type"], "FeatureCollection");
+	assert_eq!(geojson["features"].len(), 2);
+	let feature = &geojson["features"][0];
+	assert_eq!(feature["properties"]["distance"], 400.0);
+	assert_eq!(feature["geometry"]["type"], "MultiPolygon");
+
+	// exterior counterclockwise, holes clockwise
+	let rings = &feature["geometry"]["coordinates"][0];
+	assert_eq!(rings.len(), 2);
+	for (index, ring) in rings.members().enumerate() {
+		let points: Vec<(f64, f64)> = ring.members().map(|p| (p[0].as_f64().unwrap(), p[1].as_f64().unwrap())).collect();
+		assert_eq!(points[0], points[points.len() - 1]);
+		let area: f64 = points.windows(2).map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1).sum();
+		assert_eq!(area > 0.0, index == 0);
+	}
+	assert_eq!(geojson["features"][1]["geometry"]["coordinates"][0].len(), 1);
+}
+
+#[test]
+fn vector_tile() {
+	// the 1000 m contour closes the hole of the square with 1.6 km
+	let image = render(Geometry::new());
+	let mut geometry = Geometry::new();
+	geometry.add_line(square(&image, 800.0));
+	let image = render(geometry);
+	let contours = image.contours(0, &[400.0, 1000.0]).unwrap();
+	let tile = contours_to_mvt(&image, &contours);
+
+	let fields = read_message(&tile);
+	assert_eq!(fields.len(), 1);
+	assert_eq!(fields[0].0, 3);
+	let layer = read_message(fields[0].2);
+	let get = |field: u64| layer.iter().filter(move |f| f.0 == field);
+	assert_eq!(get(15).next().unwrap().1, 2);
+	assert_eq!(get(1).next().unwrap().2, b"contours");
+	assert_eq!(get(5).next().unwrap().1, 4096);
+	assert_eq!(get(3).next().unwrap().2, b"distance");
+	let values: Vec<f64> = get(4).map(|value| f64::from_le_bytes(read_message(value.2)[0].2.try_into().unwrap())).collect();
+	assert_eq!(values, vec![400.0, 1000.0]);
+
+	let features: Vec<_> = get(2).map(|feature| read_message(feature.2)).collect();
+	assert_eq!(features.len(), 2);
+	for (index, feature) in features.iter().enumerate() {
+		let field = |number: u64| feature.iter().find(|f| f.0 == number).unwrap();
+		// polygon with key 0 and the value of its distance
+		assert_eq!(field(3).1, 3);
+		assert_eq!(field(2).2, &[0, index as u8]);
+
+		// decode the rings: the first one positive (exterior), then the hole negative
+		let mut position = 0;
+		let mut values = Vec::new();
+		while position < field(4).2.len() {
+			values.push(read_varint(field(4).2, &mut position) as u32);
+		}
+		let unzigzag = |value: u32| ((value >> 1) as i64) ^ -((value & 1) as i64);
+		let (mut i, mut cursor, mut areas) = (0, (0i64, 0i64), Vec::new());
+		while i < values.len() {
+			assert_eq!(values[i], 1 | (1 << 3));
+			let mut ring = Vec::new();
+			cursor = (cursor.0 + unzigzag(values[i + 1]), cursor.1 + unzigzag(values[i + 2]));
+			ring.push(cursor);
+			assert_eq!(values[i + 3] & 7, 2);
+			let count = (values[i + 3] >> 3) as usize;
+			for k in 0..count {
+				cursor = (cursor.0 + unzigzag(values[i + 4 + 2 * k]), cursor.1 + unzigzag(values[i + 5 + 2 * k]));
+				assert!((0..=4096).contains(&cursor.0) && (0..=4096).contains(&cursor.1));
+				ring.push(cursor);
+			}
+			i += 4 + 2 * count;
+			assert_eq!(values[i], 7 | (1 << 3));
+			i += 1;
+			let n = ring.len();
+			areas.push((0..n).map(|k| ring[k].0 * ring[(k + 1) % n].1 - ring[(k + 1) % n].0 * ring[k].1).sum::<i64>());
+		}
+		assert!(areas[0] > 0);
+		assert_eq!(areas.len(), 2 - index);
+		if index == 0 {
+			assert!(areas[1] < 0);
+		}
+	}
+}
@@ -0,0 +1,91 @@
+/*
+	Coverage as signed distance: with a distance range a coverage layer has to give the same pixels as a distance layer,
+	negative inside, and agree with the coverage about which pixels are covered.
+*/
+
+use std::fs;
+use std::path::Path;
+use windradabstand::layers::{parse_layers, Layer, LayerKind};
+use windradabstand::tiles::{render_image, tile_bbox, TileCoord};
+
+mod common;
+
+const TILE: TileCoord = TileCoord { zoom: 12, x: 2201, y: 1339 };
+const SIZE: u32 = 64;
+
+fn arguments(filename: &Path, entries: json::JsonValue) -> json::JsonValue {
+	let mut arguments = json::object! { "layers": entries };
+	for entry in arguments["layers"].members_mut() {
+		entry["filename"] = filename.to_str().unwrap().into();
+	}
+	return arguments;
+}
+
+fn render(mut layers: Vec<Layer>) -> Vec<Vec<f32>> {
+	for layer in layers.iter_mut() {
+		layer.load(&tile_bbox(TILE)).unwrap();
+	}
+	let image = render_image(&layers, TILE, SIZE, 2);
+	return (0..layers.len()).map(|channel| image.channel_data(channel).to_vec()).collect();
+}
+
+#[test]
+fn coverage_as_distance() {
+	let folder = common::temp_folder("coverage-distance");
+
+	// a polygon in the middle of the tile
+	let bbox = tile_bbox(TILE);
+	let at = |fx: f64, fy: f64| json::array![bbox.x_min + (bbox.x_max - bbox.x_min) * fx, bbox.y_min + (bbox.y_max - bbox.y_min) * fy];
+	let mut geojson = json::object! {
+		"type": "FeatureCollection",
+		"features": [{ "type": "Feature", "properties": {}, "geometry": { "type": "Polygon" } }],
+	};
+	geojson["features"][0]["geometry"]["coordinates"] = json::array![[at(0.3, 0.3), at(0.7, 0.35), at(0.6, 0.7), at(0.3, 0.3)]];
+	let filename = folder.join("fixed.geojson");
+	fs::write(&filename, geojson.dump()).unwrap();
+
+	let layers = parse_layers(&arguments(&filename, json::array![
+		{ "type": "coverage" },
+		{ "type": "coverage", "min_distance": -500, "max_distance": 1000 },
+		{ "type": "distance", "min_distance": -500, "max_distance": 1000 },
+	])).unwrap();
+	assert!(matches!(layers[0].kind, LayerKind::Coverage));
+	let channels = render(layers);
+	assert_eq!(channels[1], channels[2]);
+
+	// the border of the polygon is at distance 0, so at 1/3 of the normalized range
+	let zero = 500.0 / 1500.0;
+	let mut inside = 0;
+	for (coverage, distance) in channels[0].iter().zip(&channels[1]) {
+		if *coverage == 1.0 {
+			assert!(*distance < zero);
+			inside += 1;
+		}
+		if *coverage == 0.0 {
+			assert!(*distance > zero);
+		}
+	}
+	assert!(inside > 100);
+
+	// "coverage_distance" for all coverage layers, also the old filename_geo_fix
+	let mut obj = arguments(&filename, json::array![{ "type": "coverage" }, { "type": "coverage", "min_distance": 0, "max_distance": 10 }]);
+	obj["coverage_distance"] = json::array![-500, 1000];
+	let layers = parse_layers(&obj).unwrap();
+	assert!(matches!(layers[0].kind, LayerKind::Distance { min_distance, max_distance } if (min_distance, max_distance) == (-500.0, 1000.0)));
+	assert!(matches!(layers[1].kind, LayerKind::Distance { max_distance, .. } if max_distance == 10.0));
+	assert_eq!(render(layers)[0], channels[2]);
+
+	let old = json::object! {
+		"filename_geo_dyn": filename.to_str().unwrap(), "filename_geo_fix": filename.to_str().unwrap(),
+		"min_distance": 0, "max_distance": 2500, "coverage_distance": [-500, 1000],
+	};
+	assert!(matches!(parse_layers(&old).unwrap()[1].kind, LayerKind::Distance { .. }));
+
+	// invalid ranges
+	for range in [json::array![1000, -500], json::array![0], json::JsonValue::from("far")] {
+		obj["coverage_distance"] = range;
+		assert!(parse_layers(&obj).is_err());
+	}
+	assert!(parse_layers(&arguments(&filename, json::array![{ "type": "coverage", "min_distance": -500 }])).is_err());
+	fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,163 @@
+/*
+	Curves: the position of a value has to be invertible, images with a curve decoded with their metadata have to keep
+	inside distances and distances of 10 km, and bin tiles have to keep the curve.
+*/
+
+use image::DynamicImage;
+use std::fs;
+use windradabstand::bintile::{decode, encode, Compression, VERSION};
+use windradabstand::encoding::{tile_metadata, PixelEncoding, ValueCurve};
+use windradabstand::layers::{parse_layers, LayerKind};
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point, TileFormat};
+
+mod common;
+
+const RANGE: [f32; 2] = [-2500.0, 10_000.0];
+
+fn curves() -> [ValueCurve; 3] {
+	return [
+		ValueCurve::Linear,
+		ValueCurve::Log { scale: 250.0 },
+		ValueCurve::Piecewise { breakpoints: vec![0.0, 1000.0, 2500.0] },
+	];
+}
+
+// distances around a polygon in tile 9/275/167, about 70 m per pixel
+fn render(curve: ValueCurve) -> GeoImage {
+	let info = ChannelInfo::new("wohngebaeude", RANGE, 1.0).with_curve(curve);
+	let mut image = GeoImage::with_channels(64, 9, 275, 167, &[info]);
+	let mut geometry = Geometry::new();
+	geometry.add_polygon(vec![vec![Point::new(13.6, 52.55), Point::new(13.8, 52.6), Point::new(13.7, 52.75), Point::new(13.6, 52.55)]]);
+	image.draw_distances(0, &Collection::from_geometry(geometry), RANGE[0], RANGE[1], 1);
+	return image;
+}
+
+#[test]
+fn positions() {
+	for curve in curves() {
+		assert_eq!(curve.check(RANGE), Ok(()));
+		assert_eq!(curve.to_position(RANGE, RANGE[0] as f64), 0.0);
+		assert_eq!(curve.to_position(RANGE, RANGE[1] as f64), 1.0);
+		let mut previous = -1.0;
+		for value in (-2500..=10_000).step_by(50) {
+			let position = curve.to_position(RANGE, value as f64);
+			assert!(position > previous, "{:?} at {}", curve, value);
+			previous = position;
+			let inverse = curve.from_position(RANGE, position);
+			assert!((inverse - value as f64).abs() < 1.0e-6, "{:?}: {} instead of {}", curve, inverse, value);
+		}
+	}
+
+	// the log curve has finer steps close to the geometries than far away
+	let log = ValueCurve::Log { scale: 250.0 };
+	let step = |value: f64| log.to_position(RANGE, value + 1.0) - log.to_position(RANGE, value);
+	assert!(step(0.0) > 10.0 * step(8000.0));
+	assert!(step(100.0) > (1.0 / 12500.0));
+	// every part of the piecewise curve gets the same share
+	let piecewise = &curves()[2];
+	for (value, position) in [(0.0, 0.25), (1000.0, 0.5), (2500.0, 0.75)] {
+		assert_eq!(piecewise.to_position(RANGE, value), position);
+	}
+
+	assert!(ValueCurve::Log { scale: 0.0 }.check(RANGE).is_err());
+	assert!(ValueCurve::Piecewise { breakpoints: vec![1000.0, 0.0] }.check(RANGE).is_err());
+	assert!(ValueCurve::Piecewise { breakpoints: vec![20_000.0] }.check(RANGE).is_err());
+	assert!(ValueCurve::Piecewise { breakpoints: vec![] }.check(RANGE).is_err());
+}
+
+#[test]
+fn decode_images() {
+	for curve in &curves()[1..] {
+		let image = render(curve.clone());
+		let infos = image.channel_infos();
+		for encoding in [PixelEncoding::Rgb8, PixelEncoding::Rg16] {
+			let metadata = tile_metadata(TileFormat::Png, encoding, &infos);
+			let channel = &metadata["channels"][0];
+			let decoding = match channel["curve"]["type"].as_str().unwrap() {
+				"log" => ValueCurve::Log { scale: channel["curve"]["scale"].as_f32().unwrap() },
+				_ => ValueCurve::Piecewise { breakpoints: channel["curve"]["breakpoints"].members().map(|v| v.as_f32().unwrap()).collect() },
+			};
+			assert_eq!(&decoding, curve);
+
+			let files = image.encode_images(&TileFormat::Png, encoding).unwrap();
+			let pixels = match image::load_from_memory(&files[0]).unwrap() {
+				DynamicImage::ImageRgb8(pixels) => pixels,
+				_ => panic!("expected an rgb image"),
+			};
+			let weights: Vec<f64> = channel["weights"].members().map(|weight| weight.as_f64().unwrap()).collect();
+			let offset = channel["offset"].as_f64().unwrap();
+			let step = if encoding == PixelEncoding::Rgb8 { 1.0 / 255.0 } else { 1.0 / 65535.0 };
+
+			let (mut inside, mut far) = (0, 0);
+			for (i, value) in image.channel_data(0).iter().enumerate() {
+				// deep inside the polygon the distance can be below min_distance
+				let expected = (RANGE[0] as f64 + (*value as f64) * (RANGE[1] - RANGE[0]) as f64).max(RANGE[0] as f64);
+				let rgb = &pixels.as_raw()[i * 3..i * 3 + 3];
+				let position = weights[0] * rgb[0] as f64 + weights[1] * rgb[1] as f64 + weights[2] * rgb[2] as f64 + offset;
+				let decoded = curve.from_position(RANGE, position);
+				// rgb8 is truncated, rg16 rounded: at most one step of the position
+				let tolerance = curve.from_position(RANGE, (position + step).min(1.0)) - curve.from_position(RANGE, (position - step).max(0.0));
+				assert!((decoded - expected).abs() <= tolerance + 1.0e-3, "{:?} {:?}: {} instead of {}", curve, encoding, decoded, expected);
+				if expected < -200.0 {
+					inside += 1;
+				}
+				if expected > 8000.0 {
+					far += 1;
+				}
+			}
+			assert!((inside > 0) && (far > 0), "{} {}", inside, far);
+		}
+	}
+
+	// terrarium stores the values themselves
+	let metadata = tile_metadata(TileFormat::Png, PixelEncoding::Terrarium, &render(curves()[1].clone()).channel_infos());
+	assert!(metadata["channels"][0]["curve"].is_null());
+	assert!(tile_metadata(TileFormat::Png, PixelEncoding::Rgb8, &render(ValueCurve::Linear).channel_infos())["channels"][0]["curve"].is_null());
+}
+
+#[test]
+fn bin_tiles_and_arguments() {
+	for curve in curves() {
+		let image = render(curve.clone());
+		let buffer = encode(&image, Compression::Zstd);
+		let (decoded, file_info) = decode(&buffer).unwrap();
+		assert_eq!(file_info.version, if curve == ValueCurve::Linear { 2 } else { VERSION });
+		assert_eq!(decoded.channel_infos()[0].curve, curve);
+		assert_eq!(decoded, image);
+	}
+
+	let folder = common::temp_folder("curves");
+	let filename = folder.join("empty.geojson");
+	fs::write(&filename, "{\"type\": \"FeatureCollection\", \"features\": []}").unwrap();
+	let filename = filename.to_str().unwrap();
+
+	// for all distances, coverage stays linear, a layer can set its own curve
+	let mut arguments = json::object! {
+		"curve": { "type": "log", "scale": 250 },
+		"layers": [
+			{ "filename": filename, "type": "distance", "min_distance": -2500, "max_distance": 10000 },
+			{ "filename": filename, "type": "coverage" },
+			{ "filename": filename, "type": "distance", "min_distance": 0, "max_distance": 2500, "curve": "linear" },
+		],
+	};
+	let layers = parse_layers(&arguments).unwrap();
+	assert_eq!(layers[0].channel_infos()[0].curve, ValueCurve::Log { scale: 250.0 });
+	assert!(matches!(layers[1].kind, LayerKind::Coverage));
+	assert_eq!(layers[1].channel_infos()[0].curve, ValueCurve::Linear);
+	assert_eq!(layers[2].channel_infos()[0].curve, ValueCurve::Linear);
+
+	arguments["layers"][2]["curve"] = json::object! { "type": "piecewise", "breakpoints": [500, 1000] };
+	assert_eq!(parse_layers(&arguments).unwrap()[2].curve, ValueCurve::Piecewise { breakpoints: vec![500.0, 1000.0] });
+	// breakpoints outside of [0, 2500]
+	arguments["layers"][2]["curve"] = json::object! { "type": "piecewise", "breakpoints": [500, 5000] };
+	assert!(parse_layers(&arguments).is_err());
+	arguments["layers"][2]["curve"] = "cubic".into();
+	assert!(parse_layers(&arguments).is_err());
+	arguments["layers"][2]["curve"] = json::JsonValue::Null;
+	arguments["layers"][1]["curve"] = "log".into();
+	assert!(parse_layers(&arguments).is_err());
+	arguments["layers"][1]["curve"] = json::JsonValue::Null;
+	arguments["curve"] = json::object! { "type": "log", "scale": -1 };
+	assert!(parse_layers(&arguments).is_err());
+	fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,218 @@
+/*
+	Bounds the error of the distance models against a reference:
+	the ellipsoidal geodesic distance (geographiclib) to the nearest point of a segment, that is straight in lon/lat.
+*/
+
+use geographiclib_rs::{DirectGeodesic, Geodesic, InverseGeodesic};
+use windradabstand::geometry::*;
+
+const LATITUDES: [f64; 3] = [47.5, 51.0, 54.5];
+const DISTANCES: [f64; 5] = [10.0, 300.0, 1000.0, 2500.0, 5000.0];
+
+// the point at a distance and azimuth from (lon, lat)
+fn offset(lon: f64, lat: f64, azimuth: f64, distance: f64) -> Point {
+	let (lat2, lon2): (f64, f64) = Geodesic::wgs84().direct(lat, lon, azimuth, distance);
+	return Point::new(lon2, lat2);
+}
+
+fn geodesic_distance(p0: &Point, p1: &Point) -> f64 {
+	let distance: f64 = Geodesic::wgs84().inverse(p0.y, p0.x, p1.y, p1.x);
+	return distance;
+}
+
+// reference: dense sampling of the segment, then refinement around the nearest sample
+fn reference_distance(point: &Point, p0: &Point, p1: &Point) -> f64 {
+	let at = |t: f64| -> f64 {
+		let lon = p0.x + t * (p1.x - p0.x);
+		let lat = p0.y + t * (p1.y - p0.y);
+		let distance: f64 = Geodesic::wgs84().inverse(point.y, point.x, lat, lon);
+		return distance;
+	};
+
+	let n = 1000;
+	let mut best = 0;
+	for i in 0..=n {
+		if at(i as f64 / n as f64) < at(best as f64 / n as f64) {
+			best = i;
+		}
+	}
+
+	let mut lower = (best as f64 - 1.0).max(0.0) / n as f64;
+	let mut upper = (best as f64 + 1.0).min(n as f64) / n as f64;
+	for _ in 0..100 {
+		let t1 = lower + (upper - lower) / 3.0;
+		let t2 = upper - (upper - lower) / 3.0;
+		if at(t1) < at(t2) {
+			upper = t2;
+		} else {
+			lower = t1;
+		}
+	}
+	return at((lower + upper) / 2.0);
+}
+
+fn model_distance(model: DistanceModel, point: &Point, geometry: Geometry) -> f64 {
+	let mut collection = Collection::from_geometry(geometry);
+	collection.set_distance_model(model);
+	return collection.get_min_distance(point, 1.0e6);
+}
+
+// query point and target point in all directions
+fn point_cases() -> Vec<(Point, Point)> {
+	let mut cases: Vec<(Point, Point)> = Vec::new();
+	for lat in LATITUDES {
+		for distance in DISTANCES {
+			for azimuth in (0..360).step_by(15) {
+				cases.push((Point::new(10.0, lat), offset(10.0, lat, azimuth as f64, distance)));
+			}
+		}
+	}
+	return cases;
+}
+
+// query point and segments of up to 20 km length, passing the point in all directions
+fn segment_cases() -> Vec<(Point, Point, Point)> {
+	let mut cases: Vec<(Point, Point, Point)> = Vec::new();
+	for lat in LATITUDES {
+		for distance in DISTANCES {
+			for azimuth in (0..360).step_by(30) {
+				for length in [100.0, 2000.0, 20000.0] {
+					let foot = offset(10.0, lat, azimuth as f64, distance);
+					let p0 = offset(foot.x, foot.y, azimuth as f64 + 90.0, length * 0.3);
+					let p1 = offset(foot.x, foot.y, azimuth as f64 - 90.0, length * 0.7);
+					cases.push((Point::new(10.0, lat), p0, p1));
+				}
+			}
+		}
+	}
+	return cases;
+}
+
+// returns the reference distance and the error of the model for every case
+fn point_errors(model: DistanceModel) -> Vec<(f64, f64)> {
+	return point_cases().iter().map(|(point, target)| {
+		let mut geometry = Geometry::new();
+		geometry.add_point(*target);
+		let reference = geodesic_distance(point, target);
+		return (reference, (model_distance(model, point, geometry) - reference).abs());
+	}).collect();
+}
+
+fn segment_errors(model: DistanceModel) -> Vec<(f64, f64)> {
+	return segment_cases().iter().map(|(point, p0, p1)| {
+		let mut geometry = Geometry::new();
+		geometry.add_line(vec![*p0, *p1]);
+		let reference = reference_distance(point, p0, p1);
+		return (reference, (model_distance(model, point, geometry) - reference).abs());
+	}).collect();
+}
+
+#[test]
+fn geodesic_point_distance() {
+	for (reference, error) in point_errors(DistanceModel::Geodesic) {
+		assert!(error < 0.0001, "error {} m at {} m", error, reference);
+	}
+}
+
+#[test]
+fn projected_point_distance() {
+	for (reference, error) in point_errors(DistanceModel::Projected) {
+		assert!(error < 0.001, "error {} m at {} m", error, reference);
+	}
+}
+
+#[test]
+fn approximate_point_distance() {
+	let errors = point_errors(DistanceModel::Approximate);
+	for (reference, error) in &errors {
+		assert!(*error < 0.005 * reference, "error {} m at {} m", error, reference);
+	}
+	// the reason for the other models: at 2.5 km the approximation is off by meters
+	let max_error = errors.iter().filter(|(reference, _)| *reference <= 2500.0).fold(0.0f64, |a, (_, e)| a.max(*e));
+	assert!(max_error > 1.0, "max error {} m", max_error);
+}
+
+#[test]
+fn geodesic_segment_distance() {
+	for (reference, error) in segment_errors(DistanceModel::Geodesic) {
+		assert!(error < 0.001, "error {} m at {} m", error, reference);
+	}
+}
+
+#[test]
+fn projected_segment_distance() {
+	for (reference, error) in segment_errors(DistanceModel::Projected) {
+		assert!(error < 0.01, "error {} m at {} m", error, reference);
+	}
+}
+
+#[test]
+fn approximate_segment_distance() {
+	for (reference, error) in segment_errors(DistanceModel::Approximate) {
+		assert!(error < 0.005 * reference, "error {} m at {} m", error, reference);
+	}
+}
+
+// the segment tree must find the nearest segment with every model
+#[test]
+fn nearest_segment_in_tree() {
+	let point = Point::new(10.0, 51.0);
+
+	let mut geometry = Geometry::new();
+	let mut segments: Vec<(Point, Point)> = Vec::new();
+	for i in 0..200 {
+		let azimuth = (i * 37 % 360) as f64;
+		let distance = 500.0 + (i * 53 % 2000) as f64;
+		let foot = offset(10.0, 51.0, azimuth, distance);
+		let p0 = offset(foot.x, foot.y, azimuth + 90.0, 50.0);
+		let p1 = offset(foot.x, foot.y, azimuth - 90.0, 50.0);
+		geometry.add_line(vec![p0, p1]);
+		segments.push((p0, p1));
+	}
+
+	let reference = segments.iter()
+		.map(|(p0, p1)| reference_distance(&point, p0, p1))
+		.fold(f64::INFINITY, f64::min);
+
+	let mut collection = Collection::from_geometry(geometry);
+	for model in [DistanceModel::Projected, DistanceModel::Geodesic] {
+		collection.set_distance_model(model);
+		let distance = collection.get_min_distance(&point, 1.0e6);
+		assert!((distance - reference).abs() < 0.001, "{:?}: {} instead of {}", model, distance, reference);
+	}
+}
+
+// far from the equator and over long distances, the bounds of the tree nodes must stay below the distance,
+// though a degree of longitude gets shorter towards the pole: a segment in every direction,
+// and a second one in the opposite direction, only 1 km further away. both are lines of 2 segments,
+// so that the tree has nodes with a bbox around each line
+#[test]
+fn nearest_segment_at_high_latitudes() {
+	let add_segment = |geometry: &mut Geometry, lat: f64, azimuth: f64, distance: f64| {
+		let foot = offset(10.0, lat, azimuth, distance);
+		geometry.add_line(vec![offset(foot.x, foot.y, azimuth + 90.0, 500.0), foot, offset(foot.x, foot.y, azimuth - 90.0, 500.0)]);
+	};
+	for lat in [65.0, 75.0, -80.0] {
+		let point = Point::new(10.0, lat);
+		for distance in [50000.0, 200000.0, 500000.0] {
+			for azimuth in (0..360).step_by(15) {
+				let azimuth = azimuth as f64;
+				for model in [DistanceModel::Projected, DistanceModel::Geodesic] {
+					let single = |azimuth: f64, distance: f64| {
+						let mut geometry = Geometry::new();
+						add_segment(&mut geometry, lat, azimuth, distance);
+						return model_distance(model, &point, geometry);
+					};
+					let nearest = single(azimuth, distance);
+					let brute_force = nearest.min(single(azimuth + 180.0, nearest + 1000.0));
+
+					let mut geometry = Geometry::new();
+					add_segment(&mut geometry, lat, azimuth, distance);
+					add_segment(&mut geometry, lat, azimuth + 180.0, nearest + 1000.0);
+					let found = model_distance(model, &point, geometry);
+					assert_eq!(found, brute_force, "{:?} at latitude {}, azimuth {}, {} m", model, lat, azimuth, distance);
+				}
+			}
+		}
+	}
+}
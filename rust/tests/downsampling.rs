@@ -0,0 +1,148 @@
+/*
+	Downsampling: compared to rendering the lower zoom level again, the center sample has to be closer than the box average,
+	min must never be farther, but keep coverages, and "recompute" has to give exactly the rendered tiles on every level.
+*/
+
+use std::fs;
+use std::path::Path;
+use windradabstand::bintile::Compression;
+use windradabstand::encoding::PixelEncoding;
+use windradabstand::layers::{Layer, LayerKind};
+use windradabstand::output::DirectoryOutput;
+use windradabstand::tiles::{merge_tile, render_image, render_tile, tile_bbox, TileCoord, TileOptions};
+use windradabstand::{ChannelInfo, Collection, Downsampling, GeoImage, Geometry, Point, TileFormat};
+
+mod common;
+
+// lines and a point in tile 10/550/334
+fn geometry() -> Geometry {
+	let mut geometry = Geometry::new();
+	geometry.add_line(vec![Point::new(13.41, 52.93), Point::new(13.55, 52.83), Point::new(13.70, 52.90)]);
+	geometry.add_point(Point::new(13.50, 52.95));
+	return geometry;
+}
+
+fn render(size: u32) -> GeoImage {
+	let mut image = GeoImage::with_channels(size, 10, 550, 334, &[ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0)]);
+	image.draw_distances(0, &Collection::from_geometry(geometry()), -500.0, 2000.0, 1);
+	return image;
+}
+
+fn options(folder: &Path, downsampling: Downsampling) -> TileOptions {
+	return TileOptions {
+		output: folder.join("tiles"),
+		folder_bin: folder.join("bin"),
+		size: 32,
+		threads: 1,
+		image_format: TileFormat::Bin(Compression::None),
+		pixel_encoding: PixelEncoding::Rgb8,
+		bin_compression: Compression::None,
+		skip_uniform_tiles: false,
+		downsampling,
+	};
+}
+
+fn load(folder: &Path, tile: TileCoord) -> GeoImage {
+	return GeoImage::load(&GeoImage::calc_path(folder, tile.zoom, tile.y, tile.x, ".bin")).unwrap();
+}
+
+#[test]
+fn compared_to_rendering() {
+	let image = render(256);
+	let reference = render(32);
+	let (data, reference) = (image.channel_data(0), reference.channel_data(0));
+	let mean_error = |downsampling: Downsampling| -> f64 {
+		let scaled = image.scaled_down(32, downsampling);
+		let sum: f64 = scaled.channel_data(0).iter().zip(reference).map(|(a, b)| (a - b).abs() as f64).sum();
+		return sum / reference.len() as f64;
+	};
+	let (error_box, error_center) = (mean_error(Downsampling::Box), mean_error(Downsampling::Center));
+	assert!(error_center < error_box / 4.0, "center {}, box {}", error_center, error_box);
+
+	// min is never farther than the center, besides the half diagonal of a source pixel (normalized)
+	let pixel = 40_075_016.7 * 52.9f32.to_radians().cos() / 1024.0 / 256.0;
+	let half_diagonal = pixel * 2.0f32.sqrt() / 2.0 / 2500.0;
+	let min = image.scaled_down(32, Downsampling::Min);
+	let boxed = image.scaled_down(32, Downsampling::Box);
+	for (i, value) in min.channel_data(0).iter().enumerate() {
+		assert!(*value <= boxed.channel_data(0)[i]);
+		assert!(*value <= reference[i] + half_diagonal, "{} {}", value, reference[i]);
+	}
+
+	// a block of 2×2 pixels: the center is the corner of all of them
+	assert_eq!(image.scaled_down(128, Downsampling::Center), image.scaled_down_clone(128));
+	let center = image.scaled_down(64, Downsampling::Center);
+	let expected = (data[256 + 1] + data[256 + 2] + data[2 * 256 + 1] + data[2 * 256 + 2]) / 4.0;
+	assert_eq!(center.channel_data(0)[0], expected);
+}
+
+#[test]
+fn recompute_every_level() {
+	let folder = common::temp_folder("downsampling-recompute");
+	let filename = folder.join("wohngebaeude.geojson");
+	let geojson = json::object! {
+		"type": "FeatureCollection",
+		"features": [{ "type": "Feature", "properties": {}, "geometry": { "type": "LineString", "coordinates": [[13.41, 52.93], [13.55, 52.83], [13.70, 52.90]] } }],
+	};
+	fs::write(&filename, geojson.dump()).unwrap();
+	let tile = TileCoord { zoom: 9, x: 275, y: 167 };
+	let mut layer = Layer::new("wohngebaeude", &filename, LayerKind::Distance { min_distance: -500.0, max_distance: 2000.0 });
+	layer.load(&tile_bbox(tile)).unwrap();
+	let layers = [layer];
+
+	// a block of 2×2 tiles of zoom level 10, then merged to zoom level 9
+	let options = options(&folder, Downsampling::Recompute);
+	let output = DirectoryOutput::new(&options.output, options.image_format, options.pixel_encoding);
+	let block = tile;
+	render_tile(&layers, &options, &output, block, 2).unwrap();
+	let child = TileCoord { zoom: 10, x: 551, y: 334 };
+	assert_eq!(load(&options.output, child), render_image(&layers, child, 32, 1));
+	assert_eq!(load(&options.output, tile), render_image(&layers, tile, 32, 1));
+	assert_ne!(load(&options.output, tile), render_image(&layers, tile, 64, 1).scaled_down_clone(32));
+	assert_eq!(load(&options.folder_bin, tile), render_image(&layers, tile, 16, 1));
+
+	let parent = TileCoord { zoom: 8, x: 137, y: 83 };
+	merge_tile(&layers, &options, &output, parent).unwrap();
+	assert_eq!(load(&options.output, parent), render_image(&layers, parent, 32, 1));
+
+	// box is the mean of the rendered block
+	let options = self::options(&folder.join("box"), Downsampling::Box);
+	let output = DirectoryOutput::new(&options.output, options.image_format, options.pixel_encoding);
+	render_tile(&layers, &options, &output, block, 2).unwrap();
+	assert_eq!(load(&options.output, tile), render_image(&layers, tile, 64, 1).scaled_down_clone(32));
+	assert_eq!(load(&options.folder_bin, tile), render_image(&layers, tile, 64, 1).scaled_down_clone(16));
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+// min keeps the nearest distance, but for coverages the largest share, so small areas do not vanish on lower zoom levels
+#[test]
+fn min_keeps_coverage() {
+	let folder = common::temp_folder("downsampling-min");
+	let tile = TileCoord { zoom: 9, x: 275, y: 167 };
+	let bbox = tile_bbox(tile);
+	let (width, height) = (bbox.x_max - bbox.x_min, bbox.y_max - bbox.y_min);
+	// a small square, that covers less than a pixel of the thumbnail
+	let (x, y, side) = (bbox.x_min + width * 0.4, bbox.y_min + height * 0.6, width * 0.01);
+	let ring = json::array![[x, y], [x + side, y], [x + side, y + side], [x, y + side], [x, y]];
+	let filename = folder.join("naturschutz.geojson");
+	common::write_geojson(&filename, vec![json::object! { "type": "Feature", "properties": {}, "geometry": { "type": "Polygon", "coordinates": [ring] } }]);
+	let mut layers = [
+		Layer::new("wohngebaeude", &filename, LayerKind::Distance { min_distance: -500.0, max_distance: 2000.0 }),
+		Layer::new("naturschutz", &filename, LayerKind::Coverage),
+	];
+	for layer in layers.iter_mut() {
+		layer.load(&tile_bbox(tile)).unwrap();
+	}
+
+	let options = options(&folder, Downsampling::Min);
+	let output = DirectoryOutput::new(&options.output, options.image_format, options.pixel_encoding);
+	render_tile(&layers, &options, &output, tile, 2).unwrap();
+	let image = render_image(&layers, tile, 64, 1);
+	let thumb = load(&options.folder_bin, tile);
+	assert_eq!(thumb.channel_data(0), image.scaled_down(16, Downsampling::Min).channel_data(0));
+	assert_eq!(thumb.channel_data(1), image.scaled_down(16, Downsampling::Max).channel_data(1));
+	let max_coverage = |image: &GeoImage| image.channel_data(1).iter().cloned().fold(0.0, f32::max);
+	assert!(max_coverage(&thumb) > 0.0);
+	assert_eq!(max_coverage(&thumb), max_coverage(&image));
+	fs::remove_dir_all(&folder).unwrap();
+}
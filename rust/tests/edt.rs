@@ -0,0 +1,140 @@
+/*
+	Distance transform: the edt renderer measures to the centers of rasterised cells, so it has to agree with the segment tree
+	up to half the diagonal of a cell plus 0.5 m, also for geometries outside of the tile, and keep the sign inside of polygons.
+	Benchmark on dense buildings, edt must not be slower than the tree: cargo test --release --test edt -- --ignored --nocapture
+*/
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+use windradabstand::edt::{Renderer, DEFAULT_OVERSAMPLING};
+use windradabstand::layers::{parse_layers, Layer, LayerKind};
+use windradabstand::tiles::{render_image, tile_bbox, TileCoord};
+
+mod common;
+use common::write_geojson;
+
+const RANGE: [f32; 2] = [-300.0, 1500.0];
+
+fn square(x: f64, y: f64, size: f64) -> json::JsonValue {
+	let ring = json::array![[x, y], [x + size, y], [x + size, y + size * 0.6], [x, y + size * 0.6], [x, y]];
+	return json::object! { "type": "Feature", "properties": {}, "geometry": { "type": "Polygon", "coordinates": [ring] } };
+}
+
+// both renderers on the same geometries, the distances in meters
+fn render_both(filename: &Path, tile: TileCoord, size: u32) -> (Vec<f32>, Vec<f32>, [f64; 2]) {
+	let mut layers = Vec::new();
+	let mut durations = [0.0; 2];
+	for renderer in [Renderer::Tree, Renderer::Edt { oversampling: DEFAULT_OVERSAMPLING }] {
+		let mut layer = Layer::new("gebaeude", filename, LayerKind::Distance { min_distance: RANGE[0], max_distance: RANGE[1] });
+		layer.renderer = renderer;
+		layer.load(&tile_bbox(tile)).unwrap();
+		layers.push(layer);
+	}
+	let mut channels = Vec::new();
+	for (layer, duration) in layers.chunks(1).zip(durations.iter_mut()) {
+		let start = Instant::now();
+		let image = render_image(layer, tile, size, 4);
+		*duration = start.elapsed().as_secs_f64();
+		channels.push(image.channel_data(0).iter().map(|value| RANGE[0] + value * (RANGE[1] - RANGE[0])).collect());
+	}
+	let edt = channels.pop().unwrap();
+	return (channels.pop().unwrap(), edt, durations);
+}
+
+// half the diagonal of a cell in meters, at the top of the tile
+fn tolerance(tile: TileCoord, size: u32) -> f32 {
+	let bbox = tile_bbox(tile);
+	let pixel = (bbox.x_max - bbox.x_min) / (size as f64) * 111_319.49 * bbox.y_min.to_radians().cos();
+	return (pixel / (DEFAULT_OVERSAMPLING as f64) * 0.5f64.sqrt()) as f32;
+}
+
+#[test]
+fn same_as_tree() {
+	let folder = common::temp_folder("edt");
+
+	// buildings, a line and a point, partly outside of the tile, about 10 m per pixel
+	let tile = TileCoord { zoom: 14, x: 8806, y: 5372 };
+	let bbox = tile_bbox(tile);
+	let (width, height) = (bbox.x_max - bbox.x_min, bbox.y_max - bbox.y_min);
+	let mut features = Vec::new();
+	for i in 0..12 {
+		let fx = ((i * 7) % 12) as f64 / 12.0 * 1.4 - 0.2;
+		let fy = ((i * 5) % 12) as f64 / 12.0 * 1.4 - 0.2;
+		features.push(square(bbox.x_min + width * fx, bbox.y_min + height * fy, width * (0.01 + 0.02 * (i % 4) as f64)));
+	}
+	let line = json::array![[bbox.x_min - width * 0.1, bbox.y_min + height * 0.9], [bbox.x_max + width * 0.2, bbox.y_min + height * 0.6]];
+	features.push(json::object! { "type": "Feature", "properties": {}, "geometry": { "type": "LineString", "coordinates": line } });
+	let point = json::array![bbox.x_min + width * 0.45, bbox.y_max + height * 0.1];
+	features.push(json::object! { "type": "Feature", "properties": {}, "geometry": { "type": "Point", "coordinates": point } });
+	let filename = folder.join("gebaeude.geojson");
+	write_geojson(&filename, features);
+
+	let size = 128;
+	let (tree, edt, _durations) = render_both(&filename, tile, size);
+	let tolerance = tolerance(tile, size) + 0.5;
+	let mut inside = 0;
+	for (i, (tree, edt)) in tree.iter().zip(&edt).enumerate() {
+		assert!((tree - edt).abs() <= tolerance, "pixel {}: {} instead of {}", i, edt, tree);
+		// on the border the distance of the nearest cell can be 0
+		if tree.abs() > tolerance {
+			assert_eq!(*tree < 0.0, *edt < 0.0, "pixel {}", i);
+		}
+		if *tree < -10.0 {
+			inside += 1;
+		}
+	}
+	assert!(inside > 0);
+
+	// only the approximate distance model, only for distances
+	let arguments = |layer: json::JsonValue| {
+		let mut arguments = json::object! { "renderer": "edt", "layers": [layer] };
+		arguments["layers"][0]["filename"] = filename.to_str().unwrap().into();
+		return arguments;
+	};
+	let layers = parse_layers(&arguments(json::object! { "type": "distance", "min_distance": -300, "max_distance": 1500 })).unwrap();
+	assert_eq!(layers[0].renderer, Renderer::Edt { oversampling: DEFAULT_OVERSAMPLING });
+	assert!(parse_layers(&arguments(json::object! { "type": "distance", "min_distance": 0, "max_distance": 10, "distance_model": "geodesic" })).is_err());
+	assert!(parse_layers(&arguments(json::object! { "type": "coverage", "renderer": "tree" })).is_err());
+	assert!(parse_layers(&arguments(json::object! { "type": "distance", "min_distance": 0, "max_distance": 10, "renderer": "fast" })).is_err());
+	let layers = parse_layers(&arguments(json::object! { "type": "distance", "min_distance": 0, "max_distance": 10, "renderer": "tree" })).unwrap();
+	assert_eq!(layers[0].renderer, Renderer::Tree);
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+#[ignore]
+fn benchmark_dense_buildings() {
+	let folder = common::temp_folder("edt-benchmark");
+
+	// 100 × 100 buildings of about 15 m in a town sized tile
+	let tile = TileCoord { zoom: 13, x: 4403, y: 2686 };
+	let bbox = tile_bbox(tile);
+	let (width, height) = (bbox.x_max - bbox.x_min, bbox.y_max - bbox.y_min);
+	let mut features = Vec::new();
+	for i in 0..100 {
+		for j in 0..100 {
+			let jitter = ((i * 31 + j * 17) % 10) as f64 / 40.0;
+			features.push(square(bbox.x_min + width * (i as f64 + jitter) / 100.0, bbox.y_min + height * (j as f64 + jitter) / 100.0, width * 0.005));
+		}
+	}
+	let filename = folder.join("gebaeude.geojson");
+	write_geojson(&filename, features);
+
+	for size in [256, 512] {
+		// the fastest of 3 runs, so that a cold cache does not decide
+		let (tree, edt, mut durations) = render_both(&filename, tile, size);
+		for _ in 0..2 {
+			let (_tree, _edt, again) = render_both(&filename, tile, size);
+			durations = [durations[0].min(again[0]), durations[1].min(again[1])];
+		}
+		let errors: Vec<f32> = tree.iter().zip(&edt).map(|(tree, edt)| (tree - edt).abs()).collect();
+		let max_error = errors.iter().cloned().fold(0.0, f32::max);
+		let mean_error = errors.iter().sum::<f32>() / (errors.len() as f32);
+		println!("size {}: tree {:.3} s, edt {:.3} s, error max {:.2} m, mean {:.2} m, half cell diagonal {:.2} m",
+			size, durations[0], durations[1], max_error, mean_error, tolerance(tile, size));
+		assert!(durations[1] <= durations[0], "size {}: edt {:.3} s, tree {:.3} s", size, durations[1], durations[0]);
+		assert!(max_error <= tolerance(tile, size) + 0.5, "size {}: {:.2} m", size, max_error);
+	}
+	fs::remove_dir_all(&folder).unwrap();
+}
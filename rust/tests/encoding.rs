@@ -0,0 +1,95 @@
+/*
+	Pixel encodings: every encoding, decoded with the weights and offset of its metadata, has to give back the value
+	with the precision of the encoding.
+*/
+
+use image::DynamicImage;
+use windradabstand::encoding::{tile_metadata, PixelEncoding};
+use windradabstand::{ChannelInfo, GeoImage, TileFormat};
+
+mod common;
+
+fn test_image() -> GeoImage {
+	return common::test_image(64, &[
+		ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0),
+		ChannelInfo::new("naturschutz", [0.0, 1.0], 0.0),
+		ChannelInfo::new("strassen", [0.0, 3000.0], 1.0),
+		ChannelInfo::new("hoehe", [0.0, 250.0], 0.0),
+	]);
+}
+
+// the pixel components as numbers: R, G, B or the 16 bit gray value
+fn pixel(image: &DynamicImage, index: usize) -> [f64; 3] {
+	return match image {
+		DynamicImage::ImageRgb8(image) => {
+			let p = &image.as_raw()[index * 3..index * 3 + 3];
+			[p[0] as f64, p[1] as f64, p[2] as f64]
+		},
+		DynamicImage::ImageLuma16(image) => [image.as_raw()[index] as f64, 0.0, 0.0],
+		_ => panic!("unexpected image type"),
+	};
+}
+
+fn check_encoding(encoding: PixelEncoding, tolerance: impl Fn(f64) -> f64) {
+	let image = test_image();
+	let infos = image.channel_infos();
+	let metadata = tile_metadata(TileFormat::Png, encoding, &infos);
+	assert_eq!(metadata["encoding"], encoding.name());
+
+	let files = image.encode_images(&TileFormat::Png, encoding).unwrap();
+	assert_eq!(files.len(), infos.len().div_ceil(encoding.channels_per_image()));
+	let images: Vec<DynamicImage> = files.iter().map(|file| image::load_from_memory(file).unwrap()).collect();
+
+	for (index, info) in infos.iter().enumerate() {
+		let channel = &metadata["channels"][index];
+		assert_eq!(channel["name"], info.name.as_str());
+		let weights: Vec<f64> = channel["weights"].members().map(|weight| weight.as_f64().unwrap()).collect();
+		let offset = channel["offset"].as_f64().unwrap();
+		let decoded = &images[channel["image"].as_usize().unwrap()];
+
+		let range = (info.value_range[1] - info.value_range[0]) as f64;
+		let mut max_error = 0.0f64;
+		for (i, value) in image.channel_data(index).iter().enumerate() {
+			let expected = info.value_range[0] as f64 + (value.clamp(0.0, 1.0) as f64) * range;
+			let p = pixel(decoded, i);
+			let value = weights[0] * p[0] + weights[1] * p[1] + weights[2] * p[2] + offset;
+			max_error = max_error.max((value - expected).abs());
+		}
+		assert!(max_error <= tolerance(range), "{} {}: error {}, range {}", encoding.name(), info.name, max_error, range);
+	}
+}
+
+#[test]
+fn decode_with_metadata() {
+	// truncated
+	check_encoding(PixelEncoding::Rgb8, |range| range / 255.0 * 1.0001);
+	// rounded to half a step
+	check_encoding(PixelEncoding::Rg16, |range| range / 65535.0 * 0.5001);
+	check_encoding(PixelEncoding::Gray16, |range| range / 65535.0 * 0.5001);
+	check_encoding(PixelEncoding::Terrarium, |_range| 0.5 / 256.0 + 1.0e-6);
+	check_encoding(PixelEncoding::Mapbox, |_range| 0.05 + 1.0e-6);
+}
+
+#[test]
+fn more_precise_than_rgb8() {
+	// 256 levels (about 10 m each) with rgb8, 65536 with rg16
+	let image = test_image();
+	let rg16 = image::load_from_memory(&image.encode_images(&TileFormat::Png, PixelEncoding::Rg16).unwrap()[0]).unwrap();
+	let rgb8 = image::load_from_memory(&image.encode_images(&TileFormat::Png, PixelEncoding::Rgb8).unwrap()[0]).unwrap();
+	let count_levels = |image: &DynamicImage, weights: [f64; 3]| -> usize {
+		let mut levels: Vec<u64> = (0..64 * 64).map(|i| {
+			let p = pixel(image, i);
+			return (p[0] * weights[0] + p[1] * weights[1] + p[2] * weights[2]) as u64;
+		}).collect();
+		levels.sort();
+		levels.dedup();
+		return levels.len();
+	};
+	let (levels_rg16, levels_rgb8) = (count_levels(&rg16, [256.0, 1.0, 0.0]), count_levels(&rgb8, [1.0, 0.0, 0.0]));
+	assert!(levels_rgb8 <= 256);
+	assert!(levels_rg16 > 2 * levels_rgb8, "{} {}", levels_rg16, levels_rgb8);
+
+	let gray16 = &image.encode_images(&TileFormat::Png, PixelEncoding::Gray16).unwrap()[0];
+	assert!(matches!(image::load_from_memory(gray16).unwrap(), DynamicImage::ImageLuma16(_)));
+	assert!(image.encode_images(&TileFormat::Webp { near_lossless: 100 }, PixelEncoding::Gray16).is_err());
+}
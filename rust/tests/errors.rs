@@ -0,0 +1,125 @@
+/*
+	Broken input is reported as an Error, the process keeps running.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use windradabstand::encoding::PixelEncoding;
+use windradabstand::output::DirectoryOutput;
+use windradabstand::{Bbox, Collection, Error, GeoImage, TileFormat};
+
+mod common;
+
+fn temp_file(folder: &Path, name: &str, content: &[u8]) -> PathBuf {
+	let filename = folder.join(name);
+	fs::write(&filename, content).unwrap();
+	return filename;
+}
+
+#[test]
+fn missing_files() {
+	let mut collection = Collection::new();
+	let result = collection.fill_from_file(&PathBuf::from("/nonexistent/geometries.geojson"), None);
+	assert!(matches!(result, Err(Error::Io { .. })), "{:?}", result.err());
+
+	let result = collection.fill_from_file(&PathBuf::from("/nonexistent/geometries.gpkg"), None);
+	assert!(matches!(result, Err(Error::Io { .. })), "{:?}", result.err());
+
+	let result = GeoImage::load(&PathBuf::from("/nonexistent/0.bin"));
+	assert!(matches!(result, Err(Error::Io { .. })), "{:?}", result.err());
+}
+
+#[test]
+fn broken_geometries() {
+	let folder = common::temp_folder("errors-geometries");
+	let invalid_json = temp_file(&folder, "invalid.geojson", b"{\"features\": [");
+	let unknown_type = temp_file(&folder, "unknown.geojson", b"{\"features\": [{\"geometry\": {\"type\": \"Blob\"}}]}");
+	let invalid_coordinates = temp_file(&folder, "coordinates.geojson", b"{\"features\": [{\"geometry\": {\"type\": \"Point\", \"coordinates\": [\"a\", 1]}}]}");
+	let not_fgb = temp_file(&folder, "not.fgb", b"no flatgeobuf");
+	let unknown_extension = temp_file(&folder, "geometries.shp", b"");
+
+	for filename in [invalid_json, unknown_type, invalid_coordinates, not_fgb, unknown_extension] {
+		let mut collection = Collection::new();
+		let result = collection.fill_from_file(&filename, None);
+		assert!(matches!(result, Err(Error::Format { .. })), "{}: {:?}", filename.display(), result.err());
+	}
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn corrupt_fgb() {
+	let folder = common::temp_folder("errors-fgb");
+	let bbox = Bbox::from_coordinates(13.3, 52.4, 14.1, 52.9);
+	let load = |filename: &Path, bbox: Option<&Bbox>| Collection::new().fill_from_file(filename, bbox);
+
+	// valid magic bytes, but an empty header
+	let empty_header = temp_file(&folder, "empty_header.fgb", b"fgb\x03fgb\x00\x00\x00\x00\x00");
+	let result = load(&empty_header, None);
+	assert!(matches!(result, Err(Error::Format { .. })), "{:?}", result.err());
+
+	let valid = folder.join("valid.fgb");
+	let geometries = [
+		json::object! { "type": "Polygon", "coordinates": [
+			[[13.5, 52.6], [13.7, 52.6], [13.7, 52.75], [13.5, 52.6]],
+			[[13.6, 52.65], [13.65, 52.65], [13.65, 52.7], [13.6, 52.65]],
+		] },
+		json::object! { "type": "MultiLineString", "coordinates": [[[13.4, 52.5], [13.45, 52.6]], [[13.75, 52.6], [13.8, 52.7]]] },
+		json::object! { "type": "Point", "coordinates": [13.45, 52.55] },
+	];
+	common::formats::write_fgb(&valid, &geometries, 2, 2);
+	let content = fs::read(&valid).unwrap();
+
+	// truncated: the features, that are complete, are read, but nothing may panic
+	for length in 0..content.len() {
+		let truncated = temp_file(&folder, "truncated.fgb", &content[0..length]);
+		for filter in [None, Some(&bbox)] {
+			let result = load(&truncated, filter);
+			assert!(matches!(result, Ok(_) | Err(Error::Format { .. })), "{} bytes: {:?}", length, result.err());
+		}
+	}
+	let result = load(&temp_file(&folder, "truncated.fgb", &content[0..20]), None);
+	assert!(matches!(result, Err(Error::Format { .. })), "{:?}", result.err());
+
+	// garbage: every byte set to 0xff (offsets, sizes, vtables, ring ends and R-tree nodes)
+	for pos in 8..content.len() {
+		let mut garbage = content.clone();
+		garbage[pos] = 0xff;
+		let garbage = temp_file(&folder, "garbage.fgb", &garbage);
+		for filter in [None, Some(&bbox)] {
+			let result = load(&garbage, filter);
+			assert!(matches!(result, Ok(_) | Err(Error::Format { .. })), "byte {}: {:?}", pos, result.err());
+		}
+	}
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn bad_tiles() {
+	let folder = common::temp_folder("errors-tiles");
+	let truncated = temp_file(&folder, "truncated.bin", b"garbage");
+	let result = GeoImage::load(&truncated);
+	assert!(matches!(result, Err(Error::Format { .. })), "{:?}", result.err());
+
+	// the 4 tiles of zoom level 1 must have half the size of the merged tile
+	let tile = GeoImage::new(16, 1, 0, 0, &[0.0]);
+	let infos = tile.channel_infos();
+	let result = GeoImage::merge([Some(tile), None, None, None], &infos, 64, 0, 0, 0);
+	assert!(matches!(result, Err(Error::Tile(_))), "{:?}", result.err());
+
+	let tile = GeoImage::new(32, 1, 1, 0, &[0.0]);
+	let result = GeoImage::merge([Some(tile), None, None, None], &infos, 64, 0, 0, 0);
+	assert!(matches!(result, Err(Error::Tile(_))), "{:?}", result.err());
+
+	let tile = GeoImage::new(32, 1, 1, 1, &[0.0]);
+	let result = GeoImage::merge([None, None, None, Some(tile)], &infos, 64, 0, 0, 0);
+	assert!(result.is_ok());
+
+	// only powers of 2 of the tile size can be split into tiles
+	let output = DirectoryOutput::new(&folder, TileFormat::Png, PixelEncoding::Rgb8);
+	for (size, tile_size) in [(96, 32), (64, 128), (64, 48)] {
+		let image = GeoImage::new(size, 1, 0, 0, &[0.0]);
+		let result = image.export_tile_tree(tile_size, &output, false);
+		assert!(matches!(result, Err(Error::Tile(_))), "{} into {}: {:?}", size, tile_size, result.err());
+	}
+	fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,49 @@
+/*
+	Image export: lossless WebP must contain the same pixels as PNG, near lossless WebP only small differences.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use windradabstand::{ChannelInfo, GeoImage, TileFormat};
+
+mod common;
+
+fn test_image() -> GeoImage {
+	return common::test_image(128, &[
+		ChannelInfo::new("a", [-500.0, 2000.0], 1.0e6),
+		ChannelInfo::new("b", [-500.0, 5000.0], 1.0e6),
+		ChannelInfo::new("c", [0.0, 1.0], 0.0),
+		ChannelInfo::new("d", [-500.0, 20000.0], 1.0e6),
+	]);
+}
+
+fn export(image: &GeoImage, folder: &Path, format: TileFormat) -> Vec<image::RgbImage> {
+	image.export_to(folder, &format).unwrap();
+	let extension = format.extension();
+	// 4 channels are saved as "275.ext" (channels 0-2) and "275-1.ext" (channel 3)
+	return ["275", "275-1"].iter().map(|stem| {
+		let filename = folder.join(format!("9/167/{}{}", stem, extension));
+		return image::open(&filename).unwrap().to_rgb8();
+	}).collect();
+}
+
+#[test]
+fn webp_matches_png() {
+	let folder = common::temp_folder("export");
+	let image = test_image();
+
+	let png = export(&image, &folder, TileFormat::Png);
+	let lossless = export(&image, &folder, TileFormat::Webp { near_lossless: 100 });
+	assert_eq!(png, lossless);
+
+	let near_lossless = export(&image, &folder, TileFormat::Webp { near_lossless: 60 });
+	for (png, webp) in png.iter().zip(&near_lossless) {
+		let max_difference = png.as_raw().iter().zip(webp.as_raw()).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+		assert!(max_difference <= 8, "{}", max_difference);
+	}
+
+	let size = |extension: &str| fs::metadata(PathBuf::from(&folder).join(format!("9/167/275{}", extension))).unwrap().len();
+	assert!(size(".webp") < size(".png"), "webp {} bytes, png {} bytes", size(".webp"), size(".png"));
+
+	fs::remove_dir_all(&folder).unwrap();
+}
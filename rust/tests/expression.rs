@@ -0,0 +1,126 @@
+/*
+	Distance expressions: the parser has to follow the JavaScript precedence, since the expressions are the bodies
+	of the rule functions in config.js, and it has to reject everything else with a readable message.
+*/
+
+use windradabstand::expression::Expression;
+
+fn evaluate(text: &str, h: f32, r: f32) -> f32 {
+	return Expression::parse(text).unwrap().evaluate(h, r);
+}
+
+#[test]
+fn precedence() {
+	assert_eq!(evaluate("1 + 2 * 3", 0.0, 0.0), 7.0);
+	assert_eq!(evaluate("(1 + 2) * 3", 0.0, 0.0), 9.0);
+	assert_eq!(evaluate("10 - 4 - 3", 0.0, 0.0), 3.0);
+	assert_eq!(evaluate("12 / 3 / 2", 0.0, 0.0), 2.0);
+	assert_eq!(evaluate("2 * -3", 0.0, 0.0), -6.0);
+	assert_eq!(evaluate("-h + r", 100.0, 30.0), -70.0);
+	assert_eq!(evaluate("10 * (h + r)", 100.0, 30.0), 1300.0);
+	assert_eq!(evaluate("200 + r * 2", 100.0, 30.0), 260.0);
+	assert_eq!(evaluate("1.5 * (h + r)", 100.0, 30.0), 195.0);
+	// comparisons bind weaker than sums
+	assert_eq!(evaluate("h + r <= 130", 100.0, 30.0), 1.0);
+	assert_eq!(evaluate("h + r < 130", 100.0, 30.0), 0.0);
+}
+
+#[test]
+fn comparisons() {
+	for (text, expected) in [("<", [1.0, 0.0, 0.0]), ("<=", [1.0, 1.0, 0.0]), (">", [0.0, 0.0, 1.0]), (">=", [0.0, 1.0, 1.0]), ("==", [0.0, 1.0, 0.0]), ("!=", [1.0, 0.0, 1.0])] {
+		let expression = Expression::parse(&format!("h {} 100", text)).unwrap();
+		assert_eq!([50.0, 100.0, 150.0].map(|h| expression.evaluate(h, 0.0)), expected, "{}", text);
+	}
+	// JavaScript's strict comparisons
+	assert_eq!(evaluate("h === 100 ? 1 : 2", 100.0, 0.0), 1.0);
+	assert_eq!(evaluate("h !== 100 ? 1 : 2", 100.0, 0.0), 2.0);
+	assert_eq!(evaluate("h === 100 ? 1 : 2", 99.0, 0.0), 2.0);
+}
+
+#[test]
+fn conditionals() {
+	let expression = Expression::parse("(h + r <= 150) ? 750 : 1000").unwrap();
+	assert_eq!(expression.evaluate(100.0, 50.0), 750.0);
+	assert_eq!(expression.evaluate(100.0, 51.0), 1000.0);
+
+	// right associative like in JavaScript
+	let expression = Expression::parse("h < 100 ? 1 : h < 200 ? 2 : 3").unwrap();
+	assert_eq!([50.0, 150.0, 250.0].map(|h| expression.evaluate(h, 0.0)), [1.0, 2.0, 3.0]);
+	let expression = Expression::parse("h < 100 ? h < 50 ? 1 : 2 : 3").unwrap();
+	assert_eq!([25.0, 75.0, 150.0].map(|h| expression.evaluate(h, 0.0)), [1.0, 2.0, 3.0]);
+
+	// the condition and the values are whole sums
+	assert_eq!(evaluate("h + r < 150 ? 700 + 50 : 500 * 2", 100.0, 49.0), 750.0);
+	assert_eq!(evaluate("h + r < 150 ? 700 + 50 : 500 * 2", 100.0, 50.0), 1000.0);
+}
+
+#[test]
+fn min_and_max() {
+	assert_eq!(evaluate("max(400, 2 * (h + r))", 100.0, 50.0), 400.0);
+	assert_eq!(evaluate("max(400, 2 * (h + r))", 200.0, 50.0), 500.0);
+	assert_eq!(evaluate("Math.max(1200, 10 * (h + r))", 100.0, 50.0), 1500.0);
+	assert_eq!(evaluate("Math.min(h, r, 10)", 100.0, 50.0), 10.0);
+	assert_eq!(evaluate("min(h, r)", 100.0, 50.0), 50.0);
+	assert_eq!(evaluate("max(r)", 100.0, 50.0), 50.0);
+	assert_eq!(evaluate("max(min(h, 300), r) * 2", 400.0, 50.0), 600.0);
+	assert_eq!(evaluate("max(400, h + r <= 150 ? 750 : 1000)", 100.0, 50.0), 750.0);
+}
+
+// the .expression of every rule in config.js with h and r (see withExpression), and the results of the JavaScript functions
+#[test]
+fn config_expressions() {
+	let turbines = [(100.0, 50.0), (120.0, 29.0), (166.0, 84.0)];
+	let cases = [
+		("max(r, r * 3)", [150.0, 87.0, 252.0]),
+		("max(400, 10 * (h + r))", [1500.0, 1490.0, 2500.0]),
+		("max(0, 10 * (h + r))", [1500.0, 1490.0, 2500.0]),
+		("Math.max(1200, 10 * (h + r))", [1500.0, 1490.0, 2500.0]),
+		("r", [50.0, 29.0, 84.0]),
+		("max(r, 100)", [100.0, 100.0, 100.0]),
+		("max(0, Math.max(400, 2 * (h + r)))", [400.0, 400.0, 500.0]),
+		("max(400, Math.max(400, 2 * (h + r)))", [400.0, 400.0, 500.0]),
+		("Math.max(400, 2 * (h + r))", [400.0, 400.0, 500.0]),
+		("1.5 * (h + r)", [225.0, 223.5, 375.0]),
+		("max(r, 3 * r)", [150.0, 87.0, 252.0]),
+		("max(400, (h + r <= 150) ? 750 : 1000)", [750.0, 750.0, 1000.0]),
+		("Math.max(1000, 10 * (h + r))", [1500.0, 1490.0, 2500.0]),
+		("max(r, 200)", [200.0, 200.0, 200.0]),
+		("200 + r", [250.0, 229.0, 284.0]),
+		("300 + r", [350.0, 329.0, 384.0]),
+		("max(0, (h + r) < 150 ? 750 : 1000)", [1000.0, 750.0, 1000.0]),
+		// numbers become "() => 400", combined with a default like "max(400, 700)"
+		("max(400, 700)", [700.0, 700.0, 700.0]),
+		("450", [450.0, 450.0, 450.0]),
+	];
+	for (text, expected) in cases {
+		let expression = Expression::parse(text).unwrap();
+		assert_eq!(turbines.map(|(h, r)| expression.evaluate(h, r)), expected, "{}", text);
+	}
+}
+
+#[test]
+fn errors() {
+	let cases = [
+		("", "unexpected end in expression: "),
+		("h +", "unexpected end in expression: h +"),
+		("(h + r", "expected ')', found the end in expression: (h + r"),
+		("h ? 1", "expected ':', found the end in expression: h ? 1"),
+		("max 1", "expected '(', found Number(1.0) in expression: max 1"),
+		("max(1, 2", "expected ')', found the end in expression: max(1, 2"),
+		("x * 2", "unknown name 'x' in expression: x * 2"),
+		("Math.pow(h, 2)", "unknown name 'Math.pow' in expression: Math.pow(h, 2)"),
+		("h # 2", "unexpected character '#' in expression: h # 2"),
+		("1.2.3", "invalid number '1.2.3' in expression: 1.2.3"),
+		("h r", "unexpected Name(\"r\") in expression: h r"),
+		("h + * r", "unexpected Symbol(\"*\") in expression: h + * r"),
+		// the whole function is not accepted, only its body
+		("(h, r) => h", "expected ')', found Symbol(\",\") in expression: (h, r) => h"),
+	];
+	for (text, message) in cases {
+		assert_eq!(Expression::parse(text).err().as_deref(), Some(message), "{}", text);
+	}
+
+	assert_eq!(Expression::from_json(&json::JsonValue::from(400)).unwrap().evaluate(0.0, 0.0), 400.0);
+	assert_eq!(Expression::from_json(&json::JsonValue::from("3 * r")).unwrap().evaluate(0.0, 10.0), 30.0);
+	assert_eq!(Expression::from_json(&json::JsonValue::from(true)).err().as_deref(), Some("expected a number or an expression, found true"));
+}
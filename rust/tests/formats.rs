@@ -0,0 +1,144 @@
+/*
+	GeoPackage and FlatGeobuf give the same geometries as GeoJSON: polygons with holes, multi-geometries
+	and collections, with z and m skipped. With a bbox, the spatial index drops the features outside of it.
+*/
+
+use std::fs;
+use std::path::Path;
+use windradabstand::fgb::fill_from_fgb;
+use windradabstand::gpkg::fill_from_gpkg;
+use windradabstand::{Bbox, Collection, Geometry, Point};
+
+mod common;
+
+// the first 6 are near tile 9/275/167, the last 2 far away
+fn geometries() -> Vec<json::JsonValue> {
+	return vec![
+		json::object! { "type": "Polygon", "coordinates": [
+			[[13.5, 52.6], [13.7, 52.6], [13.7, 52.75], [13.5, 52.75], [13.5, 52.6]],
+			[[13.55, 52.65], [13.55, 52.7], [13.65, 52.7], [13.65, 52.65], [13.55, 52.65]],
+		] },
+		json::object! { "type": "MultiPolygon", "coordinates": [
+			[[[13.9, 52.5], [14.0, 52.5], [13.95, 52.55], [13.9, 52.5]]],
+			[[[13.8, 52.8], [13.9, 52.8], [13.85, 52.85], [13.8, 52.8]]],
+		] },
+		json::object! { "type": "LineString", "coordinates": [[13.4, 52.8], [13.6, 52.85], [13.8, 52.82]] },
+		json::object! { "type": "MultiLineString", "coordinates": [
+			[[13.4, 52.5], [13.45, 52.6]],
+			[[13.75, 52.6], [13.8, 52.7], [13.9, 52.65]],
+		] },
+		json::object! { "type": "Point", "coordinates": [13.45, 52.55] },
+		json::object! { "type": "MultiPoint", "coordinates": [[13.75, 52.5], [13.8, 52.52]] },
+		json::object! { "type": "GeometryCollection", "geometries": [
+			{ "type": "Point", "coordinates": [10.0, 50.0] },
+			{ "type": "Polygon", "coordinates": [[[10.0, 50.1], [10.1, 50.1], [10.05, 50.2], [10.0, 50.1]]] },
+		] },
+		json::object! { "type": "Polygon", "coordinates": [[[8.0, 48.0], [8.1, 48.0], [8.1, 48.1], [8.0, 48.0]]] },
+	];
+}
+
+fn near_bbox() -> Bbox {
+	return Bbox::from_coordinates(13.36, 52.48, 14.06, 52.9);
+}
+
+fn load(filename: &Path) -> Collection {
+	let mut collection = Collection::new();
+	collection.fill_from_file(filename, None).unwrap();
+	return collection;
+}
+
+fn from_geojson(folder: &Path, geometries: &[json::JsonValue]) -> Collection {
+	let filename = folder.join("geometries.geojson");
+	let features = geometries.iter().map(|geometry| json::object! { "type": "Feature", "properties": {}, "geometry": geometry.clone() }).collect();
+	common::write_geojson(&filename, features);
+	return load(&filename);
+}
+
+fn assert_same(collection: &Collection, expected: &Collection, name: &str) {
+	assert_eq!(collection.geometry.point_count(), expected.geometry.point_count(), "{}", name);
+	assert_eq!(format!("{:?}", collection.geometry.bbox()), format!("{:?}", expected.geometry.bbox()), "{}", name);
+	for iy in 0..=40 {
+		for ix in 0..=40 {
+			let point = Point::new(13.35 + ix as f64 * 0.02, 52.45 + iy as f64 * 0.0125);
+			assert_eq!(collection.geometry.contains_point(&point), expected.geometry.contains_point(&point), "{}: inside {:?}", name, point);
+			assert_eq!(collection.get_min_distance(&point, 10000.0), expected.get_min_distance(&point, 10000.0), "{}: distance {:?}", name, point);
+		}
+	}
+}
+
+#[test]
+fn same_as_geojson() {
+	let folder = common::temp_folder("formats-geojson");
+	let polygons: Vec<json::JsonValue> = geometries().into_iter().filter(|geometry| geometry["type"] == "Polygon").collect();
+
+	// mixed types, and only polygons, which fgb stores as the geometry type of the header
+	for geometries in [geometries(), polygons] {
+		let expected = from_geojson(&folder, &geometries);
+		for dimensions in [2, 3, 4] {
+			let gpkg = folder.join("geometries.gpkg");
+			common::formats::write_gpkg(&gpkg, &geometries, dimensions, true);
+			assert_same(&load(&gpkg), &expected, &format!("gpkg, {} dimensions", dimensions));
+
+			let fgb = folder.join("geometries.fgb");
+			common::formats::write_fgb(&fgb, &geometries, dimensions, 16);
+			assert_same(&load(&fgb), &expected, &format!("fgb, {} dimensions", dimensions));
+		}
+	}
+
+	// the hole is not inside
+	let collection = load(&folder.join("geometries.fgb"));
+	assert!(collection.geometry.contains_point(&Point::new(13.52, 52.62)));
+	assert!(!collection.geometry.contains_point(&Point::new(13.6, 52.675)));
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn bbox_filter() {
+	let folder = common::temp_folder("formats-bbox");
+	let bbox = near_bbox();
+	let mut near = Geometry::new();
+	for geometry in &geometries()[0..6] {
+		near.add_json_geometry(geometry).unwrap();
+	}
+	let mut all = Geometry::new();
+	for geometry in &geometries() {
+		all.add_json_geometry(geometry).unwrap();
+	}
+
+	// without bbox or without index everything is read, retain_bbox in Collection drops the rest
+	let gpkg = folder.join("index.gpkg");
+	let gpkg_without_index = folder.join("without_index.gpkg");
+	common::formats::write_gpkg(&gpkg, &geometries(), 2, true);
+	common::formats::write_gpkg(&gpkg_without_index, &geometries(), 2, false);
+	for (filename, filter, expected) in [(&gpkg, Some(&bbox), &near), (&gpkg, None, &all), (&gpkg_without_index, Some(&bbox), &all)] {
+		let mut geometry = Geometry::new();
+		fill_from_gpkg(&mut geometry, filename, filter).unwrap();
+		assert_eq!(geometry.point_count(), expected.point_count(), "{}, {:?}", filename.display(), filter);
+	}
+
+	// node size 2 gives an R-tree with 4 levels
+	for (node_size, expected) in [(2, &near), (16, &near), (0, &all)] {
+		let fgb = folder.join("geometries.fgb");
+		common::formats::write_fgb(&fgb, &geometries(), 3, node_size);
+		let mut geometry = Geometry::new();
+		fill_from_fgb(&mut geometry, &fgb, Some(&bbox)).unwrap();
+		assert_eq!(geometry.point_count(), expected.point_count(), "node size {}", node_size);
+		assert_eq!(format!("{:?}", geometry.bbox()), format!("{:?}", expected.bbox()), "node size {}", node_size);
+
+		let mut geometry = Geometry::new();
+		fill_from_fgb(&mut geometry, &fgb, None).unwrap();
+		assert_eq!(geometry.point_count(), all.point_count(), "node size {}", node_size);
+	}
+
+	// a bbox without features
+	let empty = Bbox::from_coordinates(0.0, 0.0, 1.0, 1.0);
+	let fgb = folder.join("geometries.fgb");
+	common::formats::write_fgb(&fgb, &geometries(), 2, 2);
+	let mut geometry = Geometry::new();
+	fill_from_fgb(&mut geometry, &fgb, Some(&empty)).unwrap();
+	assert_eq!(geometry.point_count(), 0);
+	let mut geometry = Geometry::new();
+	fill_from_gpkg(&mut geometry, &gpkg, Some(&empty)).unwrap();
+	assert_eq!(geometry.point_count(), 0);
+	fs::remove_dir_all(&folder).unwrap();
+}
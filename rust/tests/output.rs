@@ -0,0 +1,239 @@
+/*
+	Tile outputs: MBTiles and PMTiles must contain the same files as a directory, identical tiles only once.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use windradabstand::encoding::PixelEncoding;
+use windradabstand::output::{group_path, open_output, zxy_to_tile_id, DirectoryOutput};
+use windradabstand::tiles::TileCoord;
+use windradabstand::{ChannelInfo, GeoImage, TileFormat};
+
+mod common;
+use common::read_varint;
+
+fn test_folder(name: &str) -> PathBuf {
+	return common::temp_folder(&format!("output-{}", name));
+}
+
+// 4 channels, so that every tile has 2 images
+fn test_image() -> GeoImage {
+	let infos: Vec<ChannelInfo> = ["a", "b", "c", "d"].iter().map(|name| ChannelInfo::new(name, [-500.0, 2000.0], 1.0e6)).collect();
+	return common::test_image(64, &infos);
+}
+
+fn read_u64(buffer: &[u8], offset: usize) -> u64 {
+	return u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+}
+
+// entries of a directory as (tile_id, run_length, length, offset)
+fn read_directory(buffer: &[u8]) -> Vec<(u64, u64, u64, u64)> {
+	let mut position = 0;
+	let count = read_varint(buffer, &mut position) as usize;
+	let mut entries = vec![(0u64, 0u64, 0u64, 0u64); count];
+	let mut tile_id = 0;
+	for entry in entries.iter_mut() {
+		tile_id += read_varint(buffer, &mut position);
+		entry.0 = tile_id;
+	}
+	for entry in entries.iter_mut() {
+		entry.1 = read_varint(buffer, &mut position);
+	}
+	for entry in entries.iter_mut() {
+		entry.2 = read_varint(buffer, &mut position);
+	}
+	for index in 0..count {
+		let value = read_varint(buffer, &mut position);
+		entries[index].3 = if value == 0 { entries[index - 1].3 + entries[index - 1].2 } else { value - 1 };
+	}
+	assert_eq!(position, buffer.len());
+	return entries;
+}
+
+// looks up a tile in a PMTiles file, following the leaf directories
+fn read_pmtile(file: &[u8], tile: TileCoord) -> Option<Vec<u8>> {
+	let tile_id = zxy_to_tile_id(tile);
+	let leaves_offset = read_u64(file, 40) as usize;
+	let data_offset = read_u64(file, 56) as usize;
+	let mut directory = &file[read_u64(file, 8) as usize..(read_u64(file, 8) + read_u64(file, 16)) as usize];
+	loop {
+		let entries = read_directory(directory);
+		let (id, run_length, length, offset) = *entries.iter().rev().find(|entry| entry.0 <= tile_id)?;
+		if run_length == 0 {
+			let start = leaves_offset + offset as usize;
+			directory = &file[start..start + length as usize];
+			continue;
+		}
+		if tile_id >= id + run_length {
+			return None;
+		}
+		let start = data_offset + offset as usize;
+		return Some(file[start..start + length as usize].to_vec());
+	}
+}
+
+#[test]
+fn tile_ids() {
+	let ids: Vec<u64> = [(0, 0, 0), (1, 0, 0), (1, 0, 1), (1, 1, 1), (1, 1, 0), (2, 0, 0), (3, 7, 0)]
+		.iter()
+		.map(|&(zoom, x, y)| zxy_to_tile_id(TileCoord { zoom, x, y }))
+		.collect();
+	assert_eq!(ids, vec![0, 1, 2, 3, 4, 5, 84]);
+
+	// a hilbert curve: consecutive ids are neighbours
+	let mut tiles: Vec<(u64, u32, u32)> = Vec::new();
+	for y in 0..16 {
+		for x in 0..16 {
+			tiles.push((zxy_to_tile_id(TileCoord { zoom: 4, x, y }), x, y));
+		}
+	}
+	tiles.sort();
+	assert_eq!(tiles[0].0, 85);
+	for pair in tiles.windows(2) {
+		assert_eq!(pair[1].0, pair[0].0 + 1);
+		assert_eq!(pair[0].1.abs_diff(pair[1].1) + pair[0].2.abs_diff(pair[1].2), 1);
+	}
+}
+
+#[test]
+fn mbtiles() {
+	let folder = test_folder("mbtiles");
+	let path = folder.join("tiles.mbtiles");
+	let image = test_image();
+	// the tile of zoom level 9 is the scaled down image
+	let images = image.scaled_down_clone(32).encode_images(&TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+
+	let output = open_output(&path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	image.export_tile_tree(32, output.as_ref(), false).unwrap();
+	// an empty tile twice
+	let empty = GeoImage::with_channels(32, 10, 0, 0, &image.channel_infos()).encode_images(&TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	output.write(TileCoord { zoom: 10, x: 0, y: 0 }, &empty).unwrap();
+	output.write(TileCoord { zoom: 10, x: 1, y: 0 }, &empty).unwrap();
+	output.finish().unwrap();
+	assert!(output.contains(TileCoord { zoom: 9, x: 275, y: 167 }).unwrap());
+	assert!(!output.contains(TileCoord { zoom: 9, x: 275, y: 168 }).unwrap());
+
+	for (group, name) in ["tiles.mbtiles", "tiles-1.mbtiles"].iter().enumerate() {
+		let connection = rusqlite::Connection::open(folder.join(name)).unwrap();
+		let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+		assert_eq!(count("SELECT COUNT(*) FROM tiles"), 7);
+		assert_eq!(count("SELECT COUNT(*) FROM images"), count("SELECT COUNT(DISTINCT tile_data) FROM tiles"));
+		assert!(count("SELECT COUNT(*) FROM images") < 7);
+
+		// rows are counted from the bottom
+		let data: Vec<u8> = connection.query_row(
+			"SELECT tile_data FROM tiles WHERE zoom_level = 9 AND tile_column = 275 AND tile_row = 344",
+			[], |row| row.get(0),
+		).unwrap();
+		assert_eq!(data, images[group]);
+
+		let format: String = connection.query_row("SELECT value FROM metadata WHERE name = 'format'", [], |row| row.get(0)).unwrap();
+		assert_eq!(format, "png");
+	}
+
+	// can be continued
+	let output = open_output(&path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	assert!(output.contains(TileCoord { zoom: 10, x: 550, y: 334 }).unwrap());
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+// a tile, whose last group could not be written, is not contained, so a resumed run writes it again
+#[test]
+fn interrupted_write() {
+	let folder = test_folder("interrupted");
+	let tile = TileCoord { zoom: 10, x: 550, y: 334 };
+	let data = test_image().encode_images(&TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+
+	let mbtiles = folder.join("tiles.mbtiles");
+	let png = GeoImage::calc_path(&folder.join("tiles"), tile.zoom, tile.y, tile.x, ".png");
+	for (path, blocked) in [(&mbtiles, group_path(&mbtiles, 1)), (&folder.join("tiles"), group_path(&png, 1))] {
+		// a folder, where group 1 has to be saved
+		fs::create_dir_all(&blocked).unwrap();
+		let output = open_output(path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+		assert!(output.write(tile, &data).is_err(), "{}", path.display());
+		assert!(!output.contains(tile).unwrap(), "{}", path.display());
+		drop(output);
+
+		fs::remove_dir(&blocked).unwrap();
+		let output = open_output(path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+		output.write(tile, &data).unwrap();
+		assert!(output.contains(tile).unwrap(), "{}", path.display());
+	}
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+// the ids of the images are the XXH3 hashes of the data, so a resumed run finds the images of earlier runs
+#[test]
+fn mbtiles_tile_ids() {
+	let folder = test_folder("mbtiles-ids");
+	let path = folder.join("tiles.mbtiles");
+	let data = test_image().encode_images(&TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	let hash = format!("{:032x}", twox_hash::XxHash3_128::oneshot(&data[0]));
+
+	let output = open_output(&path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	output.write(TileCoord { zoom: 10, x: 0, y: 0 }, &data[..1]).unwrap();
+	output.finish().unwrap();
+	let connection = rusqlite::Connection::open(&path).unwrap();
+	let tile_id: String = connection.query_row("SELECT tile_id FROM map WHERE tile_column = 0", [], |row| row.get(0)).unwrap();
+	assert_eq!(tile_id, hash);
+
+	// other data with the same hash, as if it collided: the stored image is compared and not shared
+	connection.execute("UPDATE images SET tile_data = x'00' WHERE tile_id = ?1", [&hash]).unwrap();
+	let output = open_output(&path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	output.write(TileCoord { zoom: 10, x: 1, y: 0 }, &data[..1]).unwrap();
+	output.write(TileCoord { zoom: 10, x: 2, y: 0 }, &data[..1]).unwrap();
+	output.finish().unwrap();
+	let tile = |x: u32| -> (String, Vec<u8>) {
+		return connection.query_row("SELECT tile_id, tile_data FROM map JOIN images USING (tile_id) WHERE tile_column = ?1", [x], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+	};
+	assert_eq!(tile(1), (format!("{}-1", hash), data[0].clone()));
+	assert_eq!(tile(2), tile(1));
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn pmtiles() {
+	let folder = test_folder("pmtiles");
+	let path = folder.join("tiles.pmtiles");
+	let image = test_image();
+
+	let output = open_output(&path, TileFormat::Png, PixelEncoding::Rgb8).unwrap();
+	image.export_tile_tree(16, output.as_ref(), false).unwrap();
+	// many small tiles, so that leaf directories are needed, and runs of identical tiles
+	for x in 0..4096u32 {
+		output.write(TileCoord { zoom: 12, x, y: 0 }, &[x.to_le_bytes().to_vec()]).unwrap();
+		output.write(TileCoord { zoom: 12, x, y: 4095 }, &[vec![1, 2, 3]]).unwrap();
+	}
+	output.finish().unwrap();
+
+	let directory = folder.join("directory");
+	image.export_tile_tree(16, &DirectoryOutput::new(&directory, TileFormat::Png, PixelEncoding::Rgb8), false).unwrap();
+
+	for (group, name) in ["tiles.pmtiles", "tiles-1.pmtiles"].iter().enumerate() {
+		let file = fs::read(folder.join(name)).unwrap();
+		assert_eq!(&file[0..8], b"PMTiles\x03");
+		let root_length = read_u64(&file, 16) as usize;
+		assert!(127 + root_length <= 16384);
+		let tile_count = read_u64(&file, 72);
+		let content_count = read_u64(&file, 88);
+
+		for (dy, dx) in [(0, 0), (1, 2), (3, 3)] {
+			let tile = TileCoord { zoom: 11, x: 275 * 4 + dx, y: 167 * 4 + dy };
+			let filename = GeoImage::calc_path(&directory, tile.zoom, tile.y, tile.x, ".png");
+			let filename = group_path(&filename, group);
+			assert_eq!(read_pmtile(&file, tile).unwrap(), fs::read(&filename).unwrap(), "{} {:?}", name, tile);
+		}
+		assert_eq!(read_pmtile(&file, TileCoord { zoom: 11, x: 0, y: 0 }), None);
+		if group == 0 {
+			assert_eq!(tile_count, 21 + 8192);
+			assert!(read_u64(&file, 48) > 0, "no leaf directories");
+			assert!(content_count <= 21 + 4097);
+			assert_eq!(read_pmtile(&file, TileCoord { zoom: 12, x: 1234, y: 0 }).unwrap(), 1234u32.to_le_bytes());
+			assert_eq!(read_pmtile(&file, TileCoord { zoom: 12, x: 2345, y: 4095 }).unwrap(), [1, 2, 3]);
+		} else {
+			assert_eq!(tile_count, 21);
+		}
+	}
+	assert!(!Path::new(&format!("{}.tmp", path.display())).exists());
+	fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,180 @@
+/*
+	Distance rules per Bundesland: every pixel uses the distance of the state it lies in, also right next to the border,
+	where the geometry is in the other state. Pixels outside of every state are not buildable.
+	The critical height is checked against values, that can be calculated by hand.
+*/
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use windradabstand::rules::{parse_rules, Rules};
+use windradabstand::tiles::{tile_bbox, TileCoord};
+use windradabstand::{ChannelInfo, Collection, DistanceModel, GeoImage, Geometry, Point};
+
+mod common;
+
+const TILE: TileCoord = TileCoord { zoom: 13, x: 4401, y: 2676 };
+const SIZE: u32 = 64;
+
+// the left state (AGS 11) requires 1000 m, the right one (AGS 12) 2 * (h + r) = 500 m
+const DISTANCE_LEFT: f32 = 1000.0;
+const DISTANCE_RIGHT: f32 = 500.0;
+
+struct Setup {
+	states: PathBuf,
+	rules: json::JsonValue,
+	// the border between the states is at x = border_x, both states are above y = border_y
+	border_x: f64,
+	border_y: f64,
+	building: Collection,
+}
+
+// the building is on the border, the lower quarter of the tile is in no state (only water, GF 2)
+// distances has the rule for AGS 11 (left) and AGS 12 (right)
+fn setup(folder: &Path, distances: json::JsonValue) -> Setup {
+	let bbox = tile_bbox(TILE);
+	let border_x = (bbox.x_min + bbox.x_max) / 2.0;
+	let border_y = bbox.y_min + (bbox.y_max - bbox.y_min) / 4.0;
+	let building = [border_x, bbox.y_min + (bbox.y_max - bbox.y_min) * 0.6];
+
+	let rectangle = |x0: f64, y0: f64, x1: f64, y1: f64| json::object! {
+		"type": "Polygon", "coordinates": [[[x0, y0], [x1, y0], [x1, y1], [x0, y1], [x0, y0]]],
+	};
+	let feature = |gf: u32, ags: &str, geometry: json::JsonValue| json::object! {
+		"type": "Feature", "properties": { "GF": gf, "AGS": ags }, "geometry": geometry,
+	};
+	let states = folder.join("bundeslaender.geojson");
+	common::write_geojson(&states, vec![
+		feature(4, "11", rectangle(bbox.x_min - 1.0, border_y, border_x, bbox.y_max + 1.0)),
+		feature(4, "12", rectangle(border_x, border_y, bbox.x_max + 1.0, bbox.y_max + 1.0)),
+		feature(2, "13", rectangle(bbox.x_min - 1.0, bbox.y_min - 1.0, bbox.x_max + 1.0, border_y)),
+	]);
+
+	let buildings = folder.join("wohngebaeude.geojson");
+	common::write_geojson(&buildings, vec![json::object! {
+		"type": "Feature", "properties": {}, "geometry": { "type": "Point", "coordinates": [building[0], building[1]] },
+	}]);
+
+	let rules = json::object! {
+		"type": "rules",
+		"turbines": [{ "hub_height": 150, "rotor_radius": 100 }],
+		"rules": [{ "slug": "wohngebaeude", "filename": buildings.to_str().unwrap(), "distances": distances.clone() }],
+	};
+
+	let mut geometry = Geometry::new();
+	geometry.add_point(Point::new(building[0], building[1]));
+	return Setup { states, rules, border_x, border_y, building: Collection::from_geometry(geometry) };
+}
+
+fn render(setup: &Setup, output: json::JsonValue) -> GeoImage {
+	let mut entry = setup.rules.clone();
+	for (key, value) in output.entries() {
+		entry[key] = value.clone();
+	}
+	let mut rules: Rules = parse_rules(&entry).unwrap();
+	rules.load(&setup.states, &tile_bbox(TILE), DistanceModel::Approximate).unwrap();
+	let mut image = GeoImage::with_channels(SIZE, TILE.zoom, TILE.x, TILE.y, &rules.channel_infos("rules"));
+	rules.draw_to(&mut image, 0, 1);
+	return image;
+}
+
+// margin of every pixel to the distance of its state (NaN outside of the states), and whether it is in the right state
+fn expected_margins(setup: &Setup) -> (Vec<f32>, Vec<bool>) {
+	let infos = [ChannelInfo::new("margin", [0.0, 1.0], 0.0), ChannelInfo::new("right", [0.0, 1.0], 0.0)];
+	let mut image = GeoImage::with_channels(SIZE, TILE.zoom, TILE.x, TILE.y, &infos);
+	image.draw_blocks(0, vec![], 1, |point, _geometries| {
+		if point.y < setup.border_y {
+			return f32::NAN;
+		}
+		let required = if point.x < setup.border_x { DISTANCE_LEFT } else { DISTANCE_RIGHT };
+		return setup.building.get_min_distance(point, 1.0e5) as f32 - required;
+	});
+	image.draw_blocks(1, vec![], 1, |point, _geometries| if point.x < setup.border_x { 0.0 } else { 1.0 });
+	return (image.channel_data(0).to_vec(), image.channel_data(1).iter().map(|value| *value == 1.0).collect());
+}
+
+#[test]
+fn mask_uses_the_state_of_the_pixel() {
+	let folder = common::temp_folder("rules-mask");
+	let setup = setup(&folder, json::object! { "11": DISTANCE_LEFT, "12": "2 * (h + r)" });
+	let image = render(&setup, json::object! { "output": "mask" });
+	let mask = image.channel_data(0);
+
+	// outside, between 500 and 1000 m in the right and in the left state, the rest
+	let mut counts = [0; 4];
+	let (margins, right) = expected_margins(&setup);
+	for (index, margin) in margins.into_iter().enumerate() {
+		if margin.is_nan() {
+			assert_eq!(mask[index], 0.0, "pixel {} is outside of the states", index);
+			counts[0] += 1;
+			continue;
+		}
+		// pixels on the limit may round either way
+		if margin.abs() < 1.0 {
+			continue;
+		}
+		assert_eq!(mask[index], if margin >= 0.0 { 1.0 } else { 0.0 }, "pixel {}, margin {}", index, margin);
+		let distance = margin + if right[index] { DISTANCE_RIGHT } else { DISTANCE_LEFT };
+		// buildable only in the right state, though the building is on the border
+		if (DISTANCE_RIGHT..DISTANCE_LEFT).contains(&distance) {
+			assert_eq!(mask[index], if right[index] { 1.0 } else { 0.0 }, "pixel {}, distance {}", index, distance);
+			counts[if right[index] { 1 } else { 2 }] += 1;
+		} else {
+			counts[3] += 1;
+		}
+	}
+	assert!(counts.iter().all(|count| *count > 20), "{:?}", counts);
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+#[test]
+fn field_is_the_normalized_margin() {
+	let folder = common::temp_folder("rules-field");
+	let setup = setup(&folder, json::object! { "11": DISTANCE_LEFT, "12": "2 * (h + r)" });
+	let image = render(&setup, json::object! { "output": "field", "min_distance": -300, "max_distance": 300 });
+	assert_eq!(image.channel_infos()[0].value_range, [-300.0, 300.0]);
+	let field = image.channel_data(0);
+
+	// outside of the states the margin is -infinity, so the field is 0
+	let (margins, _right) = expected_margins(&setup);
+	for (index, margin) in margins.into_iter().enumerate() {
+		let expected = if margin.is_nan() { 0.0 } else { (margin.clamp(-300.0, 300.0) + 300.0) / 600.0 };
+		assert!((field[index] - expected).abs() < 1.0e-4, "pixel {}: {} instead of {}", index, field[index], expected);
+	}
+	fs::remove_dir_all(&folder).unwrap();
+}
+
+// left: 10 * (h + r), so the critical height is a tenth of the distance
+// right: 750 m up to 150 m height, 1000 m above, so it is 0, 150 or 300 m
+#[test]
+fn critical_height() {
+	let folder = common::temp_folder("rules-critical-height");
+	let setup = setup(&folder, json::object! { "11": "10 * (h + r)", "12": "(h + r <= 150) ? 750 : 1000" });
+	let image = render(&setup, json::object! { "output": "critical_height", "max_height": 300, "rotor_ratio": 0.25 });
+	assert_eq!(image.channel_infos()[0].value_range, [0.0, 300.0]);
+	let heights = image.channel_data(0);
+
+	let mut counts = [0; 4];
+	let (margins, right) = expected_margins(&setup);
+	for (index, margin) in margins.into_iter().enumerate() {
+		let height = heights[index] * 300.0;
+		if margin.is_nan() {
+			assert_eq!(height, 0.0, "pixel {} is outside of the states", index);
+			continue;
+		}
+		// expected_margins uses 500 m for the right and 1000 m for the left state
+		if !right[index] {
+			let distance = margin + DISTANCE_LEFT;
+			assert!((height - (distance / 10.0).min(300.0)).abs() < 0.01, "pixel {}: {} m at {} m", index, height, distance);
+			continue;
+		}
+		let distance = margin + DISTANCE_RIGHT;
+		if ((distance - 750.0).abs() < 1.0) || ((distance - 1000.0).abs() < 1.0) {
+			continue;
+		}
+		let expected = if distance >= 1000.0 { 300.0 } else if distance >= 750.0 { 150.0 } else { 0.0 };
+		assert!((height - expected).abs() < 0.01, "pixel {}: {} m at {} m", index, height, distance);
+		counts[(expected / 150.0) as usize] += 1;
+	}
+	assert!(counts[0..3].iter().all(|count| *count > 20), "{:?}", counts);
+	fs::remove_dir_all(&folder).unwrap();
+}
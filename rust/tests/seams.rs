@@ -0,0 +1,94 @@
+/*
+	Seams: two neighbouring tiles, each rendered with the geometries of its own bbox, have to give exactly
+	the pixels of one render of both, especially along the shared edge.
+*/
+
+use std::fs;
+use std::path::Path;
+use windradabstand::geometry::DistanceModel;
+use windradabstand::layers::{parse_layers, Layer, LayerKind, DEFAULT_HALO};
+use windradabstand::tiles::{render_image, tile_bbox, TileCoord};
+
+mod common;
+use common::write_geojson;
+
+// tile 15/17612/10689 and its northern neighbour, both in 14/8806/5344
+const SOUTH: TileCoord = TileCoord { zoom: 15, x: 17612, y: 10689 };
+const NORTH: TileCoord = TileCoord { zoom: 15, x: 17612, y: 10688 };
+const PARENT: TileCoord = TileCoord { zoom: 14, x: 8806, y: 5344 };
+const SIZE: u32 = 128;
+const MAX_DISTANCE: f32 = 20_000.0;
+
+// meters per degree of latitude on the WGS84 ellipsoid
+fn meridian_degree(lat: f64) -> f64 {
+	let (a, e2) = (6378137.0, 0.00669437999014);
+	let m = a * (1.0 - e2) / (1.0 - e2 * lat.to_radians().sin().powi(2)).powf(1.5);
+	return m * std::f64::consts::PI / 180.0;
+}
+
+fn feature(geometry: json::JsonValue) -> json::JsonValue {
+	return json::object! { "type": "Feature", "properties": {}, "geometry": geometry };
+}
+
+fn layers(folder: &Path, tile: TileCoord) -> Vec<Layer> {
+	let mut distance = Layer::new("wohngebaeude", &folder.join("points.geojson"), LayerKind::Distance { min_distance: -500.0, max_distance: MAX_DISTANCE });
+	distance.distance_model = DistanceModel::Geodesic;
+	let mut layers = vec![distance, Layer::new("fixed", &folder.join("polygons.geojson"), LayerKind::Coverage)];
+	for layer in layers.iter_mut() {
+		layer.load(&tile_bbox(tile)).unwrap();
+	}
+	return layers;
+}
+
+#[test]
+fn neighbouring_tiles() {
+	let folder = common::temp_folder("seams");
+
+	// a point almost max_distance north of the southern tile, so its top row is just in reach
+	let bbox = tile_bbox(SOUTH);
+	let x = (bbox.x_min + bbox.x_max) / 2.0;
+	let y = bbox.y_max + (MAX_DISTANCE as f64 - 5.0) / meridian_degree(bbox.y_max);
+	write_geojson(&folder.join("points.geojson"), vec![feature(json::object! { "type": "Point", "coordinates": [x, y] })]);
+
+	// a polygon crossing the shared edge, ending inside the southern tile
+	let (x0, x1) = (bbox.x_min * 0.7 + bbox.x_max * 0.3, bbox.x_min * 0.4 + bbox.x_max * 0.6);
+	let (y0, y1) = (bbox.y_min * 0.2 + bbox.y_max * 0.8, bbox.y_max + (bbox.y_max - bbox.y_min) * 0.3);
+	let ring = json::array![[x0, y0], [x1, y0], [x1, y1], [x0, y1], [x0, y0]];
+	write_geojson(&folder.join("polygons.geojson"), vec![feature(json::object! { "type": "Polygon", "coordinates": [ring] })]);
+
+	let parent = render_image(&layers(&folder, PARENT), PARENT, 2 * SIZE, 2);
+	let south = render_image(&layers(&folder, SOUTH), SOUTH, SIZE, 2);
+	let north = render_image(&layers(&folder, NORTH), NORTH, SIZE, 2);
+
+	// both tiles are the left column of the parent
+	for channel in 0..2 {
+		let data = parent.channel_data(channel);
+		for (tile, y_offset) in [(&south, SIZE), (&north, 0)] {
+			let tile_data = tile.channel_data(channel);
+			for y in 0..SIZE {
+				for x in 0..SIZE {
+					let expected = data[((y + y_offset) * 2 * SIZE + x) as usize];
+					let value = tile_data[(y * SIZE + x) as usize];
+					assert_eq!(value, expected, "channel {}, tile {:?}, pixel {} {}", channel, tile.tile_coord(), x, y);
+				}
+			}
+		}
+	}
+
+	// the shared edge: the point is in reach of the top row of the southern tile
+	let (top, bottom) = (&south.channel_data(0)[0..SIZE as usize], &north.channel_data(0)[((SIZE - 1) * SIZE) as usize..]);
+	assert!(top.iter().any(|value| *value < 1.0));
+	assert!(top.iter().zip(bottom).all(|(top, bottom)| top > bottom));
+
+	// the halo can be set per layer
+	let filename = folder.join("polygons.geojson");
+	let layer = |halo: json::JsonValue| {
+		let mut arguments = json::object! { "layers": [{ "filename": filename.to_str().unwrap(), "type": "coverage" }] };
+		arguments["layers"][0]["halo"] = halo;
+		return arguments;
+	};
+	assert_eq!(parse_layers(&layer(json::JsonValue::Null)).unwrap()[0].halo, DEFAULT_HALO);
+	assert_eq!(parse_layers(&layer(0.into())).unwrap()[0].halo, 0.0);
+	assert!(parse_layers(&layer((-1).into())).is_err());
+	fs::remove_dir_all(&folder).unwrap();
+}
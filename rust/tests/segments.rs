@@ -0,0 +1,66 @@
+/*
+	Distances to segments: the nearest point of a segment has to be found in the same scaled coordinates
+	as the distance itself, so a point next to the middle of a long diagonal edge gets the distance to that edge.
+	Every edge of a ring is a segment, including the last one back to the first point.
+*/
+
+use std::fs;
+use windradabstand::geometry::*;
+
+mod common;
+
+const DEG2METERS: f64 = 6378137.0 * std::f64::consts::PI / 180.0;
+
+// a collection of one polygon, read from a temporary GeoJSON
+fn collection(name: &str, ring: &[[f64; 2]]) -> Collection {
+	let folder = common::temp_folder(&format!("segments-{}", name));
+	let mut geometry = json::object! { "type": "Polygon", "coordinates": [[]] };
+	for point in ring {
+		geometry["coordinates"][0].push(json::array![point[0], point[1]]).unwrap();
+	}
+	let mut feature = json::object! { "type": "Feature", "properties": {} };
+	feature["geometry"] = geometry;
+	let filename = folder.join("polygon.geojson");
+	common::write_geojson(&filename, vec![feature]);
+
+	let mut collection = Collection::new();
+	collection.fill_from_json(&filename).unwrap();
+	fs::remove_dir_all(&folder).unwrap();
+	return collection;
+}
+
+// reference: dense sampling of the segment, x scaled at the latitude of the point
+fn reference_distance(point: [f64; 2], p0: [f64; 2], p1: [f64; 2]) -> f64 {
+	let scale_x = point[1].to_radians().cos();
+	let mut min_distance = f64::MAX;
+	for i in 0..=100_000 {
+		let t = i as f64 / 100_000.0;
+		let dx = (p0[0] + t * (p1[0] - p0[0]) - point[0]) * scale_x;
+		let dy = p0[1] + t * (p1[1] - p0[1]) - point[1];
+		min_distance = min_distance.min((dx * dx + dy * dy).sqrt() * DEG2METERS);
+	}
+	return min_distance;
+}
+
+#[test]
+fn projection_onto_long_edges() {
+	// a triangle with a diagonal edge of about 26 km
+	let ring = [[13.0, 52.0], [13.2, 52.2], [13.2, 52.0], [13.0, 52.0]];
+	let collection = collection("projection", &ring);
+	for point in [[13.08, 52.12], [13.02, 52.05], [13.15, 52.17]] {
+		let expected = reference_distance(point, ring[0], ring[1]);
+		let distance = collection.get_min_distance(&Point::new(point[0], point[1]), 100_000.0);
+		assert!((distance - expected).abs() < 1.0 + expected * 1.0e-4, "{:?}: {} instead of {}", point, distance, expected);
+	}
+}
+
+#[test]
+fn last_edge_of_rings() {
+	// only the last edge, from the north west to the south west corner, is close to the point
+	let ring = [[13.0, 52.0], [13.01, 52.0], [13.01, 52.01], [13.0, 52.01], [13.0, 52.0]];
+	let collection = collection("last-edge", &ring);
+	let point = [12.999, 52.005];
+	let expected = reference_distance(point, ring[3], ring[4]);
+	let distance = collection.get_min_distance(&Point::new(point[0], point[1]), 10_000.0);
+	assert!((distance - expected).abs() < 1.0, "{} instead of {}", distance, expected);
+}
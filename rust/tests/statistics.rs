@@ -0,0 +1,120 @@
+/*
+	Area statistics: pixel areas have to add up to the area of the sphere, and the buildable area of a square region
+	around a point has to be the square without the circle, also with missing tiles and excluded channels.
+*/
+
+use std::f64::consts::PI;
+use std::fs;
+use windradabstand::bintile::Compression;
+use windradabstand::statistics::{area_statistics, load_regions, Sweep};
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point, TileFormat};
+
+mod common;
+
+// 8 km × 8 km in the middle of tile 11/1101/669, at 52.75°
+const CENTER: (f64, f64) = (13.623, 52.75);
+const HALF: f64 = 4000.0;
+
+fn rectangle(x_min: f64, y_min: f64, x_max: f64, y_max: f64) -> Vec<Point> {
+	return vec![Point::new(x_min, y_min), Point::new(x_max, y_min), Point::new(x_max, y_max), Point::new(x_min, y_max), Point::new(x_min, y_min)];
+}
+
+// the square around a center, half the side in meters
+fn square(center: (f64, f64), half: f64) -> [f64; 4] {
+	let dx = half / (6378137.0 * center.1.to_radians().cos()) * 180.0 / PI;
+	let dy = half / 6378137.0 * 180.0 / PI;
+	return [center.0 - dx, center.1 - dy, center.0 + dx, center.1 + dy];
+}
+
+fn feature(ags: &str, name: &str, gf: u32, bbox: [f64; 4]) -> json::JsonValue {
+	let [x_min, y_min, x_max, y_max] = bbox;
+	return json::object! {
+		"type": "Feature",
+		"properties": { "AGS": ags, "GEN": name, "GF": gf },
+		"geometry": {
+			"type": "Polygon",
+			"coordinates": [[[x_min, y_min], [x_max, y_min], [x_max, y_max], [x_min, y_max], [x_min, y_min]]],
+		},
+	};
+}
+
+#[test]
+fn pixel_areas() {
+	// web mercator ends at 85.0511°
+	let image = GeoImage::new(256, 0, 0, 0, &[]);
+	let sum: f64 = (0..256).map(|y| image.pixel_area(y) * 256.0).sum();
+	let expected = 4.0 * PI * 6378137.0f64.powi(2) * 85.0511287798f64.to_radians().sin();
+	assert!((sum / expected - 1.0).abs() < 1.0e-9, "{} {}", sum, expected);
+
+	// a pixel at 52.75° is smaller than at the equator
+	let image = GeoImage::new(256, 11, 1101, 669, &[]);
+	let equator = GeoImage::new(256, 11, 1101, 1023, &[]);
+	let ratio = image.pixel_area(128) / equator.pixel_area(255);
+	assert!((ratio / 52.75f64.to_radians().cos().powi(2) - 1.0).abs() < 0.01, "{}", ratio);
+}
+
+#[test]
+fn buildable_area_per_region() {
+	let folder = common::temp_folder("statistics");
+
+	// distance to a point in the center, the western half is excluded: as coverage and as coverage with coverage_distance
+	let infos = [
+		ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0),
+		ChannelInfo::new("naturschutz", [0.0, 1.0], 0.0),
+		ChannelInfo::new("naturschutz", [-300.0, 300.0], 1.0),
+		ChannelInfo::new("hoehe", [100.0, 250.0], 0.0),
+	];
+	let mut image = GeoImage::with_channels(512, 11, 1101, 669, &infos);
+	let mut geometry = Geometry::new();
+	geometry.add_point(Point::new(CENTER.0, CENTER.1));
+	image.draw_distances(0, &Collection::from_geometry(geometry), -500.0, 2000.0, 1);
+	let [x_min, y_min, _x_max, y_max] = square(CENTER, HALF);
+	let mut geometry = Geometry::new();
+	geometry.add_polygon(vec![rectangle(x_min - 0.1, y_min - 0.1, CENTER.0, y_max + 0.1)]);
+	let collection = Collection::from_geometry(geometry);
+	image.draw_geometry(1, &collection);
+	image.draw_distances(2, &collection, -300.0, 300.0, 1);
+	image.export_to(&folder, &TileFormat::Bin(Compression::None)).unwrap();
+
+	// a second region in the missing tile to the east, water areas (GF = 2) are ignored
+	let regions = json::object! {
+		"type": "FeatureCollection",
+		"features": [
+			feature("12", "Brandenburg", 4, square(CENTER, HALF)),
+			feature("12", "Brandenburg", 2, square(CENTER, 100.0)),
+			feature("13", "Osten", 4, square((CENTER.0 + 0.176, CENTER.1), 2000.0)),
+		],
+	};
+	let filename = folder.join("regions.geojson");
+	fs::write(&filename, regions.dump()).unwrap();
+	let regions = load_regions(&filename).unwrap();
+	assert_eq!(regions.len(), 2);
+
+	let close = |value: f64, expected: f64| (value / expected - 1.0).abs() < 0.01;
+	let sweep = Sweep { channel: 0, distances: vec![400.0, 1000.0], exclude_channels: vec![] };
+	let statistics = area_statistics(&folder, 11, &regions, &sweep, 2).unwrap();
+	assert_eq!(statistics.tiles, 2);
+	assert_eq!(statistics.missing_tiles, 1);
+	let (region, east) = (&statistics.regions[0], &statistics.regions[1]);
+	assert_eq!((region.ags.as_str(), region.name.as_str()), ("12", "Brandenburg"));
+	assert!(close(region.area, 64.0), "{}", region.area);
+	assert!(close(region.buildable[0], 64.0 - PI * 0.16), "{:?}", region.buildable);
+	assert!(close(region.buildable[1], 64.0 - PI), "{:?}", region.buildable);
+	// far away from everything
+	assert!(close(east.area, 16.0), "{}", east.area);
+	assert_eq!(east.buildable, vec![east.area, east.area]);
+
+	for exclude_channels in [vec![1], vec![2], vec![1, 2]] {
+		let sweep = Sweep { channel: 0, distances: vec![1000.0], exclude_channels: exclude_channels.clone() };
+		let region = &area_statistics(&folder, 11, &regions, &sweep, 1).unwrap().regions[0];
+		assert!(close(region.buildable[0], 32.0 - PI / 2.0), "{:?}: {:?}", exclude_channels, region.buildable);
+	}
+	// neither a share nor a signed distance
+	let sweep = Sweep { channel: 0, distances: vec![1000.0], exclude_channels: vec![3] };
+	assert!(area_statistics(&folder, 11, &regions, &sweep, 1).is_err());
+
+	let sweep = Sweep { channel: 0, distances: vec![3000.0], exclude_channels: vec![] };
+	assert!(area_statistics(&folder, 11, &regions, &sweep, 1).is_err());
+	assert!(area_statistics(&folder, 12, &regions, &Sweep { channel: 0, distances: vec![400.0], exclude_channels: vec![] }, 1).is_err());
+	fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,60 @@
+/*
+	Multi-threaded rendering: the blocks of the threads have to give the same pixels as one thread,
+	also for sizes, that do not split evenly into blocks.
+*/
+
+use windradabstand::tiles::{tile_bbox, TileCoord};
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point};
+
+const TILE: TileCoord = TileCoord { zoom: 10, x: 550, y: 334 };
+
+fn collection() -> Collection {
+	let mut geometry = Geometry::new();
+	geometry.add_line(vec![Point::new(13.41, 52.93), Point::new(13.55, 52.83), Point::new(13.70, 52.90)]);
+	geometry.add_polygon(vec![vec![Point::new(13.5, 52.85), Point::new(13.6, 52.86), Point::new(13.55, 52.95), Point::new(13.5, 52.85)]]);
+	geometry.add_point(Point::new(13.50, 52.95));
+	return Collection::from_geometry(geometry);
+}
+
+fn render(size: u32, threads: usize) -> GeoImage {
+	let mut image = GeoImage::with_channels(size, TILE.zoom, TILE.x, TILE.y, &[ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0)]);
+	image.draw_distances(0, &collection(), -500.0, 2000.0, threads);
+	return image;
+}
+
+#[test]
+fn same_pixels_as_one_thread() {
+	// more threads split the image into smaller blocks: 1200 into 16 blocks of 300 for one thread,
+	// into 64 blocks of 150 for 4 threads, and the recursion in the blocks splits 75 into 38 and 37
+	for size in [256, 1024, 1200] {
+		let single = render(size, 1);
+		for threads in [4, 7] {
+			assert_eq!(render(size, threads), single, "size {}, {} threads", size, threads);
+		}
+	}
+}
+
+// every pixel is calculated once, at its own center
+#[test]
+fn every_pixel_of_uneven_sizes() {
+	let bbox = tile_bbox(TILE);
+	let mercator_y = |lat: f64| (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0).tan().ln();
+	for size in [300, 513, 600] {
+		let infos = [ChannelInfo::new("column", [0.0, 1.0], -1.0), ChannelInfo::new("row", [0.0, 1.0], -1.0)];
+		let mut image = GeoImage::with_channels(size, TILE.zoom, TILE.x, TILE.y, &infos);
+		image.draw_blocks(0, vec![], 4, |point, _geometries| {
+			return ((point.x - bbox.x_min) / (bbox.x_max - bbox.x_min) * size as f64) as f32;
+		});
+		image.draw_blocks(1, vec![], 4, |point, _geometries| {
+			let top = mercator_y(bbox.y_max);
+			return ((top - mercator_y(point.y)) / (top - mercator_y(bbox.y_min)) * size as f64) as f32;
+		});
+		for y in 0..size {
+			for x in 0..size {
+				let index = (x + y * size) as usize;
+				assert!((image.channel_data(0)[index] - (x as f32 + 0.5)).abs() < 0.01, "size {}: column of pixel {}, {}", size, x, y);
+				assert!((image.channel_data(1)[index] - (y as f32 + 0.5)).abs() < 0.01, "size {}: row of pixel {}, {}", size, x, y);
+			}
+		}
+	}
+}
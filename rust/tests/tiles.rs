@@ -0,0 +1,108 @@
+/*
+	Tile lists: the tiles of a bbox like bbox2Tiles in lib/geohelper.js, the center ones first,
+	and a tile, whose images could not be saved, has its bin already, so a resumed run can merge it.
+*/
+
+use std::fs;
+use std::path::Path;
+use windradabstand::bintile::Compression;
+use windradabstand::encoding::PixelEncoding;
+use windradabstand::layers::{Layer, LayerKind};
+use windradabstand::output::TileOutput;
+use windradabstand::tiles::{bbox_to_tiles, list_tiles, render_tile, tile_bbox, TileCoord, TileOptions};
+use windradabstand::{Downsampling, Error, GeoImage, Result, TileFormat};
+
+mod common;
+
+#[test]
+fn tiles_of_bbox() {
+	// tile 10/550/334 to 10/550/335 (y from 334.98 to 335.92), the maximum is exclusive
+	assert_eq!(bbox_to_tiles(&[13.4, 52.5, 13.6, 52.7], 10), [550, 334, 551, 336]);
+	// x from 137.24 to 137.96, y from 83.39 to 84.56
+	assert_eq!(bbox_to_tiles(&[13.0, 52.0, 14.0, 53.0], 8), [137, 83, 138, 85]);
+	assert_eq!(bbox_to_tiles(&[-180.0, -85.0, 180.0, 85.0], 0), [0, 0, 1, 1]);
+
+	// the bbox of a tile, a bit smaller, gives only this tile
+	let bbox = tile_bbox(TileCoord { zoom: 12, x: 2201, y: 1343 });
+	let inner = [bbox.x_min + 1.0e-6, bbox.y_min + 1.0e-6, bbox.x_max - 1.0e-6, bbox.y_max - 1.0e-6];
+	assert_eq!(bbox_to_tiles(&inner, 12), [2201, 1343, 2202, 1344]);
+}
+
+#[test]
+fn list_center_first() {
+	let bbox = [13.0, 52.0, 14.5, 53.0];
+	let [x_min, y_min, x_max, y_max] = bbox_to_tiles(&bbox, 9);
+	let tiles = list_tiles(&bbox, 9);
+	assert_eq!(tiles.len() as u32, (x_max - x_min) * (y_max - y_min));
+
+	// every tile once
+	let mut coordinates: Vec<(u32, u32)> = tiles.iter().map(|tile| (tile.x, tile.y)).collect();
+	coordinates.sort();
+	coordinates.dedup();
+	assert_eq!(coordinates.len(), tiles.len());
+	assert!(tiles.iter().all(|tile| tile.zoom == 9 && (x_min..x_max).contains(&tile.x) && (y_min..y_max).contains(&tile.y)));
+
+	// sorted by the distance to the center
+	let (xc, yc) = ((x_min + x_max) as f64 / 2.0, (y_min + y_max) as f64 / 2.0);
+	let distances: Vec<f64> = tiles.iter().map(|tile| (tile.x as f64 - xc).hypot(tile.y as f64 - yc)).collect();
+	assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", distances);
+}
+
+// z/y/x like the paths and the outputs
+#[test]
+fn printed_tile_order() {
+	let image = GeoImage::new(32, 10, 550, 334, &[0.0]);
+	assert!(image.to_string().starts_with("tile:     10/334/550\n"), "{}", image);
+	assert!(GeoImage::calc_path(Path::new("tiles"), 10, 334, 550, ".png").ends_with("10/334/550.png"));
+}
+
+// every image fails, like an interrupted run
+struct FailingOutput;
+
+impl TileOutput for FailingOutput {
+	fn format(&self) -> TileFormat {
+		return TileFormat::Png;
+	}
+	fn encoding(&self) -> PixelEncoding {
+		return PixelEncoding::Rgb8;
+	}
+	fn write_metadata(&self, _metadata: &json::JsonValue) -> Result<()> {
+		return Ok(());
+	}
+	fn contains(&self, _tile: TileCoord) -> Result<bool> {
+		return Ok(false);
+	}
+	fn write(&self, tile: TileCoord, _images: &[Vec<u8>]) -> Result<()> {
+		return Err(Error::Tile(format!("interrupted at {}/{}/{}", tile.zoom, tile.y, tile.x)));
+	}
+	fn finish(&self) -> Result<()> {
+		return Ok(());
+	}
+}
+
+#[test]
+fn bin_before_images() {
+	let folder = common::temp_folder("tiles");
+	let filename = folder.join("wohngebaeude.geojson");
+	let line = json::array![[13.41, 52.93], [13.55, 52.83], [13.70, 52.90]];
+	common::write_geojson(&filename, vec![json::object! { "type": "Feature", "properties": {}, "geometry": { "type": "LineString", "coordinates": line } }]);
+	let tile = TileCoord { zoom: 9, x: 275, y: 167 };
+	let mut layer = Layer::new("wohngebaeude", &filename, LayerKind::Distance { min_distance: -500.0, max_distance: 2000.0 });
+	layer.load(&tile_bbox(tile)).unwrap();
+
+	let options = TileOptions {
+		output: folder.join("tiles"),
+		folder_bin: folder.join("bin"),
+		size: 32,
+		threads: 1,
+		image_format: TileFormat::Png,
+		pixel_encoding: PixelEncoding::Rgb8,
+		bin_compression: Compression::None,
+		skip_uniform_tiles: false,
+		downsampling: Downsampling::Box,
+	};
+	assert!(render_tile(&[layer], &options, &FailingOutput, tile, 1).is_err());
+	let bin = GeoImage::calc_path(&options.folder_bin, tile.zoom, tile.y, tile.x, ".bin");
+	assert!(Path::new(&bin).is_file());
+	fs::remove_dir_all(&folder).unwrap();
+}
@@ -0,0 +1,98 @@
+/*
+	Uniform tiles: tiles far away from all geometries are uniform default, they can be skipped
+	and a missing tile has to merge exactly like a rendered one.
+*/
+
+use std::fs;
+use windradabstand::encoding::PixelEncoding;
+use windradabstand::output::DirectoryOutput;
+use windradabstand::{ChannelInfo, Collection, GeoImage, Geometry, Point, TileFormat};
+
+mod common;
+
+fn infos() -> Vec<ChannelInfo> {
+	return vec![
+		ChannelInfo::new("wohngebaeude", [-500.0, 2000.0], 1.0),
+		ChannelInfo::new("naturschutz", [0.0, 1.0], 0.0),
+	];
+}
+
+// a small polygon near the top left corner of tile 10/550/334
+fn collection() -> Collection {
+	let mut geometry = Geometry::new();
+	geometry.add_polygon(vec![vec![Point::new(13.37, 52.90), Point::new(13.40, 52.90), Point::new(13.40, 52.88), Point::new(13.37, 52.90)]]);
+	return Collection::from_geometry(geometry);
+}
+
+fn render(size: u32, zoom: u32, x: u32, y: u32) -> GeoImage {
+	let collection = collection();
+	let mut image = GeoImage::with_channels(size, zoom, x, y, &infos());
+	image.draw_distances(0, &collection, -500.0, 2000.0, 1);
+	image.draw_geometry(1, &collection);
+	return image;
+}
+
+#[test]
+fn detect_uniform_tiles() {
+	assert!(!render(32, 10, 550, 334).is_uniform_default());
+	assert!(render(32, 10, 551, 335).is_uniform_default());
+	assert!(GeoImage::with_channels(32, 10, 0, 0, &infos()).is_uniform_default());
+
+	// uniform, but not the fill value
+	let mut image = GeoImage::with_channels(4, 10, 0, 0, &infos());
+	let mut geometry = Geometry::new();
+	geometry.add_polygon(vec![vec![Point::new(-181.0, 80.0), Point::new(-160.0, 80.0), Point::new(-160.0, 89.0), Point::new(-181.0, 89.0), Point::new(-181.0, 80.0)]]);
+	image.draw_geometry(1, &Collection::from_geometry(geometry));
+	assert_eq!(image.channel_data(1), &[1.0; 16]);
+	assert!(!image.is_uniform_default());
+}
+
+#[test]
+fn merge_missing_quadrants() {
+	let tiles = [(550, 334), (551, 334), (550, 335), (551, 335)];
+	let rendered: Vec<GeoImage> = tiles.iter().map(|&(x, y)| render(32, 10, x, y)).collect();
+	assert_eq!(rendered.iter().filter(|tile| tile.is_uniform_default()).count(), 3);
+
+	let merged_all = GeoImage::merge(rendered.iter().map(|tile| Some(tile.scaled_down_clone(16))).collect::<Vec<_>>().try_into().unwrap(), &infos(), 32, 9, 275, 167).unwrap();
+	let merged_skipped = GeoImage::merge(
+		[Some(rendered[0].scaled_down_clone(16)), None, None, None],
+		&infos(), 32, 9, 275, 167,
+	).unwrap();
+	assert_eq!(merged_all, merged_skipped);
+
+	// and one level further down
+	let next_all = GeoImage::merge([None, None, None, Some(merged_all.scaled_down_clone(16))], &infos(), 32, 8, 137, 83).unwrap();
+	let next_skipped = GeoImage::merge([None, None, None, Some(merged_skipped.scaled_down_clone(16))], &infos(), 32, 8, 137, 83).unwrap();
+	assert_eq!(next_all, next_skipped);
+
+	// without any tile the merged one is uniform default, with all channels
+	let empty = GeoImage::merge([None, None, None, None], &infos(), 32, 9, 275, 167).unwrap();
+	assert_eq!(empty.channel_infos(), infos());
+	assert!(empty.is_uniform_default());
+	assert_eq!(empty, GeoImage::with_channels(32, 9, 275, 167, &infos()));
+}
+
+#[test]
+fn skip_uniform_tiles() {
+	let folder = common::temp_folder("uniform");
+
+	let image = render(64, 9, 275, 167);
+	let output = DirectoryOutput::new(&folder, TileFormat::Png, PixelEncoding::Rgb8);
+	image.export_tile_tree(32, &output, true).unwrap();
+	let exists = |path: &str| folder.join(path).is_file();
+	assert!(exists("9/167/275.png"));
+	assert!(exists("10/334/550.png"));
+	assert!(!exists("10/334/551.png"));
+	assert!(!exists("10/335/550.png"));
+	assert!(!exists("10/335/551.png"));
+
+	// nothing at all
+	let empty = GeoImage::with_channels(64, 9, 276, 167, &infos());
+	empty.export_tile_tree(32, &output, true).unwrap();
+	assert!(!folder.join("9/167/276.png").exists());
+	empty.export_tile_tree(32, &output, false).unwrap();
+	assert!(exists("9/167/276.png"));
+	assert!(exists("10/335/553.png"));
+
+	fs::remove_dir_all(&folder).unwrap();
+}